@@ -0,0 +1,386 @@
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    helper::{self, ColumnType, ColumnValue, ColumnValues},
+    logger,
+    notification::NotificationState,
+    settings::{AlertCondition, AlertRule, SettingsState},
+    structs::GlobleError,
+};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Alert {
+    pub rule_id: String,
+    pub item: String,
+    pub message: String,
+    pub value: f64,
+    pub datetime: String,
+}
+
+// Tracks whether a rule/item pair is currently triggered, so we only fire on the rising edge
+// or once the cooldown has passed.
+#[derive(Clone, Debug)]
+struct AlertTrigger {
+    active: bool,
+    last_fired: Instant,
+}
+
+#[derive(Clone, Debug)]
+pub struct AlertEngine {
+    log_file: String,
+    settings: Arc<Mutex<SettingsState>>,
+    notifications: Arc<Mutex<NotificationState>>,
+    triggers: Arc<Mutex<HashMap<String, AlertTrigger>>>,
+    history: Arc<Mutex<Vec<Alert>>>,
+}
+
+impl AlertEngine {
+    pub fn new(
+        settings: Arc<Mutex<SettingsState>>,
+        notifications: Arc<Mutex<NotificationState>>,
+    ) -> Self {
+        AlertEngine {
+            log_file: "alerts.log".to_string(),
+            settings,
+            notifications,
+            triggers: Arc::new(Mutex::new(HashMap::new())),
+            history: Arc::new(Mutex::new(vec![])),
+        }
+    }
+
+    /// Returns the url names of all items that have an item specific rule.
+    pub fn get_watched_items(&self) -> Result<Vec<String>, GlobleError> {
+        let settings = self.settings.lock()?.clone();
+        Ok(settings
+            .alert_rules
+            .iter()
+            .filter(|rule| rule.enabled)
+            .filter_map(|rule| rule.item.clone())
+            .collect())
+    }
+
+    pub fn get_history(&self) -> Result<Vec<Alert>, GlobleError> {
+        Ok(self.history.lock()?.clone())
+    }
+
+    fn get_price(
+        &self,
+        live_orders: &DataFrame,
+        order_type: &str,
+        highest: bool,
+    ) -> Result<Option<i64>, GlobleError> {
+        if live_orders.height() == 0 {
+            return Ok(None);
+        }
+        let prices = match helper::get_column_values(
+            live_orders.clone(),
+            Some(col("order_type").eq(lit(order_type))),
+            "platinum",
+            ColumnType::I64,
+        )? {
+            ColumnValues::I64(values) => values,
+            _ => return Err(GlobleError::OtherError("Expected i64 values".to_string())),
+        };
+        if highest {
+            Ok(prices.into_iter().max())
+        } else {
+            Ok(prices.into_iter().min())
+        }
+    }
+
+    /// Returns the week price shift as a percent of the closed average price.
+    fn get_price_shift_percent(&self, item_stats: &DataFrame) -> Result<Option<f64>, GlobleError> {
+        if item_stats.height() == 0 {
+            return Ok(None);
+        }
+        let shift = match helper::get_column_value(
+            item_stats.clone(),
+            None,
+            "priceShift",
+            ColumnType::F64,
+        )? {
+            ColumnValue::F64(value) => value,
+            _ => return Err(GlobleError::OtherError("Expected f64 values".to_string())),
+        };
+        let closed_avg =
+            match helper::get_column_value(item_stats.clone(), None, "closedAvg", ColumnType::F64)?
+            {
+                ColumnValue::F64(value) => value,
+                _ => return Err(GlobleError::OtherError("Expected f64 values".to_string())),
+            };
+        match (shift, closed_avg) {
+            (Some(shift), Some(closed_avg)) if closed_avg > 0.0 => {
+                Ok(Some(shift / closed_avg * 100.0))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Checks a single rule, returning the measured value and a message if the condition holds.
+    fn check_rule(
+        &self,
+        rule: &AlertRule,
+        item: &str,
+        live_orders: &DataFrame,
+        item_stats: &DataFrame,
+        my_buy_price: Option<i64>,
+    ) -> Result<Option<(f64, String)>, GlobleError> {
+        let lowest_sell = self.get_price(live_orders, "sell", false)?;
+        match &rule.condition {
+            AlertCondition::SellsBelow { price } => match lowest_sell {
+                Some(lowest) if lowest < *price => Ok(Some((
+                    lowest as f64,
+                    format!("{} is listed for {} plat, below {}", item, lowest, price),
+                ))),
+                _ => Ok(None),
+            },
+            AlertCondition::SpreadAbove { spread } => {
+                let highest_buy = self.get_price(live_orders, "buy", true)?;
+                match (lowest_sell, highest_buy) {
+                    (Some(sell), Some(buy)) if sell - buy > *spread => Ok(Some((
+                        (sell - buy) as f64,
+                        format!(
+                            "{} spread is {} plat (sell {}, buy {}), above {}",
+                            item,
+                            sell - buy,
+                            sell,
+                            buy,
+                            spread
+                        ),
+                    ))),
+                    _ => Ok(None),
+                }
+            }
+            AlertCondition::PriceShiftAbove { percent } => {
+                match self.get_price_shift_percent(item_stats)? {
+                    Some(shift) if shift.abs() > *percent => Ok(Some((
+                        shift,
+                        format!(
+                            "{} moved {:.1}% over the last week, above {}%",
+                            item, shift, percent
+                        ),
+                    ))),
+                    _ => Ok(None),
+                }
+            }
+            AlertCondition::ListedBelowMyBuy => match (lowest_sell, my_buy_price) {
+                (Some(lowest), Some(buy)) if lowest < buy => Ok(Some((
+                    lowest as f64,
+                    format!(
+                        "{} is listed for {} plat, below your buy order of {}",
+                        item, lowest, buy
+                    ),
+                ))),
+                _ => Ok(None),
+            },
+        }
+    }
+
+    /// Evaluates all enabled rules for the item against its live orders and price statistics.
+    /// `live_orders` is the DataFrame returned by `get_ordres_by_item` and `item_stats` is the
+    /// item's row of the buy/sell overlap, or the same columns computed from the price history
+    /// for items only an alert watches.
+    pub fn evaluate(
+        &self,
+        item: &str,
+        live_orders: &DataFrame,
+        item_stats: &DataFrame,
+        my_buy_price: Option<i64>,
+    ) -> Result<Vec<Alert>, GlobleError> {
+        let settings = self.settings.lock()?.clone();
+        let mut fired: Vec<Alert> = vec![];
+
+        for rule in settings.alert_rules.iter() {
+            if !rule.enabled {
+                continue;
+            }
+            if let Some(rule_item) = &rule.item {
+                if rule_item != item {
+                    continue;
+                }
+            }
+
+            let key = format!("{}:{}", rule.id, item);
+            let result = self.check_rule(rule, item, live_orders, item_stats, my_buy_price)?;
+            let mut triggers = self.triggers.lock()?;
+            let (value, message) = match result {
+                Some(result) => result,
+                None => {
+                    // Condition cleared, re-arm the rule
+                    if let Some(trigger) = triggers.get_mut(&key) {
+                        trigger.active = false;
+                    }
+                    continue;
+                }
+            };
+
+            let cooldown = Duration::from_secs(rule.cooldown_minutes.max(0) as u64 * 60);
+            let should_fire = match triggers.get(&key) {
+                Some(trigger) => !trigger.active || trigger.last_fired.elapsed() >= cooldown,
+                None => true,
+            };
+            if !should_fire {
+                continue;
+            }
+            triggers.insert(
+                key,
+                AlertTrigger {
+                    active: true,
+                    last_fired: Instant::now(),
+                },
+            );
+            fired.push(Alert {
+                rule_id: rule.id.clone(),
+                item: item.to_string(),
+                message,
+                value,
+                datetime: chrono::Local::now().to_string(),
+            });
+        }
+
+        let notifications = self.notifications.lock()?.clone();
+        for alert in fired.iter() {
+            logger::info(
+                "Alerts",
+                format!("Rule: {}, {}", alert.rule_id, alert.message).as_str(),
+                true,
+                Some(self.log_file.as_str()),
+            );
+            helper::send_message_to_window("live_scraper_alert", Some(json!(alert)));
+            notifications.notify("alert", "Market alert", alert.message.as_str());
+        }
+
+        let mut history = self.history.lock()?;
+        history.extend(fired.clone());
+        // Only keep the most recent alerts around
+        let len = history.len();
+        if len > 200 {
+            history.drain(0..len - 200);
+        }
+        Ok(fired)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::AlertCondition;
+
+    fn engine(rules: Vec<AlertRule>) -> AlertEngine {
+        let mut settings = SettingsState::default();
+        settings.alert_rules = rules;
+        let settings = Arc::new(Mutex::new(settings));
+        let notifications = Arc::new(Mutex::new(NotificationState::new(Arc::clone(&settings))));
+        AlertEngine::new(settings, notifications)
+    }
+
+    fn rule(item: Option<&str>, cooldown_minutes: i64, condition: AlertCondition) -> AlertRule {
+        AlertRule {
+            id: "rule".to_string(),
+            enabled: true,
+            item: item.map(|item| item.to_string()),
+            cooldown_minutes,
+            condition,
+        }
+    }
+
+    fn orders(lowest_sell: i64, highest_buy: i64) -> DataFrame {
+        df!(
+            "order_type" => &["sell", "sell", "buy"],
+            "platinum" => &[lowest_sell, lowest_sell + 10, highest_buy],
+            "username" => &["seller", "other_seller", "buyer"]
+        )
+        .unwrap()
+    }
+
+    fn count(
+        engine: &AlertEngine,
+        item: &str,
+        live_orders: &DataFrame,
+        my_buy: Option<i64>,
+    ) -> usize {
+        engine
+            .evaluate(item, live_orders, &DataFrame::default(), my_buy)
+            .unwrap()
+            .len()
+    }
+
+    #[test]
+    fn fires_on_rising_edge() {
+        let engine = engine(vec![rule(
+            None,
+            60,
+            AlertCondition::SellsBelow { price: 100 },
+        )]);
+        assert_eq!(count(&engine, "loki_prime_set", &orders(90, 50), None), 1);
+        // Still below, the cooldown keeps it quiet
+        assert_eq!(count(&engine, "loki_prime_set", &orders(90, 50), None), 0);
+        // Cleared, then below again fires right away
+        assert_eq!(count(&engine, "loki_prime_set", &orders(120, 50), None), 0);
+        assert_eq!(count(&engine, "loki_prime_set", &orders(95, 50), None), 1);
+        assert_eq!(engine.get_history().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn fires_again_after_cooldown() {
+        let engine = engine(vec![rule(
+            None,
+            0,
+            AlertCondition::SellsBelow { price: 100 },
+        )]);
+        assert_eq!(count(&engine, "loki_prime_set", &orders(90, 50), None), 1);
+        assert_eq!(count(&engine, "loki_prime_set", &orders(90, 50), None), 1);
+    }
+
+    #[test]
+    fn triggers_are_per_item() {
+        let engine = engine(vec![rule(
+            None,
+            60,
+            AlertCondition::SellsBelow { price: 100 },
+        )]);
+        assert_eq!(count(&engine, "loki_prime_set", &orders(90, 50), None), 1);
+        assert_eq!(count(&engine, "nova_prime_set", &orders(90, 50), None), 1);
+    }
+
+    #[test]
+    fn item_rule_ignores_other_items() {
+        let engine = engine(vec![rule(
+            Some("loki_prime_set"),
+            60,
+            AlertCondition::SpreadAbove { spread: 20 },
+        )]);
+        assert_eq!(count(&engine, "nova_prime_set", &orders(90, 50), None), 0);
+        assert_eq!(count(&engine, "loki_prime_set", &orders(90, 50), None), 1);
+        assert_eq!(count(&engine, "loki_prime_set", &orders(60, 50), None), 0);
+    }
+
+    #[test]
+    fn listed_below_my_buy() {
+        let engine = engine(vec![rule(None, 60, AlertCondition::ListedBelowMyBuy)]);
+        assert_eq!(count(&engine, "loki_prime_set", &orders(90, 50), None), 0);
+        assert_eq!(
+            count(&engine, "loki_prime_set", &orders(90, 50), Some(80)),
+            0
+        );
+        assert_eq!(
+            count(&engine, "loki_prime_set", &orders(90, 50), Some(100)),
+            1
+        );
+    }
+
+    #[test]
+    fn disabled_rules_never_fire() {
+        let mut disabled = rule(None, 0, AlertCondition::SellsBelow { price: 100 });
+        disabled.enabled = false;
+        let engine = engine(vec![disabled]);
+        assert_eq!(count(&engine, "loki_prime_set", &orders(90, 50), None), 0);
+    }
+}
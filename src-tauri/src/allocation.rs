@@ -0,0 +1,321 @@
+use serde::{Deserialize, Serialize};
+
+use crate::settings::{AllocationMethod, AllocationSettings};
+
+// Above this many DP cells the greedy allocator is used instead
+const MAX_DP_CELLS: usize = 5_000_000;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BuyCandidate {
+    pub url_name: String,
+    // Empty for an order that is not posted yet
+    pub order_id: String,
+    pub price: i64,
+    // Expected profit of a single unit
+    pub profit: f64,
+    pub quantity: i64,
+    pub priority: f64,
+}
+
+impl BuyCandidate {
+    fn cost(&self) -> i64 {
+        self.price * self.quantity.max(1)
+    }
+    fn value(&self) -> f64 {
+        self.profit * self.quantity.max(1) as f64 * self.priority
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Allocation {
+    // Sum of the priority weighted profits of the selected candidates
+    pub value: f64,
+    pub cost: i64,
+    pub selected: Vec<BuyCandidate>,
+    pub unselected: Vec<BuyCandidate>,
+}
+
+/// Picks the buy orders that give the most priority weighted profit without going over the
+/// budget. Candidates below the minimum profit, or that cost more than the budget on their own,
+/// are never selected.
+pub fn allocate(
+    candidates: Vec<BuyCandidate>,
+    budget: i64,
+    settings: &AllocationSettings,
+) -> Allocation {
+    let (eligible, mut unselected): (Vec<BuyCandidate>, Vec<BuyCandidate>) =
+        candidates.into_iter().partition(|candidate| {
+            candidate.profit >= settings.min_profit
+                && candidate.value() > 0.0
+                && candidate.cost() <= budget
+        });
+
+    let steps = settings.budget_steps.max(1) as usize;
+    let use_dp = settings.method == AllocationMethod::ScaledDp
+        && (eligible.len() + 1) * (steps + 1) <= MAX_DP_CELLS;
+    let selected_flags = if use_dp {
+        scaled_dp(&eligible, budget, steps)
+    } else {
+        greedy(&eligible, budget)
+    };
+
+    let mut selected = vec![];
+    for (candidate, is_selected) in eligible.into_iter().zip(selected_flags) {
+        if is_selected {
+            selected.push(candidate);
+        } else {
+            unselected.push(candidate);
+        }
+    }
+    Allocation {
+        value: selected.iter().map(|candidate| candidate.value()).sum(),
+        cost: selected.iter().map(|candidate| candidate.cost()).sum(),
+        selected,
+        unselected,
+    }
+}
+
+/// 0/1 knapsack over the budget divided into at most `steps` steps. Costs are rounded up to
+/// whole steps, so the selection never goes over the real budget.
+fn scaled_dp(candidates: &[BuyCandidate], budget: i64, steps: usize) -> Vec<bool> {
+    let n = candidates.len();
+    let step = ((budget as f64) / steps as f64).ceil().max(1.0);
+    let capacity = ((budget as f64) / step).floor() as usize;
+    let weights: Vec<usize> = candidates
+        .iter()
+        .map(|candidate| (candidate.cost() as f64 / step).ceil() as usize)
+        .collect();
+
+    let mut best = vec![0.0; capacity + 1];
+    // taken[i][w] tells whether candidate i is part of the best selection at capacity w
+    let mut taken = vec![vec![false; capacity + 1]; n];
+    for (i, candidate) in candidates.iter().enumerate() {
+        let value = candidate.value();
+        for w in (weights[i]..=capacity).rev() {
+            let with = best[w - weights[i]] + value;
+            if with > best[w] {
+                best[w] = with;
+                taken[i][w] = true;
+            }
+        }
+    }
+
+    let mut selected = vec![false; n];
+    let mut w = capacity;
+    for i in (0..n).rev() {
+        if taken[i][w] {
+            selected[i] = true;
+            w -= weights[i];
+        }
+    }
+    selected
+}
+
+/// Takes the candidates by profit per platinum until the budget is used. When the single most
+/// valuable candidate beats the whole greedy selection it is taken alone instead, which keeps
+/// the result within half of the optimum.
+fn greedy(candidates: &[BuyCandidate], budget: i64) -> Vec<bool> {
+    let mut order: Vec<usize> = (0..candidates.len()).collect();
+    let ratio = |i: usize| candidates[i].value() / candidates[i].cost().max(1) as f64;
+    order.sort_by(|a, b| {
+        ratio(*b)
+            .partial_cmp(&ratio(*a))
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    let mut selected = vec![false; candidates.len()];
+    let mut remaining = budget;
+    let mut value = 0.0;
+    for i in order {
+        if candidates[i].cost() <= remaining {
+            selected[i] = true;
+            remaining -= candidates[i].cost();
+            value += candidates[i].value();
+        }
+    }
+
+    let best_single = (0..candidates.len()).max_by(|a, b| {
+        candidates[*a]
+            .value()
+            .partial_cmp(&candidates[*b].value())
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    if let Some(best) = best_single {
+        if candidates[best].value() > value {
+            selected = vec![false; candidates.len()];
+            selected[best] = true;
+        }
+    }
+    selected
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    // Small xorshift generator, keeps the random candidate sets reproducible
+    struct Rng(u64);
+    impl Rng {
+        fn next(&mut self, min: i64, max: i64) -> i64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            min + (self.0 % (max - min + 1) as u64) as i64
+        }
+    }
+
+    fn candidate(index: usize, price: i64, profit: f64, quantity: i64) -> BuyCandidate {
+        BuyCandidate {
+            url_name: format!("item_{}", index),
+            order_id: String::new(),
+            price,
+            profit,
+            quantity,
+            priority: 1.0,
+        }
+    }
+
+    fn random_candidates(rng: &mut Rng, count: usize, price_step: i64) -> Vec<BuyCandidate> {
+        (0..count)
+            .map(|i| {
+                candidate(
+                    i,
+                    rng.next(1, 20) * price_step,
+                    rng.next(1, 50) as f64,
+                    rng.next(1, 3),
+                )
+            })
+            .collect()
+    }
+
+    fn total(candidates: &[BuyCandidate], selected: &[bool]) -> (i64, f64) {
+        candidates
+            .iter()
+            .zip(selected)
+            .filter(|(_, is_selected)| **is_selected)
+            .fold((0, 0.0), |(cost, value), (candidate, _)| {
+                (cost + candidate.cost(), value + candidate.value())
+            })
+    }
+
+    // Best value over every subset that fits the budget
+    fn optimum(candidates: &[BuyCandidate], budget: i64) -> f64 {
+        let mut best = 0.0;
+        for mask in 0..(1u32 << candidates.len()) {
+            let selected: Vec<bool> = (0..candidates.len())
+                .map(|i| mask & (1 << i) != 0)
+                .collect();
+            let (cost, value) = total(candidates, &selected);
+            if cost <= budget && value > best {
+                best = value;
+            }
+        }
+        best
+    }
+
+    #[test]
+    fn scaled_dp_is_exact_with_unit_steps() {
+        let mut rng = Rng(0x2545f4914f6cdd1d);
+        for _ in 0..50 {
+            let candidates = random_candidates(&mut rng, 12, 10);
+            let budget = rng.next(100, 800);
+            let (cost, value) = total(
+                &candidates,
+                &scaled_dp(&candidates, budget, budget as usize),
+            );
+            assert!(cost <= budget);
+            assert_eq!(value, optimum(&candidates, budget));
+        }
+    }
+
+    #[test]
+    fn scaled_dp_is_exact_when_costs_are_whole_steps() {
+        let mut rng = Rng(0x9e3779b97f4a7c15);
+        for _ in 0..50 {
+            // A budget of 1000 in 100 steps is a step of 10 plat
+            let candidates = random_candidates(&mut rng, 12, 10);
+            let (cost, value) = total(&candidates, &scaled_dp(&candidates, 1000, 100));
+            assert!(cost <= 1000);
+            assert_eq!(value, optimum(&candidates, 1000));
+        }
+    }
+
+    #[test]
+    fn scaled_dp_stays_within_budget_with_coarse_steps() {
+        let mut rng = Rng(0xd1b54a32d192ed03);
+        for _ in 0..50 {
+            let candidates = random_candidates(&mut rng, 12, 7);
+            let budget = rng.next(100, 800);
+            let (cost, value) = total(&candidates, &scaled_dp(&candidates, budget, 13));
+            assert!(cost <= budget);
+            assert!(value <= optimum(&candidates, budget));
+        }
+    }
+
+    #[test]
+    fn greedy_is_within_half_of_optimum() {
+        let mut rng = Rng(0x94d049bb133111eb);
+        for _ in 0..50 {
+            let candidates = random_candidates(&mut rng, 12, 10);
+            let budget = rng.next(100, 800);
+            let (cost, value) = total(&candidates, &greedy(&candidates, budget));
+            let best = optimum(&candidates, budget);
+            assert!(cost <= budget);
+            assert!(value <= best);
+            assert!(value * 2.0 >= best);
+        }
+    }
+
+    #[test]
+    fn allocate_skips_unprofitable_and_too_expensive() {
+        let settings = AllocationSettings {
+            min_profit: 5.0,
+            ..AllocationSettings::default()
+        };
+        let candidates = vec![
+            candidate(0, 50, 10.0, 1),
+            candidate(1, 40, 2.0, 1),
+            candidate(2, 500, 100.0, 1),
+            candidate(3, 30, 8.0, 2),
+        ];
+        let allocation = allocate(candidates, 200, &settings);
+        let selected: Vec<&str> = allocation
+            .selected
+            .iter()
+            .map(|candidate| candidate.url_name.as_str())
+            .collect();
+        assert_eq!(selected, vec!["item_0", "item_3"]);
+        assert_eq!(allocation.cost, 110);
+        assert_eq!(allocation.value, 26.0);
+        assert_eq!(allocation.unselected.len(), 2);
+    }
+
+    #[test]
+    fn allocate_large_budget_in_time() {
+        let mut rng = Rng(0xbf58476d1ce4e5b9);
+        let candidates: Vec<BuyCandidate> = (0..400)
+            .map(|i| {
+                candidate(
+                    i,
+                    rng.next(5, 2000),
+                    rng.next(1, 300) as f64,
+                    rng.next(1, 5),
+                )
+            })
+            .collect();
+        let settings = AllocationSettings::default();
+        // The default steps keep 400 candidates on the DP path
+        assert!(401 * (settings.budget_steps as usize + 1) <= MAX_DP_CELLS);
+
+        let start = Instant::now();
+        let allocation = allocate(candidates.clone(), 100_000, &settings);
+        let elapsed = start.elapsed();
+        assert!(elapsed < Duration::from_secs(5), "took {:?}", elapsed);
+        assert!(allocation.cost <= 100_000);
+        assert_eq!(allocation.selected.len() + allocation.unselected.len(), 400);
+
+        let (_, greedy_value) = total(&candidates, &greedy(&candidates, 100_000));
+        assert!(allocation.value >= greedy_value * 0.95);
+    }
+}
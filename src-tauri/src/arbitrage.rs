@@ -0,0 +1,254 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::{
+    cache::CacheState,
+    logger,
+    price_scraper::{ItemPriceSummary, PriceScraper},
+    structs::{GlobleError, Item},
+    wfm_client::WFMClientState,
+};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ArbitragePart {
+    pub url_name: String,
+    pub item_name: String,
+    pub quantity: i64,
+    pub price: f64,
+    pub volume: f64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ArbitrageOpportunity {
+    pub set_url_name: String,
+    pub set_name: String,
+    // "buy_parts" buys every part and sells the complete set,
+    // "buy_set" buys the complete set and sells the parts
+    pub direction: String,
+    pub set_price: f64,
+    pub parts_price: f64,
+    pub profit: f64,
+    // Lowest daily volume of the set and its parts, the slowest item limits the trade
+    pub volume: f64,
+    pub score: f64,
+    // "history" or "live"
+    pub source: String,
+    pub parts: Vec<ArbitragePart>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ArbitrageState {
+    log_file: String,
+    cache: Arc<Mutex<CacheState>>,
+    price_scraper: Arc<Mutex<PriceScraper>>,
+    wfm: Arc<Mutex<WFMClientState>>,
+}
+
+impl ArbitrageState {
+    pub fn new(
+        cache: Arc<Mutex<CacheState>>,
+        price_scraper: Arc<Mutex<PriceScraper>>,
+        wfm: Arc<Mutex<WFMClientState>>,
+    ) -> Self {
+        ArbitrageState {
+            log_file: "arbitrage.log".to_string(),
+            cache,
+            price_scraper,
+            wfm,
+        }
+    }
+
+    /// Returns every set item together with its parts.
+    fn get_sets(&self) -> Result<Vec<(Item, Vec<Item>)>, GlobleError> {
+        let cache = self.cache.lock()?.clone();
+        let items = cache.get_items()?;
+        let mut sets = vec![];
+        for item in items.iter() {
+            if !item.url_name.ends_with("_set") {
+                continue;
+            }
+            let parts: Vec<Item> = item
+                .set_items
+                .clone()
+                .unwrap_or_default()
+                .iter()
+                .filter(|id| **id != item.id)
+                .filter_map(|id| cache.get_item_by_id(id))
+                .collect();
+            if parts.len() < 2 {
+                continue;
+            }
+            sets.push((item.clone(), parts));
+        }
+        Ok(sets)
+    }
+
+    fn create_opportunity(
+        &self,
+        set: &Item,
+        set_price: f64,
+        set_volume: f64,
+        parts: Vec<ArbitragePart>,
+        source: &str,
+    ) -> ArbitrageOpportunity {
+        let parts_price: f64 = parts
+            .iter()
+            .map(|part| part.price * part.quantity as f64)
+            .sum();
+        let volume = parts
+            .iter()
+            .map(|part| part.volume)
+            .fold(set_volume, f64::min);
+        let (direction, profit) = if set_price >= parts_price {
+            ("buy_parts", set_price - parts_price)
+        } else {
+            ("buy_set", parts_price - set_price)
+        };
+        ArbitrageOpportunity {
+            set_url_name: set.url_name.clone(),
+            set_name: set.item_name.clone(),
+            direction: direction.to_string(),
+            set_price,
+            parts_price,
+            profit,
+            volume,
+            score: profit * volume,
+            source: source.to_string(),
+            parts,
+        }
+    }
+
+    /// Compares the price of every set against the price of its parts using price history.
+    pub fn get_opportunities_from_history(
+        &self,
+        min_profit: f64,
+    ) -> Result<Vec<ArbitrageOpportunity>, GlobleError> {
+        let summary: HashMap<String, ItemPriceSummary> =
+            self.price_scraper.lock()?.get_price_summary()?;
+        let mut opportunities = vec![];
+        'sets: for (set, parts) in self.get_sets()? {
+            let set_stats = match summary.get(&set.url_name) {
+                Some(stats) => stats,
+                None => continue,
+            };
+            let mut arbitrage_parts = vec![];
+            for part in parts {
+                let part_stats = match summary.get(&part.url_name) {
+                    Some(stats) => stats,
+                    // Without a price for every part the comparison is meaningless
+                    None => continue 'sets,
+                };
+                arbitrage_parts.push(ArbitragePart {
+                    url_name: part.url_name.clone(),
+                    item_name: part.item_name.clone(),
+                    quantity: part.quantity_for_set.unwrap_or(1),
+                    price: part_stats.median,
+                    volume: part_stats.volume,
+                });
+            }
+            let opportunity = self.create_opportunity(
+                &set,
+                set_stats.median,
+                set_stats.volume,
+                arbitrage_parts,
+                "history",
+            );
+            if opportunity.profit >= min_profit {
+                opportunities.push(opportunity);
+            }
+        }
+        opportunities.sort_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        Ok(opportunities)
+    }
+
+    async fn get_lowest_sell_price(&self, url_name: &str) -> Result<Option<f64>, GlobleError> {
+        let wfm = self.wfm.lock()?.clone();
+        // Sets and prime parts have no ranks
+        let price = wfm.get_lowest_sell_price(url_name, 0).await?;
+        Ok(price.map(|price| price as f64))
+    }
+
+    /// Re-prices the best `limit` history opportunities with the current sell orders.
+    /// This costs one request per set and part, so keep `limit` small.
+    pub async fn get_opportunities_from_live(
+        &self,
+        min_profit: f64,
+        limit: usize,
+    ) -> Result<Vec<ArbitrageOpportunity>, GlobleError> {
+        let candidates = self.get_opportunities_from_history(0.0)?;
+        let cache = self.cache.lock()?.clone();
+        let mut opportunities = vec![];
+        'sets: for candidate in candidates.into_iter().take(limit) {
+            let set = match cache.get_item_by_url_name(&candidate.set_url_name) {
+                Some(set) => set,
+                None => continue,
+            };
+            let set_price = match self.get_lowest_sell_price(&set.url_name).await? {
+                Some(price) => price,
+                None => continue,
+            };
+            let mut parts = vec![];
+            for part in candidate.parts.iter() {
+                let price = match self.get_lowest_sell_price(&part.url_name).await? {
+                    Some(price) => price,
+                    None => continue 'sets,
+                };
+                parts.push(ArbitragePart {
+                    price,
+                    ..part.clone()
+                });
+            }
+            let opportunity =
+                self.create_opportunity(&set, set_price, candidate.volume, parts, "live");
+            logger::info_file(
+                "Arbitrage",
+                format!(
+                    "Set: {}, Direction: {}, Set: {}, Parts: {}, Profit: {}",
+                    opportunity.set_url_name,
+                    opportunity.direction,
+                    opportunity.set_price,
+                    opportunity.parts_price,
+                    opportunity.profit
+                )
+                .as_str(),
+                Some(self.log_file.as_str()),
+            );
+            if opportunity.profit >= min_profit {
+                opportunities.push(opportunity);
+            }
+        }
+        opportunities.sort_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        Ok(opportunities)
+    }
+
+    /// Returns the url names of the items we would have to buy for the best opportunities,
+    /// the parts for "buy_parts" and the set for "buy_set".
+    pub fn get_interesting_items(
+        &self,
+        min_profit: f64,
+        limit: usize,
+    ) -> Result<Vec<String>, GlobleError> {
+        let mut items = vec![];
+        for opportunity in self
+            .get_opportunities_from_history(min_profit)?
+            .into_iter()
+            .take(limit)
+        {
+            if opportunity.direction == "buy_parts" {
+                items.extend(opportunity.parts.iter().map(|part| part.url_name.clone()));
+            } else {
+                items.push(opportunity.set_url_name.clone());
+            }
+        }
+        Ok(items)
+    }
+}
@@ -0,0 +1,425 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+use crate::{
+    database::DatabaseClient,
+    helper, logger,
+    price_scraper::{ItemPriceSummary, PriceScraper},
+    structs::{GlobleError, Invantory, Order},
+    wfm_client::WFMClientState,
+};
+
+/// How the price of a new sell order is computed.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BulkPriceMode {
+    // Undercut the lowest ingame seller by this much platinum
+    LowestSell { undercut: i64 },
+    // The price we paid plus a percentage
+    Markup { percent: f64 },
+    // The median of the price history plus a percentage
+    History { percent: f64 },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkOrderStatus {
+    Done,
+    Skipped,
+    Failed,
+}
+
+/// What happened to a single order, failures are returned as errors.
+#[derive(Clone, Debug)]
+enum BulkOutcome {
+    Done(String),
+    Skipped(String),
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BulkOrderResult {
+    pub item_url: String,
+    pub order_id: Option<String>,
+    pub status: BulkOrderStatus,
+    pub message: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BulkOrderSummary {
+    pub action: String,
+    pub total: usize,
+    pub done: usize,
+    pub skipped: usize,
+    pub failed: usize,
+    pub results: Vec<BulkOrderResult>,
+}
+
+impl BulkOrderSummary {
+    fn new(action: &str, results: Vec<BulkOrderResult>) -> Self {
+        let count = |status| results.iter().filter(|r| r.status == status).count();
+        BulkOrderSummary {
+            action: action.to_string(),
+            total: results.len(),
+            done: count(BulkOrderStatus::Done),
+            skipped: count(BulkOrderStatus::Skipped),
+            failed: count(BulkOrderStatus::Failed),
+            results,
+        }
+    }
+}
+
+fn create_result(
+    item_url: &str,
+    order_id: Option<&String>,
+    result: Result<BulkOutcome, GlobleError>,
+) -> BulkOrderResult {
+    let (status, message) = match result {
+        Ok(BulkOutcome::Done(message)) => (BulkOrderStatus::Done, message),
+        Ok(BulkOutcome::Skipped(message)) => (BulkOrderStatus::Skipped, message),
+        Err(e) => (BulkOrderStatus::Failed, format!("{:?}", e)),
+    };
+    BulkOrderResult {
+        item_url: item_url.to_string(),
+        order_id: order_id.cloned(),
+        status,
+        message,
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct BulkOrderState {
+    log_file: String,
+    wfm: Arc<Mutex<WFMClientState>>,
+    db: Arc<Mutex<DatabaseClient>>,
+    price_scraper: Arc<Mutex<PriceScraper>>,
+}
+
+impl BulkOrderState {
+    pub fn new(
+        wfm: Arc<Mutex<WFMClientState>>,
+        db: Arc<Mutex<DatabaseClient>>,
+        price_scraper: Arc<Mutex<PriceScraper>>,
+    ) -> Self {
+        BulkOrderState {
+            log_file: "bulk_orders.log".to_string(),
+            wfm,
+            db,
+            price_scraper,
+        }
+    }
+
+    fn send_progress(&self, action: &str, current: usize, total: usize, item: &str) {
+        helper::send_message_to_window(
+            "bulk_orders_progress",
+            Some(json!({ "action": action, "current": current, "total": total, "item": item })),
+        );
+    }
+
+    fn finish(&self, action: &str, results: Vec<BulkOrderResult>) -> BulkOrderSummary {
+        let summary = BulkOrderSummary::new(action, results);
+        helper::send_message_to_window(
+            "bulk_orders_complete",
+            Some(json!({
+                "action": action,
+                "total": summary.total,
+                "done": summary.done,
+                "skipped": summary.skipped,
+                "failed": summary.failed,
+            })),
+        );
+        logger::info(
+            "BulkOrders",
+            format!(
+                "Action: {}, Done: {}, Skipped: {}, Failed: {}",
+                action, summary.done, summary.skipped, summary.failed
+            )
+            .as_str(),
+            true,
+            Some(self.log_file.as_str()),
+        );
+        summary
+    }
+
+    async fn get_sell_price(
+        &self,
+        inventory: &Invantory,
+        mode: &BulkPriceMode,
+        min_profit: i64,
+        price_summary: &HashMap<String, ItemPriceSummary>,
+    ) -> Result<Option<i64>, GlobleError> {
+        let price = match mode {
+            BulkPriceMode::LowestSell { undercut } => {
+                let wfm = self.wfm.lock()?.clone();
+                wfm.get_lowest_sell_price(&inventory.item_url, inventory.rank)
+                    .await?
+                    .map(|price| price - undercut)
+            }
+            BulkPriceMode::Markup { percent } => {
+                Some((inventory.price * (1.0 + percent / 100.0)).round() as i64)
+            }
+            BulkPriceMode::History { percent } => price_summary
+                .get(&inventory.item_url)
+                .map(|stats| (stats.median * (1.0 + percent / 100.0)).round() as i64),
+        };
+        // Never sell below what we paid plus the minimum profit
+        let floor = inventory.price.ceil() as i64 + min_profit;
+        Ok(price.map(|price| price.max(floor).max(1)))
+    }
+
+    /// Posts a sell order for every inventory item that does not have one yet.
+    pub async fn post_inventory(
+        &self,
+        mode: BulkPriceMode,
+        min_profit: i64,
+    ) -> Result<BulkOrderSummary, GlobleError> {
+        let action = "post_inventory";
+        let wfm = self.wfm.lock()?.clone();
+        let db = self.db.lock()?.clone();
+        // Ranked variants of an item are separate orders and separate inventory entries
+        let listed: HashSet<(String, i64)> = wfm
+            .get_user_ordres()
+            .await?
+            .sell_orders
+            .into_iter()
+            .map(|order| (order.item.url_name, order.mod_rank.unwrap_or(0)))
+            .collect();
+        let inventorys = db.get_inventorys().await?;
+        let price_summary = match mode {
+            BulkPriceMode::History { .. } => self.price_scraper.lock()?.get_price_summary()?,
+            _ => HashMap::new(),
+        };
+
+        let mut results = vec![];
+        for (index, inventory) in inventorys.iter().enumerate() {
+            self.send_progress(action, index, inventorys.len(), &inventory.item_url);
+            let result = if listed.contains(&(inventory.item_url.clone(), inventory.rank)) {
+                Ok(BulkOutcome::Skipped("Already listed".to_string()))
+            } else {
+                self.post_inventory_item(inventory, &mode, min_profit, &price_summary)
+                    .await
+            };
+            results.push(create_result(&inventory.item_url, None, result));
+        }
+        Ok(self.finish(action, results))
+    }
+
+    async fn post_inventory_item(
+        &self,
+        inventory: &Invantory,
+        mode: &BulkPriceMode,
+        min_profit: i64,
+        price_summary: &HashMap<String, ItemPriceSummary>,
+    ) -> Result<BulkOutcome, GlobleError> {
+        let wfm = self.wfm.lock()?.clone();
+        let db = self.db.lock()?.clone();
+        let price = match self
+            .get_sell_price(inventory, mode, min_profit, price_summary)
+            .await?
+        {
+            Some(price) => price,
+            None => return Ok(BulkOutcome::Skipped("No price available".to_string())),
+        };
+        let rank = Some(inventory.rank as f64).filter(|rank| *rank > 0.0);
+        wfm.post_ordre(
+            &inventory.item_url,
+            &inventory.item_id,
+            "sell",
+            price,
+            inventory.owned,
+            true,
+            rank,
+        )
+        .await?;
+        db.update_inventory_listed_price(inventory.id, Some(price))
+            .await?;
+        Ok(BulkOutcome::Done(format!("Posted for {}", price)))
+    }
+
+    /// Returns our orders of the given type, "sell", "buy" or None for both.
+    async fn get_orders(&self, order_type: Option<&str>) -> Result<Vec<Order>, GlobleError> {
+        let wfm = self.wfm.lock()?.clone();
+        let orders = wfm.get_user_ordres().await?;
+        Ok(orders
+            .sell_orders
+            .into_iter()
+            .chain(orders.buy_orders)
+            .filter(|order| order_type.map_or(true, |t| order.order_type == t))
+            .collect())
+    }
+
+    /// Returns the inventory entry of the sell order, matched on the item and the mod rank.
+    fn find_inventory<'a>(order: &Order, inventorys: &'a [Invantory]) -> Option<&'a Invantory> {
+        if order.order_type != "sell" {
+            return None;
+        }
+        inventorys.iter().find(|inventory| {
+            inventory.item_url == order.item.url_name
+                && inventory.rank == order.mod_rank.unwrap_or(0)
+        })
+    }
+
+    /// `inventory_id` is the inventory entry of a sell order, its listed price follows the order.
+    async fn update_order(
+        &self,
+        order: &Order,
+        platinum: i64,
+        visible: bool,
+        inventory_id: Option<i64>,
+    ) -> Result<BulkOutcome, GlobleError> {
+        let wfm = self.wfm.lock()?.clone();
+        let db = self.db.lock()?.clone();
+        wfm.update_order_listing(
+            &order.id,
+            platinum,
+            order.quantity,
+            visible,
+            &order.item.url_name,
+            &order.item.id,
+            &order.order_type,
+        )
+        .await?;
+        if let Some(inventory_id) = inventory_id.filter(|_| platinum != order.platinum) {
+            db.update_inventory_listed_price(inventory_id, Some(platinum))
+                .await?;
+        }
+        Ok(BulkOutcome::Done(format!(
+            "Updated to {}, visible: {}",
+            platinum, visible
+        )))
+    }
+
+    /// Changes the price of our orders by a percentage, positive raises it.
+    pub async fn reprice_orders(
+        &self,
+        percent: f64,
+        order_type: Option<String>,
+    ) -> Result<BulkOrderSummary, GlobleError> {
+        let action = "reprice_orders";
+        let orders = self.get_orders(order_type.as_deref()).await?;
+        let db = self.db.lock()?.clone();
+        let inventorys = db.get_inventorys().await?;
+        let mut results = vec![];
+        for (index, order) in orders.iter().enumerate() {
+            self.send_progress(action, index, orders.len(), &order.item.url_name);
+            let platinum = ((order.platinum as f64) * (1.0 + percent / 100.0)).round() as i64;
+            let result = if platinum.max(1) == order.platinum {
+                Ok(BulkOutcome::Skipped("Price unchanged".to_string()))
+            } else {
+                let inventory_id =
+                    Self::find_inventory(order, &inventorys).map(|inventory| inventory.id);
+                self.update_order(order, platinum.max(1), order.visible, inventory_id)
+                    .await
+            };
+            results.push(create_result(&order.item.url_name, Some(&order.id), result));
+        }
+        Ok(self.finish(action, results))
+    }
+
+    pub async fn set_orders_visible(
+        &self,
+        visible: bool,
+        order_type: Option<String>,
+    ) -> Result<BulkOrderSummary, GlobleError> {
+        let action = if visible {
+            "show_orders"
+        } else {
+            "hide_orders"
+        };
+        let orders = self.get_orders(order_type.as_deref()).await?;
+        let mut results = vec![];
+        for (index, order) in orders.iter().enumerate() {
+            self.send_progress(action, index, orders.len(), &order.item.url_name);
+            let result = if order.visible == visible {
+                Ok(BulkOutcome::Skipped("Visibility unchanged".to_string()))
+            } else {
+                self.update_order(order, order.platinum, visible, None)
+                    .await
+            };
+            results.push(create_result(&order.item.url_name, Some(&order.id), result));
+        }
+        Ok(self.finish(action, results))
+    }
+
+    /// Closes the orders as traded and books the trade in the inventory, like selling or
+    /// buying a single item from the inventory page does.
+    pub async fn close_orders(
+        &self,
+        order_ids: Vec<String>,
+    ) -> Result<BulkOrderSummary, GlobleError> {
+        let action = "close_orders";
+        let orders: Vec<Order> = self
+            .get_orders(None)
+            .await?
+            .into_iter()
+            .filter(|order| order_ids.contains(&order.id))
+            .collect();
+        let mut results = vec![];
+        for order_id in order_ids.iter() {
+            if !orders.iter().any(|order| &order.id == order_id) {
+                results.push(create_result(
+                    "",
+                    Some(order_id),
+                    Err(GlobleError::OtherError("Order not found".to_string())),
+                ));
+            }
+        }
+        for (index, order) in orders.iter().enumerate() {
+            self.send_progress(action, index, orders.len(), &order.item.url_name);
+            let result = self.close_order(order).await;
+            results.push(create_result(&order.item.url_name, Some(&order.id), result));
+        }
+        Ok(self.finish(action, results))
+    }
+
+    async fn close_order(&self, order: &Order) -> Result<BulkOutcome, GlobleError> {
+        let wfm = self.wfm.lock()?.clone();
+        // The trade is only booked once Warframe Market has closed the order
+        let message = wfm.close_order(&order.id).await?;
+        match self.book_trade(order).await {
+            Ok(()) => Ok(BulkOutcome::Done(message)),
+            Err(e) => Err(GlobleError::OtherError(format!(
+                "Order closed, but the trade could not be booked: {:?}",
+                e
+            ))),
+        }
+    }
+
+    async fn book_trade(&self, order: &Order) -> Result<(), GlobleError> {
+        let db = self.db.lock()?.clone();
+        if order.order_type == "sell" {
+            let inventorys = db.get_inventorys().await?;
+            if let Some(inventory) = Self::find_inventory(order, &inventorys) {
+                db.sell_invantory_entry(inventory.id, false, order.platinum)
+                    .await?;
+            }
+        } else {
+            db.create_inventory_entry(
+                order.item.url_name.clone(),
+                false,
+                1,
+                order.platinum,
+                order.mod_rank.unwrap_or(0),
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    pub async fn delete_buy_orders(&self) -> Result<BulkOrderSummary, GlobleError> {
+        let action = "delete_buy_orders";
+        let wfm = self.wfm.lock()?.clone();
+        let orders = self.get_orders(Some("buy")).await?;
+        let mut results = vec![];
+        for (index, order) in orders.iter().enumerate() {
+            self.send_progress(action, index, orders.len(), &order.item.url_name);
+            let result = wfm
+                .delete_order(&order.id, &order.item.url_name, &order.item.id, "buy")
+                .await
+                .map(BulkOutcome::Done);
+            results.push(create_result(&order.item.url_name, Some(&order.id), result));
+        }
+        Ok(self.finish(action, results))
+    }
+}
@@ -1,28 +1,153 @@
+use reqwest::{header, Client, StatusCode};
+use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{Read, Write};
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 
+use crate::item_search::{self, ItemSearchFilter, ItemSearchResult};
 use crate::structs::{GlobleError, Item};
 use crate::wfm_client::WFMClientState;
 use crate::{helper, logger};
 
+// Bump when the layout of the cache file changes, older files are ignored
+const CACHE_VERSION: i64 = 4;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct TradableItemsCache {
+    version: i64,
+    updated_at: String,
+    relics_etag: Option<String>,
+    wfm_items_hash: String,
+    items: Vec<Item>,
+}
+
+#[derive(Clone, Debug, Default)]
+struct ItemIndex {
+    by_id: HashMap<String, usize>,
+    by_url_name: HashMap<String, usize>,
+    by_name: HashMap<String, usize>,
+}
+
+// The items and their index share one lock, so a lookup never reads a new index against the
+// old items while a refresh swaps them
+#[derive(Clone, Debug, Default)]
+struct ItemCatalogue {
+    items: Vec<Item>,
+    index: ItemIndex,
+}
+
 #[derive(Clone, Debug)]
 pub struct CacheState {
-    pub items: Arc<Mutex<Vec<Item>>>,
+    catalogue: Arc<Mutex<ItemCatalogue>>,
+    relics_etag: Arc<Mutex<Option<String>>>,
+    wfm_items_hash: Arc<Mutex<String>>,
     log_file: PathBuf,
     wfm: Arc<Mutex<WFMClientState>>,
 }
 impl CacheState {
     pub fn new(wfm: Arc<Mutex<WFMClientState>>) -> Self {
-        CacheState {
-            items: Arc::new(Mutex::new(vec![])),
+        let cache = CacheState {
+            catalogue: Arc::new(Mutex::new(ItemCatalogue::default())),
+            relics_etag: Arc::new(Mutex::new(None)),
+            wfm_items_hash: Arc::new(Mutex::new(String::new())),
             log_file: PathBuf::from("cache"),
             wfm,
+        };
+        // Load the items from disk so lookups work before the first refresh finishes
+        match cache.read_from_file() {
+            Ok(true) => {}
+            Ok(false) => logger::info_con("Cache", "No tradable items cache found on disk"),
+            Err(e) => logger::error(
+                "Cache",
+                format!("Read Tradable Items Cache: {:?}", e).as_str(),
+                true,
+                None,
+            ),
+        }
+        cache
+    }
+
+    fn get_file_path() -> PathBuf {
+        let cache_path = helper::get_app_roaming_path().join("cache");
+        if !cache_path.exists() {
+            fs::create_dir_all(&cache_path).unwrap();
         }
+        cache_path.join("tradable_items.json")
     }
 
+    fn read_from_file(&self) -> Result<bool, GlobleError> {
+        let path = Self::get_file_path();
+        if !path.exists() {
+            return Ok(false);
+        }
+        let mut file = File::open(path)?;
+        let mut content = String::new();
+        file.read_to_string(&mut content)?;
+        let cache: TradableItemsCache = serde_json::from_str(&content)?;
+        if cache.version != CACHE_VERSION {
+            return Ok(false);
+        }
+        *self.relics_etag.lock()? = cache.relics_etag;
+        *self.wfm_items_hash.lock()? = cache.wfm_items_hash;
+        self.set_items(cache.items)?;
+        Ok(true)
+    }
+
+    fn save_to_file(&self) -> Result<(), GlobleError> {
+        let cache = TradableItemsCache {
+            version: CACHE_VERSION,
+            updated_at: chrono::Local::now().to_string(),
+            relics_etag: self.relics_etag.lock()?.clone(),
+            wfm_items_hash: self.wfm_items_hash.lock()?.clone(),
+            items: self.get_items()?,
+        };
+        let json = serde_json::to_string(&cache)?;
+        // Write next to the cache and rename it, so a crash never leaves a half written cache
+        let path = Self::get_file_path();
+        let temp_path = path.with_extension("json.tmp");
+        let mut file = File::create(&temp_path)?;
+        file.write_all(json.as_bytes())?;
+        file.sync_all()?;
+        drop(file);
+        fs::rename(temp_path, path)?;
+        Ok(())
+    }
+
+    fn set_items(&self, items: Vec<Item>) -> Result<(), GlobleError> {
+        let mut index = ItemIndex::default();
+        for (i, item) in items.iter().enumerate() {
+            index.by_id.insert(item.id.clone(), i);
+            index.by_url_name.insert(item.url_name.clone(), i);
+            index.by_name.insert(item.item_name.to_lowercase(), i);
+        }
+        *self.catalogue.lock()? = ItemCatalogue { items, index };
+        Ok(())
+    }
+
+    pub fn get_items(&self) -> Result<Vec<Item>, GlobleError> {
+        Ok(self.catalogue.lock()?.items.clone())
+    }
+
+    /// Refreshes the tradable items. When items are already cached the refresh runs in the
+    /// background, otherwise it is awaited so the app has a catalogue to work with.
     pub async fn update_cache(&self) -> Result<bool, GlobleError> {
+        let items = self.get_items()?;
+        if !items.is_empty() {
+            helper::send_message_to_window("update_tradable_items", Some(json!(items)));
+            let cache = self.clone();
+            tauri::async_runtime::spawn(async move {
+                cache.refresh_tradable_items().await;
+            });
+        } else {
+            self.refresh_tradable_items().await;
+        }
+        Ok(true)
+    }
+
+    async fn refresh_tradable_items(&self) {
         match self.update_tradable_items().await {
             Ok(_) => {}
             Err(e) => {
@@ -34,54 +159,141 @@ impl CacheState {
                 );
             }
         }
-        Ok(true)
     }
+
+    /// Fetches the relics.run item info, returns None if it did not change since `etag`.
+    async fn get_relic_item_info(
+        &self,
+        etag: Option<String>,
+    ) -> Result<Option<(HashMap<String, Value>, Option<String>)>, GlobleError> {
+        let mut request = Client::new().get("https://relics.run/history/item_data/item_info.json");
+        if let Some(etag) = etag {
+            request = request.header(header::IF_NONE_MATCH, etag);
+        }
+        let response = request.send().await?;
+        if response.status() == StatusCode::NOT_MODIFIED {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            let status = response.status();
+            let url = response.url().to_string();
+            return Err(GlobleError::HttpError(
+                status,
+                response.text().await.unwrap_or_default(),
+                url,
+            ));
+        }
+        let etag = response
+            .headers()
+            .get(header::ETAG)
+            .and_then(|v| v.to_str().ok())
+            .map(String::from);
+        Ok(Some((response.json().await?, etag)))
+    }
+
     pub async fn update_tradable_items(&self) -> Result<bool, GlobleError> {
         let wfm = self.wfm.lock()?.clone();
         let items = wfm.get_tradable_items().await?;
-        let response: HashMap<String, Value> =
-            reqwest::get("https://relics.run/history/item_data/item_info.json")
-                .await?
-                .json()
-                .await?;
+
+        // Skip the relics.run download when neither source changed
+        let items_hash = get_items_hash(&items);
+        let items_changed = items_hash != *self.wfm_items_hash.lock()?;
+        let etag = if items_changed {
+            None
+        } else {
+            self.relics_etag.lock()?.clone()
+        };
+        let (response, etag) = match self.get_relic_item_info(etag).await? {
+            Some(response) => response,
+            None => {
+                logger::info_con("Cache", "Tradable items are up to date");
+                return Ok(false);
+            }
+        };
 
         let mut new_items: Vec<Item> = Vec::new();
         // Link items with relic data on item_id
         for item in items.clone() {
             let relic_data = response.get(&item.id.clone());
-            if relic_data.is_some() {
+            if let Some(relic_data) = relic_data {
                 let mut new = item.clone();
-                let set_items = get_string_arry_from_json(relic_data.unwrap(), "set_items");
-                let tags = get_string_arry_from_json(relic_data.unwrap(), "tags");
-                let subtypes = get_string_arry_from_json(relic_data.unwrap(), "subtypes");
-                let mod_max_rank = relic_data.unwrap().get("mod_max_rank").unwrap().as_i64();
-                new.set_items = Some(set_items);
-                new.tags = Some(tags);
-                new.subtypes = Some(subtypes);
-                new.mod_max_rank = mod_max_rank;
-                new_items.push(new.clone());
+                new.set_items = Some(get_string_arry_from_json(relic_data, "set_items"));
+                new.tags = Some(get_string_arry_from_json(relic_data, "tags"));
+                new.subtypes = Some(get_string_arry_from_json(relic_data, "subtypes"));
+                new.mod_max_rank = relic_data.get("mod_max_rank").and_then(|v| v.as_i64());
+                new.quantity_for_set = relic_data.get("quantity_for_set").and_then(|v| v.as_i64());
+                new.ducats = relic_data.get("ducats").and_then(|v| v.as_i64());
+                new_items.push(new);
             }
         }
-        let mut sitems = self.items.lock()?;
-        *sitems = new_items.clone();
+        if new_items.is_empty() {
+            return Err(GlobleError::OtherError(
+                "No tradable items could be linked with relic data".to_string(),
+            ));
+        }
+        *self.relics_etag.lock()? = etag;
+        *self.wfm_items_hash.lock()? = items_hash;
+        self.set_items(new_items.clone())?;
+        self.save_to_file()?;
+        logger::info_con(
+            "Cache",
+            format!("Updated {} tradable items", new_items.len()).as_str(),
+        );
         helper::send_message_to_window("update_tradable_items", Some(json!(new_items)));
-        // self.items.store(items, Ordering::SeqCst);
-        // self.items = items;
         Ok(true)
     }
+
+    /// Looks the item up in the index and the items under the same lock.
+    fn get_item(&self, lookup: impl Fn(&ItemIndex) -> Option<usize>) -> Option<Item> {
+        let catalogue = self.catalogue.lock().unwrap();
+        lookup(&catalogue.index).and_then(|i| catalogue.items.get(i).cloned())
+    }
     pub fn get_item_by_url_name(&self, url_name: &str) -> Option<Item> {
-        let items = self.items.lock().unwrap();
-        let item = items.iter().find(|i| i.url_name == url_name);
-        if item.is_some() {
-            return Some(item.unwrap().clone());
-        }
-        None
+        self.get_item(|index| index.by_url_name.get(url_name).cloned())
+    }
+    pub fn get_item_by_id(&self, id: &str) -> Option<Item> {
+        self.get_item(|index| index.by_id.get(id).cloned())
+    }
+    pub fn get_item_by_name(&self, name: &str) -> Option<Item> {
+        let name = name.to_lowercase();
+        self.get_item(|index| index.by_name.get(&name).cloned())
+    }
+
+    pub fn search_items(
+        &self,
+        query: &str,
+        filter: &ItemSearchFilter,
+        limit: usize,
+    ) -> Result<Vec<ItemSearchResult>, GlobleError> {
+        let catalogue = self.catalogue.lock()?;
+        Ok(item_search::search_items(
+            &catalogue.items,
+            query,
+            filter,
+            limit,
+        ))
+    }
+}
+
+/// FNV-1a hash of the sorted item ids and url names, so renamed or replaced items are noticed
+/// too. A fixed algorithm is used because the hash is stored on disk.
+fn get_items_hash(items: &[Item]) -> String {
+    let mut keys: Vec<String> = items
+        .iter()
+        .map(|item| format!("{}:{}", item.id, item.url_name))
+        .collect();
+    keys.sort();
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in keys.join("\n").bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
     }
+    format!("{:016x}", hash)
 }
 
 fn get_string_arry_from_json(json: &Value, key: &str) -> Vec<String> {
     let mut string_vec = vec![];
-    if let Some(array) = json.get(key).unwrap().as_array() {
+    if let Some(array) = json.get(key).and_then(|v| v.as_array()) {
         string_vec = array
             .iter()
             .filter_map(|v| v.as_str())
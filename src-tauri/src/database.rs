@@ -4,7 +4,7 @@ use crate::{
     cache::CacheState,
     helper::{self, ColumnType, ColumnValues},
     logger,
-    structs::{GlobleError, Invantory, Transaction},
+    structs::{GlobleError, Invantory, ItemSchedule, OrderBookSnapshot, Transaction},
     wfm_client::WFMClientState,
 };
 use polars::{
@@ -13,7 +13,7 @@ use polars::{
     series::Series,
 };
 use serde_json::json;
-use sqlx::{migrate::MigrateDatabase, Pool, Row, Sqlite, SqlitePool};
+use sqlx::{migrate::MigrateDatabase, sqlite::SqliteRow, Pool, Row, Sqlite, SqlitePool};
 
 #[derive(Clone, Debug)]
 pub struct DatabaseClient {
@@ -66,12 +66,29 @@ impl DatabaseClient {
             rank integer not null default 0,
             price REAL not null default 0,
             listed_price INT default null,
-            owned INT not null default 1
+            owned INT not null default 1,
+            listed_at TEXT default null
         )",
         )
         .execute(&connection)
         .await
         .unwrap();
+        // Databases created before listings were timestamped
+        let columns: Vec<String> = sqlx::query("PRAGMA table_info(inventorys)")
+            .fetch_all(&connection)
+            .await?
+            .into_iter()
+            .map(|row| row.get(1))
+            .collect();
+        if !columns.contains(&"listed_at".to_string()) {
+            sqlx::query("ALTER TABLE inventorys ADD COLUMN listed_at TEXT default null")
+                .execute(&connection)
+                .await?;
+            sqlx::query("UPDATE inventorys SET listed_at = ?1 WHERE listed_price IS NOT NULL")
+                .bind(chrono::Utc::now().to_rfc3339())
+                .execute(&connection)
+                .await?;
+        }
         sqlx::query(
             "
         CREATE TABLE IF NOT EXISTS transactions (
@@ -90,6 +107,65 @@ impl DatabaseClient {
         .execute(&connection)
         .await
         .unwrap();
+        sqlx::query(
+            "
+        CREATE TABLE IF NOT EXISTS order_book_snapshots (
+            id integer not null primary key autoincrement,
+            item_url text not null,
+            mod_rank integer default null,
+            datetime text not null,
+            best_bid integer default null,
+            best_ask integer default null,
+            spread integer default null,
+            sell_depth_5 integer not null default 0,
+            sell_depth_10 integer not null default 0,
+            sell_depth_20 integer not null default 0,
+            buy_depth_5 integer not null default 0,
+            buy_depth_10 integer not null default 0,
+            buy_depth_20 integer not null default 0,
+            sell_orders integer not null default 0,
+            buy_orders integer not null default 0,
+            online_sellers integer not null default 0,
+            online_buyers integer not null default 0,
+            new_orders integer not null default 0,
+            removed_orders integer not null default 0,
+            ingame_sellers integer not null default 0
+        )",
+        )
+        .execute(&connection)
+        .await
+        .unwrap();
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS order_book_snapshots_item_datetime ON order_book_snapshots (item_url, datetime)",
+        )
+        .execute(&connection)
+        .await
+        .unwrap();
+        sqlx::query(
+            "
+        CREATE TABLE IF NOT EXISTS item_schedules (
+            item_url text not null primary key,
+            next_check text not null,
+            interval_seconds integer not null default 0,
+            best_buy integer default null,
+            best_sell integer default null
+        )",
+        )
+        .execute(&connection)
+        .await
+        .unwrap();
+        sqlx::query(
+            "
+        CREATE TABLE IF NOT EXISTS whispers (
+            id integer not null primary key autoincrement,
+            username text not null,
+            direction text not null,
+            datetime text not null
+        )",
+        )
+        .execute(&connection)
+        .await
+        .unwrap();
         self.import_data().await?;
         Ok(true)
     }
@@ -195,6 +271,7 @@ impl DatabaseClient {
                 price: row.get(5),
                 listed_price: row.get(6),
                 owned: row.get(7),
+                listed_at: row.get(8),
             })
             .collect();
         Ok(inventory_vec)
@@ -209,11 +286,19 @@ impl DatabaseClient {
         price: i64,
     ) -> Result<Transaction, GlobleError> {
         let connection = self.connection.lock().unwrap().clone();
-        let item = self.cache.lock()?.get_item_by_url_name(&item_id).unwrap();
+        let item = match self.cache.lock()?.get_item_by_url_name(&item_id) {
+            Some(item) => item,
+            None => {
+                return Err(GlobleError::OtherError(format!(
+                    "Could not find item {} in the tradable items cache",
+                    item_id
+                )))
+            }
+        };
         let transaction = Transaction {
             id: -1,
             item_id,
-            item_type: item.tags.unwrap().join(","),
+            item_type: item.tags.unwrap_or_default().join(","),
             item_url: item.url_name,
             item_name: item.item_name,
             datetime: chrono::Local::now().to_string(),
@@ -255,12 +340,16 @@ impl DatabaseClient {
         Ok(transaction)
     }
 
+    // Ranked variants of an item are separate inventory entries
     pub async fn get_inventory_by_url_name(
         &self,
         url_name: String,
+        rank: i64,
     ) -> Result<Option<Invantory>, GlobleError> {
         let inventorys = self.get_inventorys().await?;
-        let inventory = inventorys.iter().find(|t| t.item_url == url_name);
+        let inventory = inventorys
+            .iter()
+            .find(|t| t.item_url == url_name && t.rank == rank);
         Ok(inventory.cloned())
     }
     pub async fn create_inventory_entry(
@@ -271,7 +360,7 @@ impl DatabaseClient {
         price: i64,
         rank: i64,
     ) -> Result<Invantory, GlobleError> {
-        let inventorys = self.get_inventory_by_url_name(id.clone()).await?;
+        let inventorys = self.get_inventory_by_url_name(id.clone(), rank).await?;
         let connection = self.connection.lock().unwrap().clone();
         let wfm = self.wfm.lock()?.clone();
         let operation = match inventorys {
@@ -283,7 +372,15 @@ impl DatabaseClient {
             quantity = 1;
         }
 
-        let item = self.cache.lock()?.get_item_by_url_name(&id).unwrap();
+        let item = match self.cache.lock()?.get_item_by_url_name(&id) {
+            Some(item) => item,
+            None => {
+                return Err(GlobleError::OtherError(format!(
+                    "Could not find item {} in the tradable items cache",
+                    id
+                )))
+            }
+        };
         let inventory = match inventorys {
             Some(t) => {
                 let total_owned = t.owned + quantity;
@@ -320,6 +417,7 @@ impl DatabaseClient {
                     price: price as f64,
                     listed_price: None,
                     owned: 1,
+                    listed_at: None,
                 };
                 inventory
             }
@@ -377,8 +475,13 @@ impl DatabaseClient {
         if inventory.owned <= 0 {
             self.delete_inventory_entry(id).await?;
         } else {
-            sqlx::query("UPDATE inventorys SET owned = ?1 WHERE id = ?2")
+            // A sale restarts the unsold time of the listing
+            if inventory.listed_price.is_some() {
+                inventory.listed_at = Some(chrono::Utc::now().to_rfc3339());
+            }
+            sqlx::query("UPDATE inventorys SET owned = ?1, listed_at = ?2 WHERE id = ?3")
                 .bind(inventory.clone().owned)
+                .bind(inventory.clone().listed_at)
                 .bind(inventory.clone().id)
                 .execute(&connection)
                 .await?;
@@ -442,18 +545,43 @@ impl DatabaseClient {
         item_url: String,
         listed_price: Option<i64>,
     ) -> Result<bool, GlobleError> {
-        let inventory = self.get_inventory_by_url(item_url.to_string()).await?;
+        match self.get_inventory_by_url(item_url.to_string()).await? {
+            Some(inventory) => {
+                self.update_inventory_listed_price(inventory.id, listed_price)
+                    .await
+            }
+            None => Ok(false),
+        }
+    }
+    /// Sets the listed price of one entry, ranked variants of an item are separate entries.
+    pub async fn update_inventory_listed_price(
+        &self,
+        id: i64,
+        listed_price: Option<i64>,
+    ) -> Result<bool, GlobleError> {
+        let inventorys = self.get_inventorys().await?;
+        let inventory = inventorys.iter().find(|t| t.id == id).cloned();
         if inventory.is_none() {
             return Ok(false);
         }
         let mut inventory = inventory.unwrap();
         let connection = self.connection.lock().unwrap().clone();
-        let result = sqlx::query("UPDATE inventorys SET listed_price = ?1 WHERE id = ?2")
+        // Price updates keep the listing time, only a new or removed listing changes it
+        let listed_at = match listed_price {
+            Some(_) => inventory
+                .listed_at
+                .clone()
+                .or_else(|| Some(chrono::Utc::now().to_rfc3339())),
+            None => None,
+        };
+        sqlx::query("UPDATE inventorys SET listed_price = ?1, listed_at = ?2 WHERE id = ?3")
             .bind(listed_price)
+            .bind(listed_at.clone())
             .bind(inventory.id.clone())
             .execute(&connection)
             .await?;
         inventory.listed_price = listed_price;
+        inventory.listed_at = listed_at;
         helper::send_message_to_window(
             "update_data",
             Some(json!({ "type": "inventorys",
@@ -463,6 +591,187 @@ impl DatabaseClient {
         );
         Ok(true)
     }
+    pub async fn update_inventory_owned(
+        &self,
+        id: i64,
+        owned: i64,
+    ) -> Result<Option<Invantory>, GlobleError> {
+        let inventorys = self.get_inventorys().await?;
+        let inventory = inventorys.iter().find(|t| t.id == id).cloned();
+        if inventory.is_none() {
+            return Ok(None);
+        }
+        if owned <= 0 {
+            return self.delete_inventory_entry(id).await;
+        }
+        let mut inventory = inventory.unwrap();
+        let connection = self.connection.lock().unwrap().clone();
+        sqlx::query("UPDATE inventorys SET owned = ?1 WHERE id = ?2")
+            .bind(owned)
+            .bind(id)
+            .execute(&connection)
+            .await?;
+        inventory.owned = owned;
+        helper::send_message_to_window(
+            "update_data",
+            Some(json!({ "type": "inventorys",
+                "operation": "update",
+                "data": inventory
+            })),
+        );
+        Ok(Some(inventory))
+    }
+
+    pub async fn create_order_book_snapshot(
+        &self,
+        snapshot: OrderBookSnapshot,
+    ) -> Result<OrderBookSnapshot, GlobleError> {
+        let connection = self.connection.lock()?.clone();
+        let result = sqlx::query(
+            "INSERT INTO order_book_snapshots (item_url, mod_rank, datetime, best_bid, best_ask, spread, sell_depth_5, sell_depth_10, sell_depth_20, buy_depth_5, buy_depth_10, buy_depth_20, sell_orders, buy_orders, online_sellers, online_buyers, new_orders, removed_orders, ingame_sellers) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19)")
+            .bind(snapshot.item_url.clone())
+            .bind(snapshot.mod_rank)
+            .bind(snapshot.datetime.clone())
+            .bind(snapshot.best_bid)
+            .bind(snapshot.best_ask)
+            .bind(snapshot.spread)
+            .bind(snapshot.sell_depth_5)
+            .bind(snapshot.sell_depth_10)
+            .bind(snapshot.sell_depth_20)
+            .bind(snapshot.buy_depth_5)
+            .bind(snapshot.buy_depth_10)
+            .bind(snapshot.buy_depth_20)
+            .bind(snapshot.sell_orders)
+            .bind(snapshot.buy_orders)
+            .bind(snapshot.online_sellers)
+            .bind(snapshot.online_buyers)
+            .bind(snapshot.new_orders)
+            .bind(snapshot.removed_orders)
+            .bind(snapshot.ingame_sellers)
+            .execute(&connection).await?;
+        Ok(OrderBookSnapshot {
+            id: result.last_insert_rowid(),
+            ..snapshot
+        })
+    }
+
+    /// Returns the snapshots of the item taken after `since`, oldest first.
+    pub async fn get_order_book_snapshots(
+        &self,
+        item_url: &str,
+        since: &str,
+    ) -> Result<Vec<OrderBookSnapshot>, GlobleError> {
+        let connection = self.connection.lock()?.clone();
+        let snapshots: Vec<OrderBookSnapshot> = sqlx::query(
+            "SELECT * FROM order_book_snapshots WHERE item_url = ?1 AND datetime >= ?2 ORDER BY datetime",
+        )
+        .bind(item_url)
+        .bind(since)
+        .fetch_all(&connection)
+        .await?
+        .into_iter()
+        .map(|row| get_order_book_snapshot_from_row(&row))
+        .collect();
+        Ok(snapshots)
+    }
+
+    /// Returns the most recent snapshot of every item.
+    pub async fn get_latest_order_book_snapshots(
+        &self,
+    ) -> Result<Vec<OrderBookSnapshot>, GlobleError> {
+        let connection = self.connection.lock()?.clone();
+        let snapshots: Vec<OrderBookSnapshot> = sqlx::query(
+            "SELECT * FROM order_book_snapshots WHERE id IN (SELECT MAX(id) FROM order_book_snapshots GROUP BY item_url)",
+        )
+        .fetch_all(&connection)
+        .await?
+        .into_iter()
+        .map(|row| get_order_book_snapshot_from_row(&row))
+        .collect();
+        Ok(snapshots)
+    }
+
+    pub async fn delete_order_book_snapshots_before(
+        &self,
+        datetime: &str,
+    ) -> Result<u64, GlobleError> {
+        let connection = self.connection.lock()?.clone();
+        let result = sqlx::query("DELETE FROM order_book_snapshots WHERE datetime < ?1")
+            .bind(datetime)
+            .execute(&connection)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    pub async fn get_item_schedules(&self) -> Result<Vec<ItemSchedule>, GlobleError> {
+        let connection = self.connection.lock()?.clone();
+        let schedules: Vec<ItemSchedule> = sqlx::query("SELECT * FROM item_schedules")
+            .fetch_all(&connection)
+            .await?
+            .into_iter()
+            .map(|row| ItemSchedule {
+                item_url: row.get(0),
+                next_check: row.get(1),
+                interval_seconds: row.get(2),
+                best_buy: row.get(3),
+                best_sell: row.get(4),
+            })
+            .collect();
+        Ok(schedules)
+    }
+
+    pub async fn upsert_item_schedule(&self, schedule: &ItemSchedule) -> Result<(), GlobleError> {
+        let connection = self.connection.lock()?.clone();
+        sqlx::query(
+            "INSERT OR REPLACE INTO item_schedules (item_url, next_check, interval_seconds, best_buy, best_sell) VALUES (?1, ?2, ?3, ?4, ?5)",
+        )
+        .bind(schedule.item_url.clone())
+        .bind(schedule.next_check.clone())
+        .bind(schedule.interval_seconds)
+        .bind(schedule.best_buy)
+        .bind(schedule.best_sell)
+        .execute(&connection)
+        .await?;
+        Ok(())
+    }
+
+    /// Records a whisper we sent to or received from a player, `direction` is "sent" or
+    /// "received".
+    pub async fn create_whisper_entry(
+        &self,
+        username: &str,
+        direction: &str,
+    ) -> Result<(), GlobleError> {
+        let connection = self.connection.lock()?.clone();
+        sqlx::query("INSERT INTO whispers (username, direction, datetime) VALUES (?1, ?2, ?3)")
+            .bind(username)
+            .bind(direction)
+            .bind(chrono::Utc::now().to_rfc3339())
+            .execute(&connection)
+            .await?;
+        Ok(())
+    }
+
+    /// Returns the lowercase names of the players that have not answered at least
+    /// `min_unanswered` of our whispers sent before `answered_before`.
+    pub async fn get_unresponsive_users(
+        &self,
+        min_unanswered: i64,
+        answered_before: &str,
+    ) -> Result<Vec<String>, GlobleError> {
+        let connection = self.connection.lock()?.clone();
+        let users: Vec<String> = sqlx::query(
+            "SELECT lower(s.username) AS username FROM whispers s WHERE s.direction = 'sent' AND s.datetime < ?1 AND NOT EXISTS (SELECT 1 FROM whispers r WHERE r.direction = 'received' AND lower(r.username) = lower(s.username) AND r.datetime >= s.datetime) GROUP BY lower(s.username) HAVING COUNT(*) >= ?2",
+        )
+        .bind(answered_before)
+        .bind(min_unanswered)
+        .fetch_all(&connection)
+        .await?
+        .into_iter()
+        .map(|row| row.get::<String, _>("username"))
+        .collect();
+        Ok(users)
+    }
 
     // TODO: Remove in production
     pub async fn import_data(&self) -> Result<bool, GlobleError> {
@@ -516,3 +825,97 @@ impl DatabaseClient {
         Ok(true)
     }
 }
+
+fn get_order_book_snapshot_from_row(row: &SqliteRow) -> OrderBookSnapshot {
+    OrderBookSnapshot {
+        id: row.get(0),
+        item_url: row.get(1),
+        mod_rank: row.get(2),
+        datetime: row.get(3),
+        best_bid: row.get(4),
+        best_ask: row.get(5),
+        spread: row.get(6),
+        sell_depth_5: row.get(7),
+        sell_depth_10: row.get(8),
+        sell_depth_20: row.get(9),
+        buy_depth_5: row.get(10),
+        buy_depth_10: row.get(11),
+        buy_depth_20: row.get(12),
+        sell_orders: row.get(13),
+        buy_orders: row.get(14),
+        online_sellers: row.get(15),
+        online_buyers: row.get(16),
+        new_orders: row.get(17),
+        removed_orders: row.get(18),
+        ingame_sellers: row.get(19),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::AuthState;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    async fn memory_client() -> DatabaseClient {
+        // One connection, every connection to :memory: is its own database
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        let wfm = Arc::new(Mutex::new(WFMClientState::new(Arc::new(Mutex::new(
+            AuthState::default(),
+        )))));
+        let client = DatabaseClient {
+            log_file: "db.log".to_string(),
+            connection: Arc::new(Mutex::new(pool)),
+            cache: Arc::new(Mutex::new(CacheState::new(Arc::clone(&wfm)))),
+            wfm,
+        };
+        client.initialize().await.unwrap();
+        client
+    }
+
+    fn hours_from_now(hours: i64) -> String {
+        (chrono::Utc::now() + chrono::Duration::hours(hours)).to_rfc3339()
+    }
+
+    #[tokio::test]
+    async fn unresponsive_users() {
+        let db = memory_client().await;
+        for (username, direction) in [
+            ("Ghost", "sent"),
+            ("ghost", "sent"),
+            ("Friend", "sent"),
+            ("Friend", "sent"),
+            ("friend", "received"),
+            ("Once", "sent"),
+        ] {
+            db.create_whisper_entry(username, direction).await.unwrap();
+        }
+        assert_eq!(
+            db.get_unresponsive_users(2, &hours_from_now(1))
+                .await
+                .unwrap(),
+            vec!["ghost".to_string()]
+        );
+        let mut users = db
+            .get_unresponsive_users(1, &hours_from_now(1))
+            .await
+            .unwrap();
+        users.sort();
+        assert_eq!(users, vec!["ghost".to_string(), "once".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn whispers_inside_the_response_time_are_not_counted() {
+        let db = memory_client().await;
+        db.create_whisper_entry("Ghost", "sent").await.unwrap();
+        assert!(db
+            .get_unresponsive_users(1, &hours_from_now(-1))
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
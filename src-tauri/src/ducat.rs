@@ -0,0 +1,234 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::{
+    cache::CacheState, database::DatabaseClient, logger, price_scraper::PriceScraper,
+    settings::SettingsState, structs::GlobleError, wfm_client::WFMClientState,
+};
+
+// Live mode re-prices this many parts per requested candidate, each lookup is a rate limited
+// order book request
+const LIVE_LOOKUP_FACTOR: usize = 3;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DucatValue {
+    pub url_name: String,
+    pub item_name: String,
+    pub ducats: i64,
+    // Platinum price used for the calculation, from price history or live orders
+    pub price: f64,
+    pub volume: f64,
+    pub plat_per_ducat: f64,
+    pub ducats_per_plat: f64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TrashToBaroEntry {
+    pub inventory_id: i64,
+    pub owned: i64,
+    // Total value if sold for platinum and if traded for ducats, both in platinum
+    pub plat_value: f64,
+    pub ducat_value: f64,
+    pub value: DucatValue,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DucatBuyCandidate {
+    // Highest price we should pay to stay above the ducats per plat target
+    pub max_buy_price: i64,
+    pub value: DucatValue,
+}
+
+#[derive(Clone, Debug)]
+pub struct DucatState {
+    log_file: String,
+    settings: Arc<Mutex<SettingsState>>,
+    cache: Arc<Mutex<CacheState>>,
+    price_scraper: Arc<Mutex<PriceScraper>>,
+    wfm: Arc<Mutex<WFMClientState>>,
+    db: Arc<Mutex<DatabaseClient>>,
+    // Ducat values learned from our own orders, the catalogue does not have all of them
+    known_ducats: Arc<Mutex<HashMap<String, i64>>>,
+}
+
+impl DucatState {
+    pub fn new(
+        settings: Arc<Mutex<SettingsState>>,
+        cache: Arc<Mutex<CacheState>>,
+        price_scraper: Arc<Mutex<PriceScraper>>,
+        wfm: Arc<Mutex<WFMClientState>>,
+        db: Arc<Mutex<DatabaseClient>>,
+    ) -> Self {
+        DucatState {
+            log_file: "ducats.log".to_string(),
+            settings,
+            cache,
+            price_scraper,
+            wfm,
+            db,
+            known_ducats: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Learns ducat values from the `ducats` field of our profile orders.
+    pub async fn update_known_ducats(&self) -> Result<(), GlobleError> {
+        let wfm = self.wfm.lock()?.clone();
+        let orders = wfm.get_user_ordres().await?;
+        let mut known_ducats = self.known_ducats.lock()?;
+        for order in orders.sell_orders.iter().chain(orders.buy_orders.iter()) {
+            if let Some(ducats) = order.item.ducats {
+                known_ducats.insert(order.item.url_name.clone(), ducats);
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the ducat value of every prime part priced by the price history.
+    pub fn get_ducat_values(&self) -> Result<Vec<DucatValue>, GlobleError> {
+        let cache = self.cache.lock()?.clone();
+        let known_ducats = self.known_ducats.lock()?.clone();
+        let summary = self.price_scraper.lock()?.get_price_summary()?;
+
+        let mut values = vec![];
+        for item in cache.get_items()?.iter() {
+            let ducats = match known_ducats.get(&item.url_name).cloned().or(item.ducats) {
+                Some(ducats) if ducats > 0 => ducats,
+                _ => continue,
+            };
+            let stats = match summary.get(&item.url_name) {
+                Some(stats) if stats.median > 0.0 => stats,
+                _ => continue,
+            };
+            values.push(create_ducat_value(
+                &item.url_name,
+                &item.item_name,
+                ducats,
+                stats.median,
+                stats.volume,
+            ));
+        }
+        values.sort_by(|a, b| {
+            b.ducats_per_plat
+                .partial_cmp(&a.ducats_per_plat)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        Ok(values)
+    }
+
+    /// Inventory items that are worth more when traded to Baro than when sold for platinum.
+    pub async fn get_trash_to_baro(&self) -> Result<Vec<TrashToBaroEntry>, GlobleError> {
+        let settings = self.settings.lock()?.clone();
+        let db = self.db.lock()?.clone();
+        if let Err(e) = self.update_known_ducats().await {
+            logger::error_con("Ducats", format!("Update Known Ducats: {:?}", e).as_str());
+        }
+        let values: HashMap<String, DucatValue> = self
+            .get_ducat_values()?
+            .into_iter()
+            .map(|value| (value.url_name.clone(), value))
+            .collect();
+
+        let mut entries = vec![];
+        for inventory in db.get_inventorys().await? {
+            let value = match values.get(&inventory.item_url) {
+                Some(value) => value,
+                None => continue,
+            };
+            let plat_value = value.price * inventory.owned as f64;
+            let ducat_value =
+                value.ducats as f64 * settings.ducats.plat_per_ducat * inventory.owned as f64;
+            if ducat_value > plat_value {
+                entries.push(TrashToBaroEntry {
+                    inventory_id: inventory.id,
+                    owned: inventory.owned,
+                    plat_value,
+                    ducat_value,
+                    value: value.clone(),
+                });
+            }
+        }
+        entries.sort_by(|a, b| {
+            (b.ducat_value - b.plat_value)
+                .partial_cmp(&(a.ducat_value - a.plat_value))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        logger::info_file(
+            "Ducats",
+            format!("Trash to Baro: {} items", entries.len()).as_str(),
+            Some(self.log_file.as_str()),
+        );
+        Ok(entries)
+    }
+
+    /// Cheap parts with a high ducat value, the buying mode of the ducat analysis.
+    /// With `live` the best `limit * LIVE_LOOKUP_FACTOR` parts by their historic price are
+    /// re-priced from the current sell orders, so one call makes a bounded number of requests.
+    pub async fn get_buy_candidates(
+        &self,
+        live: bool,
+        limit: usize,
+    ) -> Result<Vec<DucatBuyCandidate>, GlobleError> {
+        let settings = self.settings.lock()?.clone().ducats;
+        let wfm = self.wfm.lock()?.clone();
+
+        // Sorted by the historic ducats per platinum, the best parts are re-priced first
+        let values: Vec<DucatValue> = self
+            .get_ducat_values()?
+            .into_iter()
+            .filter(|value| value.ducats >= settings.min_ducats)
+            .collect();
+        let values = if live {
+            let mut live_values = vec![];
+            for value in values.into_iter().take(limit * LIVE_LOOKUP_FACTOR) {
+                if let Some(price) = wfm.get_lowest_sell_price(&value.url_name, 0).await? {
+                    live_values.push(create_ducat_value(
+                        &value.url_name,
+                        &value.item_name,
+                        value.ducats,
+                        price as f64,
+                        value.volume,
+                    ));
+                }
+            }
+            live_values
+        } else {
+            values
+        };
+
+        let mut candidates: Vec<DucatBuyCandidate> = values
+            .into_iter()
+            .filter(|value| value.ducats_per_plat >= settings.min_ducats_per_plat)
+            .map(|value| DucatBuyCandidate {
+                max_buy_price: (value.ducats as f64 / settings.min_ducats_per_plat).floor() as i64,
+                value,
+            })
+            .collect();
+        candidates.sort_by(|a, b| {
+            b.value
+                .ducats_per_plat
+                .partial_cmp(&a.value.ducats_per_plat)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        candidates.truncate(limit);
+        Ok(candidates)
+    }
+}
+
+fn create_ducat_value(
+    url_name: &str,
+    item_name: &str,
+    ducats: i64,
+    price: f64,
+    volume: f64,
+) -> DucatValue {
+    DucatValue {
+        url_name: url_name.to_string(),
+        item_name: item_name.to_string(),
+        ducats,
+        price,
+        volume,
+        plat_per_ducat: price / ducats as f64,
+        ducats_per_plat: ducats as f64 / price.max(1.0),
+    }
+}
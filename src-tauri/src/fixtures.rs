@@ -0,0 +1,25 @@
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::path::PathBuf;
+
+/// Reads a recorded API response from `tests/fixtures`.
+pub fn read_fixture(name: &str) -> Value {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fixtures")
+        .join(name);
+    let content = std::fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("Fixture {}: {:?}", path.display(), e));
+    serde_json::from_str(&content).unwrap_or_else(|e| panic!("Fixture {}: {:?}", name, e))
+}
+
+/// Deserializes the payload of a recorded Warframe Market response the same way the client
+/// does, under `payload_key` when it is set.
+pub fn read_payload<T: DeserializeOwned>(name: &str, payload_key: Option<&str>) -> T {
+    let response = read_fixture(name);
+    let data = match payload_key {
+        Some(payload_key) => response["payload"][payload_key].clone(),
+        None => response["payload"].clone(),
+    };
+    serde_json::from_value(data).unwrap_or_else(|e| panic!("Fixture {}: {:?}", name, e))
+}
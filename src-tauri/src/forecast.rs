@@ -0,0 +1,474 @@
+use chrono::{Datelike, Duration, NaiveDate};
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use crate::{logger, price_scraper::PriceScraper, settings::SettingsState, structs::GlobleError};
+
+// z value of the 95% confidence band
+const CONFIDENCE_Z: f64 = 1.96;
+// A weekday needs this many observations before its seasonality is trusted
+const MIN_WEEKDAY_OBSERVATIONS: usize = 2;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LinearTrend {
+    // Price change per day
+    pub slope: f64,
+    pub intercept: f64,
+    pub residual_std: f64,
+    // Mean and sum of squares of the day index, needed for the prediction interval
+    x_mean: f64,
+    x_sxx: f64,
+    n: usize,
+}
+
+impl LinearTrend {
+    /// Half width of the prediction interval at day index `x`.
+    pub fn band(&self, x: f64) -> f64 {
+        if self.n < 3 || self.x_sxx == 0.0 {
+            return 0.0;
+        }
+        let leverage = 1.0 + 1.0 / self.n as f64 + (x - self.x_mean).powi(2) / self.x_sxx;
+        CONFIDENCE_Z * self.residual_std * leverage.sqrt()
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ForecastPoint {
+    pub date: String,
+    pub expected_price: f64,
+    pub lower: f64,
+    pub upper: f64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ItemForecast {
+    pub url_name: String,
+    pub observations: usize,
+    pub last_price: f64,
+    pub moving_average: f64,
+    pub ewma: f64,
+    pub trend: Option<LinearTrend>,
+    // Multiplier per weekday, Monday first, 1.0 when there is not enough history
+    pub weekday_factors: [f64; 7],
+    // Average expected price over the forecast days
+    pub expected_price: f64,
+    // Standard deviation of the daily price change in percent
+    pub volatility: f64,
+    pub points: Vec<ForecastPoint>,
+}
+
+pub fn moving_average(values: &[f64], window: usize) -> Option<f64> {
+    if values.is_empty() || window == 0 {
+        return None;
+    }
+    let window = &values[values.len().saturating_sub(window)..];
+    Some(window.iter().sum::<f64>() / window.len() as f64)
+}
+
+/// Exponentially weighted moving average, `alpha` is the weight of the newest value.
+pub fn ewma(values: &[f64], alpha: f64) -> Option<f64> {
+    let mut iter = values.iter();
+    let first = *iter.next()?;
+    Some(iter.fold(first, |average, value| {
+        alpha * value + (1.0 - alpha) * average
+    }))
+}
+
+/// Least squares fit of the values against their index.
+pub fn linear_trend(values: &[f64]) -> Option<LinearTrend> {
+    let n = values.len();
+    if n < 2 {
+        return None;
+    }
+    let x_mean = (n - 1) as f64 / 2.0;
+    let y_mean = values.iter().sum::<f64>() / n as f64;
+    let mut sxx = 0.0;
+    let mut sxy = 0.0;
+    for (i, y) in values.iter().enumerate() {
+        sxx += (i as f64 - x_mean).powi(2);
+        sxy += (i as f64 - x_mean) * (y - y_mean);
+    }
+    let slope = sxy / sxx;
+    let intercept = y_mean - slope * x_mean;
+    let residual_std = if n > 2 {
+        let sse: f64 = values
+            .iter()
+            .enumerate()
+            .map(|(i, y)| (y - (intercept + slope * i as f64)).powi(2))
+            .sum();
+        (sse / (n - 2) as f64).sqrt()
+    } else {
+        0.0
+    };
+    Some(LinearTrend {
+        slope,
+        intercept,
+        residual_std,
+        x_mean,
+        x_sxx: sxx,
+        n,
+    })
+}
+
+/// Average ratio of the price on each weekday to the overall mean.
+pub fn weekday_factors(dates: &[NaiveDate], values: &[f64]) -> [f64; 7] {
+    let mut factors = [1.0; 7];
+    let mean = values.iter().sum::<f64>() / values.len().max(1) as f64;
+    if mean <= 0.0 {
+        return factors;
+    }
+    let mut sums = [0.0; 7];
+    let mut counts = [0usize; 7];
+    for (date, value) in dates.iter().zip(values.iter()) {
+        let day = date.weekday().num_days_from_monday() as usize;
+        sums[day] += value / mean;
+        counts[day] += 1;
+    }
+    for day in 0..7 {
+        if counts[day] >= MIN_WEEKDAY_OBSERVATIONS {
+            factors[day] = sums[day] / counts[day] as f64;
+        }
+    }
+    factors
+}
+
+/// Standard deviation of the day to day change in percent.
+pub fn volatility(values: &[f64]) -> f64 {
+    let changes: Vec<f64> = values
+        .windows(2)
+        .filter(|w| w[0] > 0.0)
+        .map(|w| (w[1] - w[0]) / w[0] * 100.0)
+        .collect();
+    if changes.len() < 2 {
+        return 0.0;
+    }
+    let mean = changes.iter().sum::<f64>() / changes.len() as f64;
+    let variance =
+        changes.iter().map(|c| (c - mean).powi(2)).sum::<f64>() / (changes.len() - 1) as f64;
+    variance.sqrt()
+}
+
+/// Forecasts the next `horizon` days from a daily price series sorted by date. The level is
+/// the EWMA of the deseasonalized prices, moved along the linear trend and scaled back by the
+/// weekday factor of each forecast day.
+pub fn forecast_series(
+    url_name: &str,
+    dates: &[NaiveDate],
+    values: &[f64],
+    horizon: usize,
+    alpha: f64,
+    window: usize,
+) -> Option<ItemForecast> {
+    if values.is_empty() || dates.len() != values.len() {
+        return None;
+    }
+    let factors = weekday_factors(dates, values);
+    let deseasonalized: Vec<f64> = dates
+        .iter()
+        .zip(values.iter())
+        .map(|(date, value)| value / factors[date.weekday().num_days_from_monday() as usize])
+        .collect();
+    let level = ewma(&deseasonalized, alpha)?;
+    let trend = linear_trend(&deseasonalized);
+    let last_date = *dates.last()?;
+    let last_index = (values.len() - 1) as f64;
+
+    let points: Vec<ForecastPoint> = (1..=horizon)
+        .map(|h| {
+            let date = last_date + Duration::days(h as i64);
+            let factor = factors[date.weekday().num_days_from_monday() as usize];
+            let (slope, band) = match &trend {
+                Some(trend) => (trend.slope, trend.band(last_index + h as f64)),
+                None => (0.0, 0.0),
+            };
+            let expected = ((level + slope * h as f64) * factor).max(0.0);
+            ForecastPoint {
+                date: date.format("%Y-%m-%d").to_string(),
+                expected_price: expected,
+                lower: (expected - band * factor).max(0.0),
+                upper: expected + band * factor,
+            }
+        })
+        .collect();
+    let expected_price = if points.is_empty() {
+        level
+    } else {
+        points.iter().map(|p| p.expected_price).sum::<f64>() / points.len() as f64
+    };
+
+    Some(ItemForecast {
+        url_name: url_name.to_string(),
+        observations: values.len(),
+        last_price: *values.last()?,
+        moving_average: moving_average(values, window)?,
+        ewma: ewma(values, alpha)?,
+        trend,
+        weekday_factors: factors,
+        expected_price,
+        volatility: volatility(values),
+        points,
+    })
+}
+
+// The forecasts only change when the price history or the forecast settings do
+#[derive(Clone, Debug, PartialEq)]
+struct ForecastCacheKey {
+    history_modified: Option<SystemTime>,
+    settings: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct ForecastState {
+    log_file: String,
+    settings: Arc<Mutex<SettingsState>>,
+    price_scraper: Arc<Mutex<PriceScraper>>,
+    cache: Arc<Mutex<Option<(ForecastCacheKey, HashMap<String, ItemForecast>)>>>,
+}
+
+impl ForecastState {
+    pub fn new(
+        settings: Arc<Mutex<SettingsState>>,
+        price_scraper: Arc<Mutex<PriceScraper>>,
+    ) -> Self {
+        ForecastState {
+            log_file: "forecast.log".to_string(),
+            settings,
+            price_scraper,
+            cache: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Returns the closed median price per day of every item, sorted by date. Mods and arcanes
+    /// only use their max rank, like the live scraper, so ranked and unranked prices are not
+    /// mixed in one series.
+    fn get_series(&self) -> Result<HashMap<String, Vec<(NaiveDate, f64)>>, GlobleError> {
+        let df = self
+            .price_scraper
+            .lock()?
+            .get_price_historys()?
+            .lazy()
+            .filter(col("order_type").eq(lit("closed")))
+            .select(&[
+                col("name"),
+                col("datetime"),
+                col("median"),
+                col("mod_rank").cast(DataType::Float64),
+            ])
+            .collect()?;
+        let names = df.column("name")?.utf8()?.clone();
+        let datetimes = df.column("datetime")?.utf8()?.clone();
+        let medians = df.column("median")?.f64()?.clone();
+        let mod_ranks = df.column("mod_rank")?.f64()?.clone();
+
+        let mut max_ranks: HashMap<&str, f64> = HashMap::new();
+        for (name, mod_rank) in names.into_iter().zip(mod_ranks.into_iter()) {
+            if let (Some(name), Some(mod_rank)) = (name, mod_rank) {
+                let max_rank = max_ranks.entry(name).or_insert(mod_rank);
+                *max_rank = max_rank.max(mod_rank);
+            }
+        }
+
+        let mut series: HashMap<String, Vec<(NaiveDate, f64)>> = HashMap::new();
+        for (((name, datetime), median), mod_rank) in names
+            .into_iter()
+            .zip(datetimes.into_iter())
+            .zip(medians.into_iter())
+            .zip(mod_ranks.into_iter())
+        {
+            let (name, datetime, median) = match (name, datetime, median) {
+                (Some(name), Some(datetime), Some(median)) => (name, datetime, median),
+                _ => continue,
+            };
+            if mod_rank != max_ranks.get(name).cloned() {
+                continue;
+            }
+            // relics.run dates look like 2023-09-01T00:00:00.000+00:00
+            let date = match NaiveDate::parse_from_str(datetime.get(..10).unwrap_or(""), "%Y-%m-%d")
+            {
+                Ok(date) => date,
+                Err(_) => continue,
+            };
+            series
+                .entry(name.to_string())
+                .or_default()
+                .push((date, median));
+        }
+        for values in series.values_mut() {
+            values.sort_by(|a, b| a.0.cmp(&b.0));
+        }
+        Ok(series)
+    }
+
+    /// Returns the forecast of every item, computed again only when the price history file or
+    /// the forecast settings changed.
+    pub fn get_forecasts(&self) -> Result<HashMap<String, ItemForecast>, GlobleError> {
+        let settings = self.settings.lock()?.clone().forecast;
+        let key = ForecastCacheKey {
+            history_modified: self.price_scraper.lock()?.get_price_history_modified(),
+            settings: serde_json::to_string(&settings)?,
+        };
+        if let Some((cached_key, forecasts)) = self.cache.lock()?.as_ref() {
+            if *cached_key == key {
+                return Ok(forecasts.clone());
+            }
+        }
+
+        let mut forecasts = HashMap::new();
+        for (name, values) in self.get_series()? {
+            let (dates, prices): (Vec<NaiveDate>, Vec<f64>) = values.into_iter().unzip();
+            if let Some(forecast) = forecast_series(
+                &name,
+                &dates,
+                &prices,
+                settings.horizon_days.max(1) as usize,
+                settings.ewma_alpha,
+                settings.moving_average_days.max(1) as usize,
+            ) {
+                forecasts.insert(name, forecast);
+            }
+        }
+        logger::info_file(
+            "Forecast",
+            format!("Forecasted {} items", forecasts.len()).as_str(),
+            Some(self.log_file.as_str()),
+        );
+        *self.cache.lock()? = Some((key, forecasts.clone()));
+        Ok(forecasts)
+    }
+
+    pub fn get_forecast(&self, item: &str) -> Result<Option<ItemForecast>, GlobleError> {
+        Ok(self.get_forecasts()?.remove(item))
+    }
+
+    /// Returns a DataFrame with the name, forecastPrice and forecastVolatility of every item,
+    /// ready to be joined on the buy/sell overlap.
+    pub fn get_forecasts_df(&self) -> Result<DataFrame, GlobleError> {
+        let forecasts = self.get_forecasts()?;
+        let mut names = vec![];
+        let mut prices = vec![];
+        let mut volatilities = vec![];
+        for (name, forecast) in forecasts {
+            names.push(name);
+            prices.push(forecast.expected_price);
+            volatilities.push(forecast.volatility);
+        }
+        Ok(DataFrame::new(vec![
+            Series::new("name", names),
+            Series::new("forecastPrice", prices),
+            Series::new("forecastVolatility", volatilities),
+        ])?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    fn dates(count: usize) -> Vec<NaiveDate> {
+        // 2023-01-02 is a Monday
+        let start = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+        (0..count)
+            .map(|i| start + Duration::days(i as i64))
+            .collect()
+    }
+
+    #[test]
+    fn moving_average_uses_the_last_values() {
+        assert_eq!(moving_average(&[1.0, 2.0, 3.0, 4.0], 2), Some(3.5));
+        assert_eq!(moving_average(&[1.0, 2.0], 5), Some(1.5));
+        assert_eq!(moving_average(&[], 3), None);
+        assert_eq!(moving_average(&[1.0], 0), None);
+    }
+
+    #[test]
+    fn ewma_weights_the_newest_value() {
+        assert_eq!(ewma(&[1.0, 2.0, 3.0], 0.5), Some(2.25));
+        assert_eq!(ewma(&[1.0, 2.0, 3.0], 1.0), Some(3.0));
+        assert_eq!(ewma(&[5.0], 0.3), Some(5.0));
+        assert_eq!(ewma(&[], 0.5), None);
+    }
+
+    #[test]
+    fn linear_trend_fits_a_line() {
+        let trend = linear_trend(&[1.0, 3.0, 5.0, 7.0, 9.0]).unwrap();
+        assert!(close(trend.slope, 2.0));
+        assert!(close(trend.intercept, 1.0));
+        assert!(close(trend.residual_std, 0.0));
+        assert!(close(trend.band(10.0), 0.0));
+        assert!(linear_trend(&[1.0]).is_none());
+    }
+
+    #[test]
+    fn trend_band_widens_away_from_the_data() {
+        let trend = linear_trend(&[10.0, 12.0, 11.0, 13.0, 12.0, 14.0]).unwrap();
+        assert!(trend.slope > 0.0);
+        assert!(trend.band(10.0) > trend.band(6.0));
+        assert!(trend.band(6.0) > 0.0);
+    }
+
+    #[test]
+    fn weekday_factors_need_two_observations() {
+        let values: Vec<f64> = dates(14)
+            .iter()
+            .map(|date| {
+                if date.weekday() == chrono::Weekday::Mon {
+                    20.0
+                } else {
+                    10.0
+                }
+            })
+            .collect();
+        let factors = weekday_factors(&dates(14), &values);
+        let mean = 160.0 / 14.0;
+        assert!(close(factors[0], 20.0 / mean));
+        assert!(close(factors[1], 10.0 / mean));
+
+        // A single week does not say anything about the weekdays
+        let factors = weekday_factors(&dates(7), &values[..7]);
+        assert_eq!(factors, [1.0; 7]);
+    }
+
+    #[test]
+    fn volatility_of_daily_changes() {
+        assert!(close(volatility(&[100.0, 110.0, 99.0]), 200.0_f64.sqrt()));
+        assert_eq!(volatility(&[100.0, 110.0]), 0.0);
+    }
+
+    #[test]
+    fn flat_series_forecasts_the_same_price() {
+        let forecast = forecast_series("item", &dates(14), &[50.0; 14], 3, 0.3, 7).unwrap();
+        assert_eq!(forecast.points.len(), 3);
+        assert!(close(forecast.expected_price, 50.0));
+        assert!(forecast
+            .points
+            .iter()
+            .all(|p| close(p.lower, 50.0) && close(p.upper, 50.0)));
+        assert_eq!(forecast.volatility, 0.0);
+    }
+
+    #[test]
+    fn rising_series_follows_the_trend() {
+        let values: Vec<f64> = (0..7).map(|i| 100.0 + i as f64).collect();
+        let forecast = forecast_series("item", &dates(7), &values, 3, 1.0, 7).unwrap();
+        let expected: Vec<f64> = forecast.points.iter().map(|p| p.expected_price).collect();
+        assert!(close(expected[0], 107.0));
+        assert!(close(expected[1], 108.0));
+        assert!(close(expected[2], 109.0));
+        assert!(close(forecast.expected_price, 108.0));
+        assert_eq!(forecast.points[0].date, "2023-01-09");
+        assert_eq!(forecast.last_price, 106.0);
+    }
+
+    #[test]
+    fn mismatched_series_has_no_forecast() {
+        assert!(forecast_series("item", &dates(3), &[1.0, 2.0], 3, 0.3, 7).is_none());
+        assert!(forecast_series("item", &[], &[], 3, 0.3, 7).is_none());
+    }
+}
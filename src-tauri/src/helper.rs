@@ -13,7 +13,7 @@ use polars::{
     series::Series,
 };
 use serde_json::{json, Value};
-use tauri::Window;
+use tauri::{api::notification::Notification, Manager, Window};
 
 use crate::structs::GlobleError;
 
@@ -236,39 +236,19 @@ pub fn last_x_days(x: i64) -> Vec<String> {
         .rev()
         .collect()
 }
-pub fn send_message_to_discord(webhook: String, message: String, ping: bool) {
-    tauri::async_runtime::spawn(async move {
-        let client = reqwest::Client::new();
-        let mut user_id: Option<String> = None;
-
-        if ping {
-            let res = client
-                .post(webhook.as_str())
-                .json(&json!({ "content": message }))
-                .send()
-                .await;
-            if let Ok(res) = res {
-                let json: Value = res.json().await.unwrap();
-                if let Some(id) = json.get("user").unwrap().get("id") {
-                    user_id = Some(id.to_string());
-                }
-            }
-        }
-        let mut message = message.to_string();
-        if user_id.is_some() {
-            message = format!("{} <@{}>", message, user_id.unwrap());
-        }
-
-        let res = client
-            .post(webhook)
-            .json(&json!({ "content": message }))
-            .send()
-            .await;
-        match res {
+pub fn send_desktop_notification(title: &str, body: &str) -> Result<(), GlobleError> {
+    let window = WINDOW.lock()?;
+    if let Some(window) = &*window {
+        let identifier = window.config().tauri.bundle.identifier.clone();
+        match Notification::new(identifier).title(title).body(body).show() {
             Ok(_) => {}
             Err(e) => {
-                println!("Error while sending message to discord {:?}", e);
+                return Err(GlobleError::OtherError(format!(
+                    "Error while sending desktop notification {:?}",
+                    e
+                )));
             }
         }
-    });
+    }
+    Ok(())
 }
@@ -0,0 +1,379 @@
+use serde::{Deserialize, Serialize};
+
+use crate::structs::Item;
+
+// Shorthands players commonly use in trade chat
+const ALIASES: [(&str, &str); 8] = [
+    ("bp", "blueprint"),
+    ("p", "prime"),
+    ("sys", "systems"),
+    ("neuro", "neuroptics"),
+    ("chas", "chassis"),
+    ("rcvr", "receiver"),
+    ("brl", "barrel"),
+    ("stk", "stock"),
+];
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ItemSearchFilter {
+    // Every tag must be present on the item
+    #[serde(default)]
+    pub tags: Vec<String>,
+    // Only items that are part of the set with this url_name
+    #[serde(default)]
+    pub set: Option<String>,
+    // Only items that belong to a set (true) or not (false)
+    #[serde(default)]
+    pub in_set: Option<bool>,
+    #[serde(default)]
+    pub subtype: Option<String>,
+    #[serde(default)]
+    pub min_mod_rank: Option<i64>,
+    #[serde(default)]
+    pub max_mod_rank: Option<i64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ItemSearchResult {
+    pub score: f64,
+    pub item: Item,
+}
+
+fn split_words(text: &str) -> Vec<String> {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        let mut current = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            current[j] = (prev[j] + 1)
+                .min(current[j - 1] + 1)
+                .min(prev[j - 1] + cost);
+        }
+        prev = current;
+    }
+    prev[b.len()]
+}
+
+/// Scores how well a single query token matches a single word, 0 means no match.
+fn score_word(token: &str, word: &str) -> f64 {
+    if token == word {
+        return 3.0;
+    }
+    if word.starts_with(token) {
+        return 2.0 * (0.5 + 0.5 * token.len() as f64 / word.len() as f64);
+    }
+    if token.len() >= 3 && word.contains(token) {
+        return 1.0;
+    }
+    // Allow small typos on longer tokens
+    let max_distance = match token.len() {
+        0..=3 => 0,
+        4..=6 => 1,
+        _ => 2,
+    };
+    if max_distance > 0 && levenshtein(token, word) <= max_distance {
+        return 0.8;
+    }
+    0.0
+}
+
+/// Returns the best score of the token against the words and the position of the matched word.
+fn score_token(token: &str, words: &[String]) -> (f64, Option<usize>) {
+    let alias = ALIASES
+        .iter()
+        .find(|(short, _)| *short == token)
+        .map(|(_, long)| *long);
+    let mut best = (0.0, None);
+    for (i, word) in words.iter().enumerate() {
+        let mut score = score_word(token, word);
+        if let Some(alias) = alias {
+            score = score.max(score_word(alias, word));
+        }
+        if score > best.0 {
+            best = (score, Some(i));
+        }
+    }
+    best
+}
+
+/// Scores an item against the query tokens, None if any token does not match.
+fn score_item(tokens: &[String], query: &str, item: &Item) -> Option<f64> {
+    let name_words = split_words(&item.item_name);
+    let url_words = split_words(&item.url_name);
+    let mut score = 0.0;
+    let mut positions: Vec<usize> = vec![];
+    for token in tokens {
+        let (name_score, position) = score_token(token, &name_words);
+        let (url_score, _) = score_token(token, &url_words);
+        let token_score = name_score.max(url_score);
+        if token_score == 0.0 {
+            return None;
+        }
+        if let Some(position) = position {
+            positions.push(position);
+        }
+        score += token_score;
+    }
+    // Tokens in the same order as the words of the name
+    if positions.windows(2).all(|w| w[0] < w[1]) {
+        score += 0.5;
+    }
+    if item.item_name.to_lowercase().starts_with(query) {
+        score += 2.0;
+    }
+    // Prefer items without unmatched extra words, "rhino p bp" should favor the blueprint over
+    // the neuroptics blueprint
+    let unmatched = name_words.len().saturating_sub(positions.len());
+    score -= 0.25 * unmatched as f64;
+    Some(score)
+}
+
+fn matches_filter(item: &Item, filter: &ItemSearchFilter, set_items: &Option<Vec<String>>) -> bool {
+    let tags = item.tags.clone().unwrap_or_default();
+    if !filter.tags.iter().all(|tag| tags.contains(tag)) {
+        return false;
+    }
+    let item_set = item.set_items.clone().unwrap_or_default();
+    if let Some(in_set) = filter.in_set {
+        if in_set != (item_set.len() > 1) {
+            return false;
+        }
+    }
+    if let Some(set_items) = set_items {
+        if !set_items.contains(&item.id) {
+            return false;
+        }
+    }
+    if let Some(subtype) = &filter.subtype {
+        if !item.subtypes.clone().unwrap_or_default().contains(subtype) {
+            return false;
+        }
+    }
+    if filter.min_mod_rank.is_some() || filter.max_mod_rank.is_some() {
+        let rank = match item.mod_max_rank {
+            Some(rank) => rank,
+            None => return false,
+        };
+        if rank < filter.min_mod_rank.unwrap_or(i64::MIN)
+            || rank > filter.max_mod_rank.unwrap_or(i64::MAX)
+        {
+            return false;
+        }
+    }
+    true
+}
+
+/// Searches the items by fuzzy and prefix matching on item_name and url_name.
+/// Results are ranked by score, highest first, ties are broken by name.
+pub fn search_items(
+    items: &[Item],
+    query: &str,
+    filter: &ItemSearchFilter,
+    limit: usize,
+) -> Vec<ItemSearchResult> {
+    let query = query.trim().to_lowercase();
+    let tokens = split_words(&query);
+
+    // Resolve the set filter to the ids of the items in that set
+    let set_items: Option<Vec<String>> = filter.set.as_ref().map(|set| {
+        items
+            .iter()
+            .find(|item| &item.url_name == set)
+            .and_then(|item| item.set_items.clone())
+            .unwrap_or_default()
+    });
+
+    let mut results: Vec<ItemSearchResult> = items
+        .iter()
+        .filter(|item| matches_filter(item, filter, &set_items))
+        .filter_map(|item| {
+            let score = if tokens.is_empty() {
+                Some(0.0)
+            } else {
+                score_item(&tokens, &query, item)
+            };
+            score.map(|score| ItemSearchResult {
+                score,
+                item: item.clone(),
+            })
+        })
+        .collect();
+
+    results.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| a.item.item_name.cmp(&b.item.item_name))
+    });
+    results.truncate(limit);
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RHINO_SET: [&str; 5] = [
+        "rhino_prime_set",
+        "rhino_prime_blueprint",
+        "rhino_prime_neuroptics_blueprint",
+        "rhino_prime_chassis_blueprint",
+        "rhino_prime_systems_blueprint",
+    ];
+
+    fn item(name: &str, tags: &[&str], set_items: Option<&[&str]>, rank: Option<i64>) -> Item {
+        let url_name = name.to_lowercase().replace(' ', "_");
+        Item {
+            item_name: name.to_string(),
+            id: url_name.clone(),
+            url_name,
+            thumb: String::new(),
+            set_items: set_items.map(|ids| ids.iter().map(|id| id.to_string()).collect()),
+            tags: Some(tags.iter().map(|tag| tag.to_string()).collect()),
+            mod_max_rank: rank,
+            subtypes: None,
+            quantity_for_set: None,
+            ducats: None,
+        }
+    }
+
+    fn items() -> Vec<Item> {
+        vec![
+            item("Rhino Prime Set", &["prime", "set"], Some(&RHINO_SET), None),
+            item(
+                "Rhino Prime Blueprint",
+                &["prime", "blueprint"],
+                Some(&RHINO_SET),
+                None,
+            ),
+            item(
+                "Rhino Prime Neuroptics Blueprint",
+                &["prime", "component", "blueprint"],
+                Some(&RHINO_SET),
+                None,
+            ),
+            item(
+                "Rhino Prime Chassis Blueprint",
+                &["prime", "component", "blueprint"],
+                Some(&RHINO_SET),
+                None,
+            ),
+            item(
+                "Rhino Prime Systems Blueprint",
+                &["prime", "component", "blueprint"],
+                Some(&RHINO_SET),
+                None,
+            ),
+            item("Arcane Grace", &["arcane_enhancement"], None, Some(5)),
+            item("Arcane Energize", &["arcane_enhancement"], None, Some(5)),
+            item("Serration", &["mod", "rifle"], None, Some(10)),
+        ]
+    }
+
+    fn search(query: &str, filter: &ItemSearchFilter) -> Vec<String> {
+        search_items(&items(), query, filter, 10)
+            .into_iter()
+            .map(|result| result.item.item_name)
+            .collect()
+    }
+
+    #[test]
+    fn abbreviations_rank_the_plain_blueprint_first() {
+        let results = search("rhino p bp", &ItemSearchFilter::default());
+        assert_eq!(results[0], "Rhino Prime Blueprint");
+        // The component blueprints match too, the set has no blueprint word
+        assert_eq!(results.len(), 4);
+        assert!(!results.contains(&"Rhino Prime Set".to_string()));
+    }
+
+    #[test]
+    fn component_abbreviation() {
+        let results = search("rhino p neuro", &ItemSearchFilter::default());
+        assert_eq!(results, vec!["Rhino Prime Neuroptics Blueprint"]);
+        let results = search("rhino p chas bp", &ItemSearchFilter::default());
+        assert_eq!(results, vec!["Rhino Prime Chassis Blueprint"]);
+    }
+
+    #[test]
+    fn prefix_ties_are_sorted_by_name() {
+        let results = search("arc", &ItemSearchFilter::default());
+        assert_eq!(results, vec!["Arcane Energize", "Arcane Grace"]);
+    }
+
+    #[test]
+    fn small_typos_match() {
+        let results = search("serattion", &ItemSearchFilter::default());
+        assert_eq!(results, vec!["Serration"]);
+        let results = search("rjino bp", &ItemSearchFilter::default());
+        assert_eq!(results[0], "Rhino Prime Blueprint");
+        // Tokens of three letters or less have to match exactly
+        assert!(search("rhino sex", &ItemSearchFilter::default()).is_empty());
+    }
+
+    #[test]
+    fn every_token_has_to_match() {
+        assert!(search("rhino grace", &ItemSearchFilter::default()).is_empty());
+    }
+
+    #[test]
+    fn empty_query_with_tags_lists_all_tagged_items() {
+        let filter = ItemSearchFilter {
+            tags: vec!["arcane_enhancement".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(search("", &filter), vec!["Arcane Energize", "Arcane Grace"]);
+        let results = search_items(&items(), "", &filter, usize::MAX);
+        assert!(results.iter().all(|result| result.score == 0.0));
+    }
+
+    #[test]
+    fn all_tags_are_required() {
+        let filter = ItemSearchFilter {
+            tags: vec!["prime".to_string(), "component".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(search("", &filter).len(), 3);
+    }
+
+    #[test]
+    fn set_and_rank_filters() {
+        let filter = ItemSearchFilter {
+            set: Some("rhino_prime_set".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(search("", &filter).len(), 5);
+
+        let filter = ItemSearchFilter {
+            in_set: Some(false),
+            ..Default::default()
+        };
+        assert_eq!(
+            search("", &filter),
+            vec!["Arcane Energize", "Arcane Grace", "Serration"]
+        );
+
+        let filter = ItemSearchFilter {
+            min_mod_rank: Some(6),
+            ..Default::default()
+        };
+        assert_eq!(search("", &filter), vec!["Serration"]);
+    }
+
+    #[test]
+    fn limit_is_applied() {
+        assert_eq!(
+            search_items(&items(), "rhino", &ItemSearchFilter::default(), 2).len(),
+            2
+        );
+    }
+}
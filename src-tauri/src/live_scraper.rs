@@ -1,6 +1,16 @@
+use crate::alerts::AlertEngine;
+use crate::allocation::{self, BuyCandidate};
+use crate::arbitrage::ArbitrageState;
 use crate::auth::AuthState;
+use crate::forecast::ForecastState;
+use crate::notification::NotificationState;
+use crate::order_book::OrderBookState;
 use crate::price_scraper::PriceScraper;
-use crate::structs::Order;
+use crate::scoring::{self, OpportunityScore, ScoreInput};
+use crate::settings::LiveScraperSettings;
+use crate::status::StatusState;
+use crate::structs::{Invantory, ItemSchedule, Order};
+use crate::websocket::WFMSocketState;
 use crate::{
     database::DatabaseClient,
     helper::{self, ColumnType, ColumnValue, ColumnValues},
@@ -9,28 +19,81 @@ use crate::{
     structs::GlobleError,
     wfm_client::WFMClientState,
 };
+use crate::{outlier, repricing, scheduler, trust};
 use polars::prelude::*;
+use serde::Serialize;
 use serde_json::json;
 use std::time::Duration;
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     sync::{
         atomic::{AtomicBool, Ordering},
         Arc, Mutex,
     },
 };
 
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LiveScraperState {
+    Idle,
+    Starting,
+    Running,
+    Paused,
+    // Waiting before retrying after a transient error
+    Backoff,
+    Stopping,
+    // Stopped because of an error that could not be recovered from
+    Error,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct LiveScraperStatus {
+    pub state: LiveScraperState,
+    pub current_item: Option<String>,
+    // Items checked in the current run
+    pub processed_items: usize,
+    pub total_items: usize,
+    pub error: Option<String>,
+    pub consecutive_errors: i64,
+    pub retry_at: Option<String>,
+}
+impl Default for LiveScraperStatus {
+    fn default() -> Self {
+        Self {
+            state: LiveScraperState::Idle,
+            current_item: None,
+            processed_items: 0,
+            total_items: 0,
+            error: None,
+            consecutive_errors: 0,
+            retry_at: None,
+        }
+    }
+}
+
 // Structs for the Warframe Market API
 
 #[derive(Clone)]
 pub struct LiveScraper {
     log_file: String,
     is_running: Arc<AtomicBool>,
+    // True until the loop task has finished tearing down, which outlasts is_running
+    is_looping: Arc<AtomicBool>,
+    is_paused: Arc<AtomicBool>,
+    lifecycle: Arc<Mutex<LiveScraperStatus>>,
     settings: Arc<Mutex<SettingsState>>,
     price_scraper: Arc<Mutex<PriceScraper>>,
     wfm: Arc<Mutex<WFMClientState>>,
     auth: Arc<Mutex<AuthState>>,
     db: Arc<Mutex<DatabaseClient>>,
+    notifications: Arc<Mutex<NotificationState>>,
+    alerts: Arc<Mutex<AlertEngine>>,
+    arbitrage: Arc<Mutex<ArbitrageState>>,
+    order_book: Arc<Mutex<OrderBookState>>,
+    forecast: Arc<Mutex<ForecastState>>,
+    scores: Arc<Mutex<Vec<OpportunityScore>>>,
+    socket: Arc<Mutex<WFMSocketState>>,
+    status: Arc<Mutex<StatusState>>,
 }
 
 impl LiveScraper {
@@ -40,68 +103,228 @@ impl LiveScraper {
         wfm: Arc<Mutex<WFMClientState>>,
         auth: Arc<Mutex<AuthState>>,
         db: Arc<Mutex<DatabaseClient>>,
+        notifications: Arc<Mutex<NotificationState>>,
+        alerts: Arc<Mutex<AlertEngine>>,
+        arbitrage: Arc<Mutex<ArbitrageState>>,
+        order_book: Arc<Mutex<OrderBookState>>,
+        forecast: Arc<Mutex<ForecastState>>,
+        socket: Arc<Mutex<WFMSocketState>>,
+        status: Arc<Mutex<StatusState>>,
     ) -> Self {
         LiveScraper {
             log_file: "live_scraper.log".to_string(),
             price_scraper,
             settings,
             is_running: Arc::new(AtomicBool::new(false)),
+            is_looping: Arc::new(AtomicBool::new(false)),
+            is_paused: Arc::new(AtomicBool::new(false)),
+            lifecycle: Arc::new(Mutex::new(LiveScraperStatus::default())),
             wfm,
             auth,
             db,
+            notifications,
+            alerts,
+            arbitrage,
+            order_book,
+            forecast,
+            scores: Arc::new(Mutex::new(vec![])),
+            socket,
+            status,
         }
     }
 
+    /// Starts the loop, fails while the previous loop is still stopping so two loops never
+    /// trade at the same time.
     pub fn start_loop(&mut self) -> Result<(), GlobleError> {
+        if self.is_running.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        let notifications = self.notifications.lock()?.clone();
+        let status = self.status.lock()?.clone();
+        let websocket_enabled = self.settings.lock()?.websocket.enabled;
+        if self.is_looping.swap(true, Ordering::SeqCst) {
+            let e = GlobleError::OtherError("The live scraper is still stopping".to_string());
+            helper::send_message_to_window(
+                "live_scraper_error",
+                Some(json!({ "error": format!("{:?}", e) })),
+            );
+            return Err(e);
+        }
         self.is_running.store(true, Ordering::SeqCst);
+        self.is_paused.store(false, Ordering::SeqCst);
+        if let Err(e) = self.update_status(|status| {
+            *status = LiveScraperStatus::default();
+            status.state = LiveScraperState::Starting;
+        }) {
+            self.is_running.store(false, Ordering::SeqCst);
+            self.is_looping.store(false, Ordering::SeqCst);
+            return Err(e);
+        }
         let is_running = Arc::clone(&self.is_running);
-        let forced_stop = Arc::clone(&self.is_running);
+        let is_looping = Arc::clone(&self.is_looping);
         let scraper = self.clone();
+        if websocket_enabled {
+            if let Ok(socket) = self.socket.lock() {
+                socket.start();
+            }
+        }
 
         tauri::async_runtime::spawn(async move {
             // A loop that takes output from the async process and sends it
             // to the webview via a Tauri Event
             logger::info_con("LiveScraper", "Loop live scraper is started");
-            match scraper.delete_all_orders().await {
-                Ok(_) => {
-                    logger::info_con("LiveScraper:DeleteAllOrders", "Delete all orders success");
-                }
-                Err(e) => {
-                    logger::error_con("LiveScraper:DeleteAllOrders", format!("{:?}", e).as_str());
-                    helper::send_message_to_window(
-                        "live_scraper_error",
-                        Some(json!({ "error": format!("{:?}", e) })),
-                    );
-
-                    forced_stop.store(false, Ordering::SeqCst);
-                    eprint!("{:?}", e);
+            // Read on every use, so changed settings apply without a restart
+            let get_settings = || match scraper.settings.lock() {
+                Ok(settings) => settings.live_scraper.clone(),
+                Err(_) => LiveScraperSettings::default(),
+            };
+            if get_settings().delete_orders_on_start {
+                match scraper.delete_all_orders().await {
+                    Ok(_) => {
+                        logger::info_con(
+                            "LiveScraper:DeleteAllOrders",
+                            "Delete all orders success",
+                        );
+                    }
+                    Err(e) => {
+                        logger::error_con(
+                            "LiveScraper:DeleteAllOrders",
+                            format!("{:?}", e).as_str(),
+                        );
+                        scraper.set_error(&e);
+                        is_running.store(false, Ordering::SeqCst);
+                    }
                 }
             }
+            let _ = scraper.update_status(|status| {
+                if status.state == LiveScraperState::Starting {
+                    status.state = LiveScraperState::Running;
+                }
+            });
 
-            while is_running.load(Ordering::SeqCst) && forced_stop.load(Ordering::SeqCst) {
+            while is_running.load(Ordering::SeqCst) {
+                scraper.wait_while_paused().await;
+                if !is_running.load(Ordering::SeqCst) {
+                    break;
+                }
                 logger::info_con("LiveScraper", "Loop live scraper is running...");
+                let mut wait = Duration::from_secs(1);
                 match scraper.run().await {
-                    Ok(_) => {}
+                    Ok(next_run) => {
+                        let _ = scraper.update_status(|status| {
+                            status.consecutive_errors = 0;
+                            status.error = None;
+                        });
+                        wait = next_run;
+                    }
                     Err(e) => {
                         logger::error_con("LiveScraper", format!("{:?}", e).as_str());
                         helper::send_message_to_window(
                             "live_scraper_error",
                             Some(json!({ "error": format!("{:?}", e) })),
                         );
-
-                        forced_stop.store(false, Ordering::SeqCst);
-                        eprint!("{:?}", e);
+                        let settings = get_settings();
+                        let errors = scraper
+                            .get_status()
+                            .map(|status| status.consecutive_errors + 1)
+                            .unwrap_or(1);
+                        if is_transient(&e) && errors <= settings.max_retries {
+                            let delay = settings
+                                .backoff_seconds
+                                .max(1)
+                                .saturating_mul(1_i64 << (errors - 1).min(16))
+                                .min(settings.max_backoff_seconds.max(1));
+                            scraper.backoff(&e, errors, delay).await;
+                        } else {
+                            notifications.notify(
+                                "live_scraper_error",
+                                "Live scraper stopped",
+                                format!("The live scraper stopped because of an error: {:?}", e)
+                                    .as_str(),
+                            );
+                            scraper.set_error(&e);
+                            is_running.store(false, Ordering::SeqCst);
+                        }
                     }
                 }
-                tokio::time::sleep(Duration::from_secs(1)).await;
+                // Sleep in steps, so stopping does not wait for the next item to be due
+                let mut waited = Duration::ZERO;
+                while waited < wait && is_running.load(Ordering::SeqCst) {
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    waited += Duration::from_secs(1);
+                }
+            }
+
+            let failed = scraper
+                .get_status()
+                .map(|status| status.state == LiveScraperState::Error)
+                .unwrap_or(false);
+            if !failed {
+                scraper.set_state(LiveScraperState::Stopping);
+            }
+            if get_settings().delete_orders_on_stop {
+                if let Err(e) = scraper.delete_all_orders().await {
+                    logger::error_con("LiveScraper:DeleteAllOrders", format!("{:?}", e).as_str());
+                }
             }
+            if let Err(e) = status.on_live_scraper_stopped() {
+                logger::error_con("LiveScraper:Status", format!("{:?}", e).as_str());
+            }
+            let _ = scraper.update_status(|status| {
+                status.current_item = None;
+                if !failed {
+                    status.state = LiveScraperState::Idle;
+                }
+            });
             logger::info_con("LiveScraper", "Loop live scraper is stopped");
+            is_looping.store(false, Ordering::SeqCst);
         });
         Ok(())
     }
 
     pub fn stop_loop(&self) {
         self.is_running.store(false, Ordering::SeqCst);
+        self.is_paused.store(false, Ordering::SeqCst);
+        let _ = self.update_status(|status| {
+            if status.state != LiveScraperState::Idle && status.state != LiveScraperState::Error {
+                status.state = LiveScraperState::Stopping;
+            }
+        });
+        let automate = match self.settings.lock() {
+            Ok(settings) => settings.status.automate,
+            Err(_) => false,
+        };
+        if let Ok(socket) = self.socket.lock() {
+            // The status is kept over the socket, so it stays open when it is automated
+            let result = if automate {
+                socket.clear_books()
+            } else {
+                socket.stop()
+            };
+            if let Err(e) = result {
+                logger::error_con("LiveScraper:Socket", format!("{:?}", e).as_str());
+            }
+        }
+    }
+
+    /// Pauses the loop before the next item, the orders that are already placed are kept.
+    pub fn pause(&self) -> Result<(), GlobleError> {
+        if !self.is_running() {
+            return Err(GlobleError::OtherError(
+                "The live scraper is not running".to_string(),
+            ));
+        }
+        self.is_paused.store(true, Ordering::SeqCst);
+        self.update_status(|status| status.state = LiveScraperState::Paused)
+    }
+
+    pub fn resume(&self) -> Result<(), GlobleError> {
+        self.is_paused.store(false, Ordering::SeqCst);
+        self.update_status(|status| {
+            if status.state == LiveScraperState::Paused {
+                status.state = LiveScraperState::Running;
+            }
+        })
     }
 
     pub fn is_running(&self) -> bool {
@@ -109,7 +332,82 @@ impl LiveScraper {
         self.is_running.load(Ordering::SeqCst)
     }
 
-    pub async fn run(&self) -> Result<(), GlobleError> {
+    pub fn get_status(&self) -> Result<LiveScraperStatus, GlobleError> {
+        Ok(self.lifecycle.lock()?.clone())
+    }
+
+    /// Applies the change to the status and sends it to the window.
+    fn update_status(
+        &self,
+        update: impl FnOnce(&mut LiveScraperStatus),
+    ) -> Result<(), GlobleError> {
+        let status = {
+            let mut status = self.lifecycle.lock()?;
+            update(&mut status);
+            status.clone()
+        };
+        helper::send_message_to_window("live_scraper_status", Some(json!(status)));
+        Ok(())
+    }
+
+    fn set_state(&self, state: LiveScraperState) {
+        if let Err(e) = self.update_status(|status| status.state = state) {
+            logger::error_con("LiveScraper:Status", format!("{:?}", e).as_str());
+        }
+    }
+
+    fn set_error(&self, error: &GlobleError) {
+        helper::send_message_to_window(
+            "live_scraper_error",
+            Some(json!({ "error": format!("{:?}", error) })),
+        );
+        let _ = self.update_status(|status| {
+            status.state = LiveScraperState::Error;
+            status.error = Some(format!("{:?}", error));
+            status.retry_at = None;
+        });
+    }
+
+    /// Waits `delay` seconds before the next run, returns early when the loop is stopped.
+    async fn backoff(&self, error: &GlobleError, errors: i64, delay: i64) {
+        logger::info_con(
+            "LiveScraper",
+            format!("Retrying in {} seconds, attempt {}", delay, errors).as_str(),
+        );
+        let retry_at = chrono::Utc::now() + chrono::Duration::seconds(delay);
+        let _ = self.update_status(|status| {
+            status.state = LiveScraperState::Backoff;
+            status.error = Some(format!("{:?}", error));
+            status.consecutive_errors = errors;
+            status.retry_at = Some(retry_at.to_rfc3339());
+        });
+        for _ in 0..delay {
+            if !self.is_running() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+        let paused = self.is_paused.load(Ordering::SeqCst);
+        let _ = self.update_status(|status| {
+            status.retry_at = None;
+            if status.state == LiveScraperState::Backoff {
+                status.state = if paused {
+                    LiveScraperState::Paused
+                } else {
+                    LiveScraperState::Running
+                };
+            }
+        });
+    }
+
+    async fn wait_while_paused(&self) {
+        while self.is_paused.load(Ordering::SeqCst) && self.is_running() {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    }
+
+    /// Checks the items that are due and returns how long to wait before the next run.
+    pub async fn run(&self) -> Result<Duration, GlobleError> {
         let buy_sell_overlap = self.get_buy_sell_overlap().await?;
         let settings = self.settings.lock()?.clone();
         let db = self.db.lock()?.clone();
@@ -119,6 +417,29 @@ impl LiveScraper {
         let whitelist = settings.whitelist.clone();
         // Call the database to get the inventory names
         let inventory_names = db.get_inventory_names().await?;
+        // Items that are only checked because an alert rule watches them
+        let alert_items = self.alerts.lock()?.get_watched_items()?;
+        // Alert only items are not in the overlap, their stats come from the price history
+        let price_history_df = if alert_items.is_empty() {
+            DataFrame::default()
+        } else {
+            self.price_scraper.lock()?.get_price_historys()?
+        };
+        // Price history and whisper history used to spot fake and stale orders
+        let price_summary = self.price_scraper.lock()?.get_price_summary()?;
+        let unresponsive_users: HashSet<String> = if settings.outliers.unanswered_whispers > 0 {
+            let answered_before = chrono::Utc::now()
+                - chrono::Duration::hours(settings.outliers.whisper_response_hours.max(0));
+            db.get_unresponsive_users(
+                settings.outliers.unanswered_whispers,
+                &answered_before.to_rfc3339(),
+            )
+            .await?
+            .into_iter()
+            .collect()
+        } else {
+            HashSet::new()
+        };
 
         // Get interesting items from buy_sell_overlap
         let interesting_items: Vec<String> = match helper::get_column_values(
@@ -134,6 +455,8 @@ impl LiveScraper {
         // Get current orders from Warframe Market Sell and Buy orders
         let (mut current_buy_orders_df, current_sell_orders_df) =
             wfm.get_ordres_data_frames().await?;
+        // Alerts look up our buy order of any item, including items only an alert watches
+        let all_buy_orders_df = current_buy_orders_df.clone();
 
         if current_buy_orders_df.shape().0 != 0 {
             current_buy_orders_df = current_buy_orders_df
@@ -162,32 +485,161 @@ impl LiveScraper {
                 .collect()?;
         }
 
-        // Combine inventory_names and interesting_items and whitelist
-        let all_interesting_items = inventory_names
-            .clone()
+        // Items of the best set arbitrage opportunities that made it into the overlap go first
+        let arbitrage_items: Vec<String> = self
+            .get_arbitrage_items(&settings)?
             .into_iter()
-            .chain(interesting_items.clone().into_iter())
-            .chain(whitelist.into_iter())
-            .collect::<HashSet<_>>()
-            .into_iter()
-            .collect::<Vec<_>>();
-        // Remove duplicates
-        let all_interesting_items: HashSet<String> = HashSet::from_iter(all_interesting_items);
+            .filter(|item| interesting_items.contains(item))
+            .collect();
+
+        // Combine interesting_items, inventory_names, whitelist and alert items without
+        // duplicates, keeping the order of the overlap so the best scored items go first
+        let mut all_interesting_items: Vec<String> = vec![];
+        for item in arbitrage_items
+            .iter()
+            .chain(interesting_items.iter())
+            .chain(inventory_names.iter())
+            .chain(whitelist.iter())
+            .chain(alert_items.iter())
+        {
+            if !all_interesting_items.contains(item) {
+                all_interesting_items.push(item.clone());
+            }
+        }
 
         logger::info_file(
             "LiveScraper",
             format!("Interesting items: {:?}", all_interesting_items).as_str(),
             Some(self.log_file.as_str()),
         );
-        // Loop through all interesting items
-        for item in all_interesting_items.clone() {
+
+        // Items with an order of ours, and the expected profit of buying at the best buy price,
+        // decide how often an item is checked
+        let mut schedules: HashMap<String, ItemSchedule> = HashMap::new();
+        let mut ordered_items: HashSet<String> = inventory_names.iter().cloned().collect();
+        let mut expected_profits: HashMap<String, f64> = HashMap::new();
+        let due_items = if settings.scheduler.enabled {
+            for schedule in db.get_item_schedules().await? {
+                schedules.insert(schedule.item_url.clone(), schedule);
+            }
+            for df in [&current_buy_orders_df, &current_sell_orders_df] {
+                if df.height() == 0 {
+                    continue;
+                }
+                match helper::get_column_values(df.clone(), None, "url_name", ColumnType::String)? {
+                    ColumnValues::String(values) => ordered_items.extend(values),
+                    _ => {
+                        return Err(GlobleError::OtherError(
+                            "Expected string values".to_string(),
+                        ))
+                    }
+                };
+            }
+            let profits = match helper::get_column_values(
+                buy_sell_overlap
+                    .clone()
+                    .lazy()
+                    // Missing values would shift the profits against the item names
+                    .select([(col("closedAvg") - col("maxBuy"))
+                        .fill_null(lit(0.0))
+                        .alias("profit")])
+                    .collect()?,
+                None,
+                "profit",
+                ColumnType::F64,
+            )? {
+                ColumnValues::F64(values) => values,
+                _ => return Err(GlobleError::OtherError("Expected f64 values".to_string())),
+            };
+            expected_profits = interesting_items
+                .iter()
+                .cloned()
+                .zip(profits.into_iter())
+                .collect();
+            scheduler::get_due_items(&all_interesting_items, &schedules, chrono::Utc::now())
+        } else {
+            all_interesting_items.clone()
+        };
+
+        // Loop through the interesting items that are due
+        let total_items = due_items.len();
+        for (index, item) in due_items.into_iter().enumerate() {
+            self.wait_while_paused().await;
             if self.is_running() == false || item == "" {
                 break;
             }
+            self.update_status(|status| {
+                status.current_item = Some(item.clone());
+                status.processed_items = index;
+                status.total_items = total_items;
+            })?;
 
             logger::info_con("LiveScraper", format!("Checking item: {item}").as_str());
 
-            let mut item_live_orders_df = wfm.get_ordres_by_item(&item).await?;
+            // With the socket connected the book is kept up to date in memory,
+            // only stale books are fetched again
+            let socket = self.socket.lock()?.clone();
+            let max_age =
+                Duration::from_secs(settings.websocket.full_refresh_minutes.max(0) as u64 * 60);
+            let pushed_max_age =
+                Duration::from_secs(settings.websocket.pushed_order_minutes.max(0) as u64 * 60);
+            let in_game_name = self.auth.lock()?.ingame_name.clone();
+            let reschedule = |best_prices| {
+                scheduler::next_schedule(
+                    &item,
+                    schedules.get(&item),
+                    &settings.scheduler,
+                    ordered_items.contains(&item),
+                    expected_profits.get(&item).cloned().unwrap_or(0.0),
+                    best_prices,
+                    chrono::Utc::now(),
+                )
+            };
+            let (item_orders, changed) = match socket.get_orders(&item, max_age, pushed_max_age)? {
+                Some(book) => book,
+                None => {
+                    let orders = wfm.get_item_orders(&item).await?;
+                    if socket.is_connected() {
+                        socket.watch(&item, orders.clone())?;
+                    }
+                    (orders, true)
+                }
+            };
+            if settings.scheduler.enabled {
+                let best_prices = scheduler::get_best_prices(&item_orders, &in_game_name);
+                db.upsert_item_schedule(&reschedule(Some(best_prices)))
+                    .await?;
+            }
+            // An unchanged book would only record the same snapshot again
+            if changed {
+                let order_book = self.order_book.lock()?.clone();
+                if let Err(e) = order_book.record(&item, &item_orders, &in_game_name).await {
+                    logger::error_con(
+                        "LiveScraper:OrderBook",
+                        format!("Item: {}, Error: {:?}", item, e).as_str(),
+                    );
+                }
+            }
+            let now = chrono::Utc::now();
+            let (item_orders, mut excluded) =
+                trust::filter_orders(item_orders, &settings.trust, &in_game_name, now);
+            let (item_orders, suspicious) = outlier::filter_orders(
+                item_orders,
+                &settings.outliers,
+                price_summary.get(&item),
+                &unresponsive_users,
+                &in_game_name,
+                now,
+            );
+            excluded.extend(suspicious);
+            for order in excluded.iter() {
+                logger::info_file(
+                    "LiveScraper:Trust",
+                    format!("Item: {}, Excluded: {:?}", item, order).as_str(),
+                    Some(self.log_file.as_str()),
+                );
+            }
+            let mut item_live_orders_df = wfm.convert_item_orders_to_datafream(item_orders)?;
             logger::log_dataframe(
                 &mut item_live_orders_df,
                 "live_scraper_item_live_orders_df.csv",
@@ -201,6 +653,25 @@ impl LiveScraper {
                 .lazy()
                 .filter(col("name").eq(lit(item.clone())))
                 .collect()?;
+
+            let alert_stats = if item_stats.height() == 0 && alert_items.contains(&item) {
+                self.get_history_stats(&price_history_df, &item)?
+            } else {
+                item_stats.clone()
+            };
+            self.check_alerts(
+                &item,
+                &item_live_orders_df,
+                &alert_stats,
+                &all_buy_orders_df,
+            )?;
+            if !interesting_items.contains(&item)
+                && !inventory_names.contains(&item)
+                && !whitelist.contains(&item)
+            {
+                continue;
+            }
+
             // Check if item is in all_interesting_items
             if !interesting_items.contains(&item) {
                 logger::info_file(
@@ -275,6 +746,11 @@ impl LiveScraper {
             .await?;
         }
 
+        self.update_status(|status| {
+            status.current_item = None;
+            status.processed_items = status.total_items;
+        })?;
+
         // if current_sell_orders_df.height() != 0 {}
         logger::log_dataframe(
             &mut current_buy_orders_df.clone(),
@@ -284,9 +760,108 @@ impl LiveScraper {
             &mut current_sell_orders_df.clone(),
             "live_scraper_current_sell_orders_df.csv",
         );
+        if !settings.scheduler.enabled {
+            return Ok(Duration::from_secs(1));
+        }
+        let schedules: HashMap<String, ItemSchedule> = db
+            .get_item_schedules()
+            .await?
+            .into_iter()
+            .map(|schedule| (schedule.item_url.clone(), schedule))
+            .collect();
+        Ok(scheduler::get_wait(
+            &settings.scheduler,
+            &all_interesting_items,
+            &schedules,
+            chrono::Utc::now(),
+        ))
+    }
+
+    fn check_alerts(
+        &self,
+        item_name: &str,
+        item_live_orders_df: &DataFrame,
+        item_stats: &DataFrame,
+        current_buy_orders: &DataFrame,
+    ) -> Result<(), GlobleError> {
+        let alerts = self.alerts.lock()?.clone();
+        let in_game_name = self.auth.lock()?.clone().ingame_name;
+
+        // Get the price of our own buy order for the item if we have one
+        let my_buy_price: Option<i64> = if current_buy_orders.height() == 0 {
+            None
+        } else {
+            match helper::get_column_value(
+                current_buy_orders.clone(),
+                Some(col("url_name").eq(lit(item_name))),
+                "platinum",
+                ColumnType::I64,
+            )? {
+                ColumnValue::I64(values) => values,
+                _ => return Err(GlobleError::OtherError("Expected i64 values".to_string())),
+            }
+        };
+
+        // Our own orders should never trigger an alert
+        let live_orders_df = item_live_orders_df
+            .clone()
+            .lazy()
+            .filter(col("username").neq(lit(in_game_name)))
+            .collect()?;
+        alerts.evaluate(item_name, &live_orders_df, item_stats, my_buy_price)?;
         Ok(())
     }
 
+    /// The sets and components of the best arbitrage opportunities that are in the overlap.
+    fn get_arbitrage_items(&self, settings: &SettingsState) -> Result<Vec<String>, GlobleError> {
+        if !settings.arbitrage.feed_live_scraper {
+            return Ok(vec![]);
+        }
+        let arbitrage = self.arbitrage.lock()?.clone();
+        let items = match arbitrage.get_interesting_items(
+            settings.arbitrage.min_profit as f64,
+            settings.arbitrage.max_sets as usize,
+        ) {
+            Ok(items) => items,
+            Err(e) => {
+                logger::error_con("LiveScraper:Arbitrage", format!("{:?}", e).as_str());
+                return Ok(vec![]);
+            }
+        };
+        logger::info_file(
+            "LiveScraper",
+            format!("Arbitrage items: {:?}", items).as_str(),
+            Some(self.log_file.as_str()),
+        );
+        Ok(items)
+    }
+
+    /// The closed average and week price shift of an item that is not in the buy/sell overlap,
+    /// in the columns the overlap uses.
+    fn get_history_stats(&self, df: &DataFrame, item: &str) -> Result<DataFrame, GlobleError> {
+        if df.height() == 0 {
+            return Ok(DataFrame::default());
+        }
+        let closed_df = df
+            .clone()
+            .lazy()
+            .filter(
+                col("order_type")
+                    .eq(lit("closed"))
+                    .and(col("name").eq(lit(item))),
+            )
+            .collect()?;
+        if closed_df.height() == 0 {
+            return Ok(DataFrame::default());
+        }
+        let closed_avg = closed_df.column("avg_price")?.mean().unwrap_or(0.0);
+        Ok(DataFrame::new(vec![
+            Series::new("name", &[item]),
+            Series::new("closedAvg", &[closed_avg]),
+            Series::new("priceShift", &[self.get_week_increase(df, item)?]),
+        ])?)
+    }
+
     fn get_week_increase(&self, df: &DataFrame, row_name: &str) -> Result<f64, GlobleError> {
         // Pre-filter DataFrame based on "order_type" == "closed"
         let week_df = df
@@ -344,7 +919,6 @@ impl LiveScraper {
     pub async fn get_buy_sell_overlap(&self) -> Result<DataFrame, GlobleError> {
         let settings = self.settings.lock()?.clone();
         let db = self.db.lock()?.clone();
-        println!("{:?}", settings);
         let df = self.price_scraper.lock()?.get_price_historys()?;
         let volume_threshold = settings.volume_threshold;
         let range_threshold = settings.range_threshold;
@@ -375,7 +949,9 @@ impl LiveScraper {
 
         // Call the database to get the inventory names and DataFrame
         let inventory_names = db.get_inventory_names().await?;
-        let inventory_names_s = Series::new("desired_column_name", inventory_names);
+        let inventory_names_s = Series::new("desired_column_name", inventory_names.clone());
+        // Arbitrage items do not need the price range, the set and its parts are the margin
+        let arbitrage_s = Series::new("arbitrage", self.get_arbitrage_items(&settings)?);
 
         // Filters the DataFrame based on the given predicates and returns a new DataFrame.
         // The `volume_threshold` and `range_threshold` arguments are used to filter by volume and range.
@@ -388,7 +964,11 @@ impl LiveScraper {
                 col("order_type").eq(lit("closed")).and(
                     col("volume")
                         .gt(lit(volume_threshold))
-                        .and(col("range").gt(lit(range_threshold)))
+                        .and(
+                            col("range")
+                                .gt(lit(range_threshold))
+                                .or(col("name").is_in(lit(arbitrage_s))),
+                        )
                         .or(col("name").is_in(lit(inventory_names_s.clone()))),
                 ),
             )
@@ -516,8 +1096,120 @@ impl LiveScraper {
             .rename("median", "closedMedian")?
             .rename("weekPriceShift", "priceShift")?;
 
-        logger::log_dataframe(buy_sell_overlap, "live_scraper_buy_sell_overlap.csv");
-        return Ok(buy_sell_overlap.clone());
+        // Add the forecasted price, it replaces the closed average when enabled
+        let forecasts_df = self.forecast.lock()?.get_forecasts_df()?;
+        let mut buy_sell_overlap = buy_sell_overlap.left_join(&forecasts_df, ["name"], ["name"])?;
+        if settings.forecast.use_in_strategy {
+            buy_sell_overlap = buy_sell_overlap
+                .lazy()
+                .with_column(
+                    when(col("forecastPrice").is_not_null())
+                        .then(col("forecastPrice"))
+                        .otherwise(col("closedAvg"))
+                        .alias("closedAvg"),
+                )
+                .collect()?;
+        }
+
+        let mut buy_sell_overlap = self
+            .score_buy_sell_overlap(buy_sell_overlap, &inventory_names)
+            .await?;
+
+        logger::log_dataframe(&mut buy_sell_overlap, "live_scraper_buy_sell_overlap.csv");
+        return Ok(buy_sell_overlap);
+    }
+
+    pub fn get_scores(&self) -> Result<Vec<OpportunityScore>, GlobleError> {
+        Ok(self.scores.lock()?.clone())
+    }
+
+    /// Adds the opportunity score and its breakdown to the buy/sell overlap. Items below the
+    /// minimum score are removed unless they are in the inventory or on the whitelist.
+    async fn score_buy_sell_overlap(
+        &self,
+        buy_sell_overlap: DataFrame,
+        inventory_names: &Vec<String>,
+    ) -> Result<DataFrame, GlobleError> {
+        let settings = self.settings.lock()?.clone();
+        let db = self.db.lock()?.clone();
+        let sellers: HashMap<String, i64> = db
+            .get_latest_order_book_snapshots()
+            .await?
+            .into_iter()
+            .map(|snapshot| (snapshot.item_url, snapshot.ingame_sellers))
+            .collect();
+
+        let names = buy_sell_overlap.column("name")?.utf8()?.clone();
+        let closed_avgs = buy_sell_overlap.column("closedAvg")?.f64()?.clone();
+        let max_buys = buy_sell_overlap.column("maxBuy")?.f64()?.clone();
+        let volumes = buy_sell_overlap.column("closedVol")?.f64()?.clone();
+        let volatilities = buy_sell_overlap
+            .column("forecastVolatility")?
+            .f64()?
+            .clone();
+
+        let mut scores: Vec<OpportunityScore> = vec![];
+        for i in 0..buy_sell_overlap.height() {
+            let name = names.get(i).unwrap_or_default().to_string();
+            let input = ScoreInput {
+                sellers: sellers.get(&name).cloned(),
+                name,
+                closed_avg: closed_avgs.get(i).unwrap_or(0.0),
+                max_buy: max_buys.get(i).unwrap_or(0.0),
+                volume: volumes.get(i).unwrap_or(0.0),
+                volatility: volatilities.get(i),
+            };
+            scores.push(scoring::score_opportunity(&input, &settings.scoring));
+        }
+
+        let buy_sell_overlap = buy_sell_overlap.hstack(&[
+            Series::new("score", scores.iter().map(|s| s.score).collect::<Vec<_>>()),
+            Series::new(
+                "expectedMargin",
+                scores.iter().map(|s| s.expected_margin).collect::<Vec<_>>(),
+            ),
+            Series::new(
+                "timeToSellHours",
+                scores
+                    .iter()
+                    .map(|s| s.time_to_sell_hours)
+                    .collect::<Vec<_>>(),
+            ),
+            Series::new(
+                "sellers",
+                scores.iter().map(|s| s.sellers).collect::<Vec<_>>(),
+            ),
+        ])?;
+        let keep = Series::new(
+            "keep",
+            inventory_names
+                .iter()
+                .chain(settings.whitelist.iter())
+                .cloned()
+                .collect::<Vec<_>>(),
+        );
+        let buy_sell_overlap = buy_sell_overlap
+            .lazy()
+            .filter(
+                col("score")
+                    .gt_eq(lit(settings.scoring.min_score))
+                    .or(col("name").is_in(lit(keep))),
+            )
+            .collect()?;
+        let buy_sell_overlap = if settings.scoring.sort_by_score {
+            helper::sort_dataframe(buy_sell_overlap, "score", true)?
+        } else {
+            buy_sell_overlap
+        };
+
+        scores.sort_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        helper::send_message_to_window("live_scraper_scores", Some(json!(scores)));
+        *self.scores.lock()? = scores;
+        Ok(buy_sell_overlap)
     }
     async fn get_my_order_information(
         &self,
@@ -640,47 +1332,6 @@ impl LiveScraper {
         Ok(true)
     }
 
-    fn knapsack(
-        &self,
-        items: Vec<(i64, f64, String, String)>,
-        max_weight: i64,
-    ) -> Result<
-        (
-            i64,
-            Vec<(i64, f64, String, String)>,
-            Vec<(i64, f64, String, String)>,
-        ),
-        GlobleError,
-    > {
-        let n = items.len();
-        let mut dp = vec![vec![0; (max_weight + 1) as usize]; (n + 1) as usize];
-
-        for i in 1..=n {
-            for w in 1..=max_weight {
-                let (weight, value, _, _) = items[i - 1];
-                if weight <= w {
-                    dp[i][w as usize] =
-                        dp[i - 1][w as usize].max(dp[i - 1][(w - weight) as usize] + value as i64);
-                } else {
-                    dp[i][w as usize] = dp[i - 1][w as usize];
-                }
-            }
-        }
-
-        let mut selected_items = Vec::new();
-        let mut unselected_items = Vec::new();
-        let mut w = max_weight;
-        for i in (0..n).rev() {
-            if dp[i + 1][w as usize] != dp[i][w as usize] {
-                selected_items.push(items[i].clone());
-                w -= items[i].0;
-            } else {
-                unselected_items.push(items[i].clone());
-            }
-        }
-
-        Ok((dp[n][max_weight as usize], selected_items, unselected_items))
-    }
     async fn compare_live_orders_when_buying(
         &self,
         item_name: &str,
@@ -784,7 +1435,7 @@ impl LiveScraper {
         }
 
         // Get highest buy order price
-        let post_price: i64 = match helper::get_column_value(
+        let mut post_price: i64 = match helper::get_column_value(
             live_buy_orders_df.clone(),
             None,
             "platinum",
@@ -794,6 +1445,34 @@ impl LiveScraper {
             _ => return Err(GlobleError::OtherError("Expected i64 values".to_string())),
         };
 
+        // Raise our buy order when it stayed unfilled for too long
+        if active {
+            let created_at = match helper::get_column_value(
+                current_orders.clone(),
+                Some(col("url_name").eq(lit(item_name))),
+                "creation_date",
+                ColumnType::String,
+            )? {
+                ColumnValue::String(values) => values,
+                _ => {
+                    return Err(GlobleError::OtherError(
+                        "Expected string values".to_string(),
+                    ))
+                }
+            };
+            let raised_price = repricing::apply_buy_increase(
+                post_price,
+                item_closed_avg,
+                created_at.as_deref(),
+                &settings.repricing,
+                chrono::Utc::now(),
+            );
+            if raised_price != post_price {
+                logger::info_con("LiveScraper", format!("Buy order for {item_name} is unfilled for too long, raising it from {post_price} to {raised_price} plat").as_str());
+                post_price = raised_price;
+            }
+        }
+
         // Get the average price of the item from the Warframe Market API
         let closed_avg_metric: f64 =
             match helper::get_column_value(item_stats.clone(), None, "closedAvg", ColumnType::F64)?
@@ -878,10 +1557,17 @@ impl LiveScraper {
                     return Ok(None);
                 }
             } else {
-                let mut buy_orders_list: Vec<(i64, f64, String, String)> = vec![];
-                // Create a Vec of Tuples from the DataFrame of current orders
+                let priority = |url_name: &str| {
+                    settings
+                        .allocation
+                        .priorities
+                        .get(url_name)
+                        .cloned()
+                        .unwrap_or(1.0)
+                };
+                let mut candidates: Vec<BuyCandidate> = vec![];
+                // Create the candidates from the DataFrame of current orders
                 if current_orders.shape().0 != 0 {
-                    // Convert to Vec of Tuples
                     let platinum_values = match helper::get_column_values(
                         current_orders.clone(),
                         None,
@@ -927,55 +1613,80 @@ impl LiveScraper {
                             return Err(GlobleError::OtherError("Expected f64 values".to_string()))
                         }
                     };
-                    buy_orders_list = platinum_values
+                    let quantity_values = match helper::get_column_values(
+                        current_orders.clone(),
+                        None,
+                        "quantity",
+                        ColumnType::I64,
+                    )? {
+                        ColumnValues::I64(values) => values,
+                        _ => {
+                            return Err(GlobleError::OtherError("Expected i64 values".to_string()))
+                        }
+                    };
+                    candidates = platinum_values
                         .into_iter()
                         .zip(potential_profit_values.into_iter())
                         .zip(url_name_values.into_iter())
                         .zip(id_values.into_iter())
-                        .map(|(((platinum, profit), url_name), id)| {
-                            (platinum, profit, url_name, id)
-                        })
+                        .zip(quantity_values.into_iter())
+                        .map(
+                            |((((platinum, profit), url_name), id), quantity)| BuyCandidate {
+                                priority: priority(&url_name),
+                                url_name,
+                                order_id: id,
+                                price: platinum,
+                                profit,
+                                quantity,
+                            },
+                        )
                         .collect();
                 }
-                buy_orders_list.append(&mut vec![(
-                    post_price,
-                    potential_profit,
-                    item_name.to_string(),
-                    "".to_string(),
-                )]);
-
-                let (max_profit, selected_buy_orders, unselected_buy_orders) =
-                    self.knapsack(buy_orders_list, max_total_price_cap as i64)?;
+                candidates.push(BuyCandidate {
+                    url_name: item_name.to_string(),
+                    order_id: "".to_string(),
+                    price: post_price,
+                    profit: potential_profit,
+                    quantity: 1,
+                    priority: priority(item_name),
+                });
+
+                let allocation = allocation::allocate(
+                    candidates,
+                    max_total_price_cap as i64,
+                    &settings.allocation,
+                );
 
                 logger::debug_file(
                     "LiveScraper",
                     format!(
-                        "Name: {item_name}, MaxProfit: {max_profit}, {:?}, {:?}",
-                        selected_buy_orders, unselected_buy_orders
+                        "Name: {item_name}, Value: {}, Cost: {}, {:?}, {:?}",
+                        allocation.value,
+                        allocation.cost,
+                        allocation.selected,
+                        allocation.unselected
                     )
                     .as_str(),
                     Some("knapsack.log"),
                 );
 
-                let selected_item_names: Vec<String> = selected_buy_orders
+                let selected_item_names: Vec<String> = allocation
+                    .selected
                     .iter()
-                    .map(|order| order.2.clone())
+                    .map(|order| order.url_name.clone())
                     .collect();
 
                 if selected_item_names.contains(&item_name.to_string()) {
-                    logger::debug_file(
-                        "LiveScraper",
-                        format!(
-                            "Name: {item_name}, MaxProfit: {max_profit}, {:?}, {:?}",
-                            selected_buy_orders, unselected_buy_orders
-                        )
-                        .as_str(),
-                        Some("knapsack2.log"),
-                    );
-                    if !unselected_buy_orders.is_empty() {
-                        let unselected_item_names: Vec<String> = unselected_buy_orders
+                    // Only orders that are already posted have to be removed
+                    let unselected_orders: Vec<&BuyCandidate> = allocation
+                        .unselected
+                        .iter()
+                        .filter(|order| !order.order_id.is_empty())
+                        .collect();
+                    if !unselected_orders.is_empty() {
+                        let unselected_item_names: Vec<String> = unselected_orders
                             .iter()
-                            .map(|order| order.2.clone())
+                            .map(|order| order.url_name.clone())
                             .collect();
 
                         current_orders =
@@ -986,14 +1697,19 @@ impl LiveScraper {
                                 ))
                                 .collect()?;
 
-                        for unselected_item in &unselected_buy_orders {
-                            wfm.delete_order(unselected_item.3.as_str(), item_name, item_id, "buy")
-                                .await?;
+                        for unselected_order in unselected_orders {
+                            wfm.delete_order(
+                                unselected_order.order_id.as_str(),
+                                item_name,
+                                item_id,
+                                "buy",
+                            )
+                            .await?;
                             logger::debug_con(
                                 "component",
                                 format!(
                                     "DELETED BUY order for {} since it is not as optimal",
-                                    unselected_item.2
+                                    unselected_order.url_name
                                 )
                                 .as_str(),
                             );
@@ -1032,8 +1748,10 @@ impl LiveScraper {
         _item_stats: &DataFrame,
         _inventory_df: &DataFrame,
     ) -> Result<(), GlobleError> {
+        let settings = self.settings.lock()?.clone();
         let wfm = self.wfm.lock()?.clone();
         let db = self.db.lock()?.clone();
+        let notifications = self.notifications.lock()?.clone();
 
         // Get the current orders for the item from the Warframe Market API
         let (order_id, visibility, price, active) = self
@@ -1087,7 +1805,8 @@ impl LiveScraper {
 
         // If there are no buyers, update order to be 30p above average price
         if sellers == 0 {
-            let post_price = (avg_price + 30) as i64;
+            let post_price =
+                self.apply_sell_decay(item_name, (avg_price + 30) as i64, &inventory, &settings);
             db.update_inventory_by_url(item_name.to_string(), Some(post_price))
                 .await?;
             if active {
@@ -1129,7 +1848,14 @@ impl LiveScraper {
                 "LiveScraper",
                 format!("Item {item_name} is too cheap. Not putting up a sell order.").as_str(),
             );
-            // SEND NOTIFICATION TO DISCORD HERE AND SAY THAT THE ITEM IS TOO CHEAP
+            notifications.notify(
+                "item_too_cheap",
+                "Item too cheap",
+                format!(
+                    "{item_name} is listed for {post_price} plat, your average buy price is {avg_price} plat"
+                )
+                .as_str(),
+            );
         }
 
         if post_price + 10 > post_price && sellers >= 2 {
@@ -1137,6 +1863,7 @@ impl LiveScraper {
         } else {
             post_price = (avg_price + 10).max(post_price);
         }
+        let post_price = self.apply_sell_decay(item_name, post_price, &inventory, &settings);
         if active {
             if price != post_price {
                 wfm.update_order_listing(
@@ -1178,6 +1905,26 @@ impl LiveScraper {
         }
         Ok(())
     }
+    /// Lowers the sell price when the listing stayed unsold for too long.
+    fn apply_sell_decay(
+        &self,
+        item_name: &str,
+        post_price: i64,
+        inventory: &Invantory,
+        settings: &SettingsState,
+    ) -> i64 {
+        let decayed_price = repricing::apply_sell_decay(
+            post_price,
+            inventory.price,
+            inventory.listed_at.as_deref(),
+            &settings.repricing,
+            chrono::Utc::now(),
+        );
+        if decayed_price != post_price {
+            logger::info_con("LiveScraper", format!("Sell order for {item_name} is unsold for too long, lowering it from {post_price} to {decayed_price} plat").as_str());
+        }
+        decayed_price
+    }
     fn get_new_buy_data(
         &self,
         current_orders: DataFrame,
@@ -1196,3 +1943,14 @@ impl LiveScraper {
         Ok(helper::merge_dataframes(vec![current_orders, order_df])?)
     }
 }
+
+/// Network errors, rate limits and server errors are worth retrying, anything else stops the loop.
+pub(crate) fn is_transient(error: &GlobleError) -> bool {
+    match error {
+        GlobleError::ReqwestError(_) | GlobleError::TooManyRequests(_) => true,
+        GlobleError::HttpError(status, _, _) => {
+            status.is_server_error() || *status == reqwest::StatusCode::TOO_MANY_REQUESTS
+        }
+        _ => false,
+    }
+}
@@ -138,6 +138,17 @@ pub fn trace_con(component: &str, msg: &str) {
 }
 
 
+pub fn warning(component: &str, msg: &str, console: bool, file: Option<&str>) {
+    dolog(1, component, msg, console, file);
+}
+pub fn warning_file(component: &str, msg: &str, file: Option<&str>) {
+    warning( component, msg,false, file);
+}
+pub fn warning_con(component: &str, msg: &str) {
+    warning( component, msg,true, None);
+}
+
+
 /// Logs the given DataFrame to a CSV file with the given name in the log folder.
 /// The `df` argument is a mutable reference to the DataFrame to be logged.
 /// The `name` argument is a string representing the name of the CSV file to be created.
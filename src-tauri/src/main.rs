@@ -1,30 +1,71 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+use alerts::{Alert, AlertEngine};
+use arbitrage::{ArbitrageOpportunity, ArbitrageState};
 use auth::AuthState;
+use bulk::{BulkOrderState, BulkOrderSummary, BulkPriceMode};
 use cache::CacheState;
 use database::DatabaseClient;
+use ducat::{DucatBuyCandidate, DucatState, DucatValue, TrashToBaroEntry};
+use forecast::{ForecastState, ItemForecast};
+use item_search::{ItemSearchFilter, ItemSearchResult};
+use notification::NotificationState;
+use order_book::OrderBookState;
 use price_scraper::PriceScraper;
+use reconciliation::{Mismatch, ReconcileFix, ReconciliationState};
+use relic::{RelicState, RelicValue};
+use riven::{RivenPriceEstimate, RivenSearchFilter, RivenState};
+use scoring::OpportunityScore;
 use serde_json::{json, Value};
-use settings::SettingsState;
+use settings::{SettingsFieldError, SettingsState};
+use settings_presets::{SettingsPreset, SettingsPreview};
+use status::StatusState;
 use std::sync::Arc;
 use std::{env, sync::Mutex};
-use structs::{GlobleError, Invantory, Transaction};
+use structs::{
+    Auction, AuctionBid, ClosedOrders, DropSource, GlobleError, Invantory, ItemStatistics,
+    OrderBookSnapshot, Review, RivenItem, Transaction, UserProfile,
+};
 use tauri::async_runtime::block_on;
 use tauri::{App, Manager};
+use websocket::WFMSocketState;
 use wfm_client::WFMClientState;
 mod structs;
 mod whisper_scraper;
 use whisper_scraper::WhisperScraper; // add this line
 mod live_scraper;
-use live_scraper::LiveScraper;
+use live_scraper::{LiveScraper, LiveScraperStatus};
 
+mod alerts;
+mod allocation;
+mod arbitrage;
 mod auth;
+mod bulk;
 mod cache;
 mod database;
+mod ducat;
+#[cfg(test)]
+mod fixtures;
+mod forecast;
 mod helper;
+mod item_search;
 mod logger;
+mod notification;
+mod order_book;
+mod outlier;
 mod price_scraper;
+mod price_source;
+mod reconciliation;
+mod relic;
+mod repricing;
+mod riven;
+mod scheduler;
+mod scoring;
 mod settings;
+mod settings_presets;
+mod status;
+mod trust;
+mod websocket;
 mod wfm_client;
 
 use helper::WINDOW as HE_WINDOW;
@@ -36,6 +77,7 @@ async fn setup(
     wfm: tauri::State<'_, Arc<Mutex<WFMClientState>>>,
     cache: tauri::State<'_, Arc<Mutex<CacheState>>>,
     db: tauri::State<'_, Arc<Mutex<DatabaseClient>>>,
+    order_book: tauri::State<'_, Arc<Mutex<OrderBookState>>>,
 ) -> Result<Value, GlobleError> {
     let settings = settings.lock()?.clone();
     let auth = auth.lock()?.clone();
@@ -44,6 +86,10 @@ async fn setup(
     cache.update_cache().await?;
     let db = db.lock()?.clone();
     db.initialize().await?;
+    let order_book = order_book.lock()?.clone();
+    if let Err(e) = order_book.clean_up().await {
+        logger::error_con("OrderBook", format!("Clean Up: {:?}", e).as_str());
+    }
 
     // Check if the user access token is valid
     // let valid = wfm.validate().await?;
@@ -93,8 +139,7 @@ async fn create_invantory_entry(
     let db = db.lock()?.clone();
     let invantory = db
         .create_inventory_entry(id, report, quantity, price, rank)
-        .await
-        .unwrap();
+        .await?;
     Ok(invantory)
 }
 
@@ -110,8 +155,7 @@ async fn create_transaction_entry(
     let db = db.lock()?.clone();
     let transaction = db
         .create_transaction_entry(id, ttype, quantity, rank, price)
-        .await
-        .unwrap();
+        .await?;
     Ok(transaction)
 }
 
@@ -165,27 +209,476 @@ fn toggle_live_scraper(live_scraper: tauri::State<'_, Arc<std::sync::Mutex<LiveS
     }
 }
 
+#[tauri::command]
+fn pause_live_scraper(
+    live_scraper: tauri::State<'_, Arc<Mutex<LiveScraper>>>,
+) -> Result<(), GlobleError> {
+    let live_scraper = live_scraper.lock()?.clone();
+    live_scraper.pause()
+}
+
+#[tauri::command]
+fn resume_live_scraper(
+    live_scraper: tauri::State<'_, Arc<Mutex<LiveScraper>>>,
+) -> Result<(), GlobleError> {
+    let live_scraper = live_scraper.lock()?.clone();
+    live_scraper.resume()
+}
+
+#[tauri::command]
+fn get_live_scraper_status(
+    live_scraper: tauri::State<'_, Arc<Mutex<LiveScraper>>>,
+) -> Result<LiveScraperStatus, GlobleError> {
+    let live_scraper = live_scraper.lock()?.clone();
+    live_scraper.get_status()
+}
+
+/// Saves the settings when they are valid, otherwise returns the field errors and keeps the
+/// current settings.
 #[tauri::command]
 async fn update_settings(
     settings: SettingsState,
-    settings_state: tauri::State<'_, Arc<std::sync::Mutex<SettingsState>>>,
+    settings_state: tauri::State<'_, Arc<Mutex<SettingsState>>>,
+    live_scraper: tauri::State<'_, Arc<Mutex<LiveScraper>>>,
+    socket: tauri::State<'_, Arc<Mutex<WFMSocketState>>>,
+) -> Result<Vec<SettingsFieldError>, GlobleError> {
+    let errors = settings.validate();
+    if !errors.is_empty() {
+        return Ok(errors);
+    }
+    let settings = SettingsState {
+        version: settings::SETTINGS_VERSION,
+        ..settings
+    };
+    settings.save_to_file()?;
+    let previous = std::mem::replace(&mut *settings_state.lock()?, settings.clone());
+
+    // The scrapers read the shared settings on every loop, only the socket has to be told
+    let socket = socket.lock()?.clone();
+    if previous.websocket.enabled && !settings.websocket.enabled {
+        socket.stop()?;
+    } else if !previous.websocket.enabled
+        && settings.websocket.enabled
+        && live_scraper.lock()?.is_running()
+    {
+        socket.start();
+    }
+    helper::send_message_to_window("settings_updated", Some(json!(settings)));
+    Ok(vec![])
+}
+
+#[tauri::command]
+fn validate_settings(settings: SettingsState) -> Vec<SettingsFieldError> {
+    settings.validate()
+}
+
+#[tauri::command]
+fn get_settings_presets(
+    cache: tauri::State<'_, Arc<Mutex<CacheState>>>,
+) -> Result<Vec<SettingsPreset>, GlobleError> {
+    let cache = cache.lock()?.clone();
+    settings_presets::get_presets(&cache)
+}
+
+#[tauri::command]
+fn save_settings_preset(
+    name: String,
+    description: String,
+    settings: tauri::State<'_, Arc<Mutex<SettingsState>>>,
+) -> Result<SettingsPreset, GlobleError> {
+    let settings = settings.lock()?.clone();
+    settings_presets::save_preset(&name, &description, &settings)
+}
+
+#[tauri::command]
+fn delete_settings_preset(name: String) -> Result<(), GlobleError> {
+    settings_presets::delete_preset(&name)
+}
+
+/// Shows what the preset would change, apply it by passing the settings to update_settings.
+#[tauri::command]
+fn preview_settings_preset(
+    name: String,
+    settings: tauri::State<'_, Arc<Mutex<SettingsState>>>,
+    cache: tauri::State<'_, Arc<Mutex<CacheState>>>,
+) -> Result<SettingsPreview, GlobleError> {
+    let settings = settings.lock()?.clone();
+    let cache = cache.lock()?.clone();
+    let preset = settings_presets::get_preset(&cache, &name)?;
+    settings_presets::preview(&settings, preset.settings)
+}
+
+#[tauri::command]
+fn export_settings(
+    settings: tauri::State<'_, Arc<Mutex<SettingsState>>>,
+) -> Result<String, GlobleError> {
+    let settings = settings.lock()?.clone();
+    settings_presets::export(&settings)
+}
+
+/// Shows what the imported JSON would change, apply it by passing the settings to
+/// update_settings.
+#[tauri::command]
+fn preview_settings_import(
+    json: String,
+    settings: tauri::State<'_, Arc<Mutex<SettingsState>>>,
+) -> Result<SettingsPreview, GlobleError> {
+    let settings = settings.lock()?.clone();
+    settings_presets::preview(&settings, serde_json::from_str(&json)?)
+}
+
+#[tauri::command]
+fn set_user_blocked(
+    username: String,
+    blocked: bool,
+    settings_state: tauri::State<'_, Arc<Mutex<SettingsState>>>,
+) -> Result<SettingsState, GlobleError> {
+    let mut settings = settings_state.lock()?;
+    settings
+        .trust
+        .blocklist
+        .retain(|name| !name.eq_ignore_ascii_case(&username));
+    if blocked {
+        settings.trust.blocklist.push(username);
+    }
+    settings.save_to_file()?;
+    Ok(settings.clone())
+}
+
+#[tauri::command]
+async fn record_whisper_sent(
+    username: String,
+    db: tauri::State<'_, Arc<Mutex<DatabaseClient>>>,
 ) -> Result<(), GlobleError> {
-    let arced_mutex = Arc::clone(&settings_state);
-    let mut my_lock = arced_mutex.lock()?;
-    my_lock.volume_threshold = settings.volume_threshold;
-    my_lock.range_threshold = settings.range_threshold;
-    my_lock.avg_price_cap = settings.avg_price_cap;
-    my_lock.max_total_price_cap = settings.max_total_price_cap;
-    my_lock.price_shift_threshold = settings.price_shift_threshold;
-    my_lock.blacklist = settings.blacklist;
-    my_lock.whitelist = settings.whitelist;
-    my_lock.strict_whitelist = settings.strict_whitelist;
-    my_lock.ping_on_notif = settings.ping_on_notif;
-    my_lock.webhook = settings.webhook;
-    my_lock.save_to_file().expect("Could not save settings");
+    let db = db.lock()?.clone();
+    Ok(db.create_whisper_entry(&username, "sent").await?)
+}
+
+#[tauri::command]
+async fn send_test_notification(
+    index: usize,
+    notifications: tauri::State<'_, Arc<Mutex<NotificationState>>>,
+) -> Result<(), GlobleError> {
+    let notifications = notifications.lock()?.clone();
+    notifications.send_test(index).await?;
     Ok(())
 }
 
+#[tauri::command]
+fn get_alert_history(
+    alerts: tauri::State<'_, Arc<Mutex<AlertEngine>>>,
+) -> Result<Vec<Alert>, GlobleError> {
+    let alerts = alerts.lock()?.clone();
+    Ok(alerts.get_history()?)
+}
+
+#[tauri::command]
+fn search_items(
+    query: String,
+    filter: Option<ItemSearchFilter>,
+    limit: Option<usize>,
+    cache: tauri::State<'_, Arc<Mutex<CacheState>>>,
+) -> Result<Vec<ItemSearchResult>, GlobleError> {
+    let cache = cache.lock()?.clone();
+    cache.search_items(
+        query.as_str(),
+        &filter.unwrap_or_default(),
+        limit.unwrap_or(25),
+    )
+}
+
+#[tauri::command]
+async fn get_set_arbitrage(
+    live: bool,
+    min_profit: Option<f64>,
+    limit: Option<usize>,
+    arbitrage: tauri::State<'_, Arc<Mutex<ArbitrageState>>>,
+) -> Result<Vec<ArbitrageOpportunity>, GlobleError> {
+    let arbitrage = arbitrage.lock()?.clone();
+    let min_profit = min_profit.unwrap_or(0.0);
+    if live {
+        Ok(arbitrage
+            .get_opportunities_from_live(min_profit, limit.unwrap_or(5))
+            .await?)
+    } else {
+        let mut opportunities = arbitrage.get_opportunities_from_history(min_profit)?;
+        opportunities.truncate(limit.unwrap_or(50));
+        Ok(opportunities)
+    }
+}
+
+#[tauri::command]
+async fn get_ducat_analysis(
+    ducats: tauri::State<'_, Arc<Mutex<DucatState>>>,
+) -> Result<Vec<DucatValue>, GlobleError> {
+    let ducats = ducats.lock()?.clone();
+    if let Err(e) = ducats.update_known_ducats().await {
+        logger::error_con("Ducats", format!("Update Known Ducats: {:?}", e).as_str());
+    }
+    ducats.get_ducat_values()
+}
+
+#[tauri::command]
+async fn get_trash_to_baro(
+    ducats: tauri::State<'_, Arc<Mutex<DucatState>>>,
+) -> Result<Vec<TrashToBaroEntry>, GlobleError> {
+    let ducats = ducats.lock()?.clone();
+    Ok(ducats.get_trash_to_baro().await?)
+}
+
+#[tauri::command]
+async fn get_ducat_buy_candidates(
+    live: bool,
+    limit: Option<usize>,
+    ducats: tauri::State<'_, Arc<Mutex<DucatState>>>,
+) -> Result<Vec<DucatBuyCandidate>, GlobleError> {
+    let ducats = ducats.lock()?.clone();
+    Ok(ducats
+        .get_buy_candidates(live, limit.unwrap_or(if live { 10 } else { 50 }))
+        .await?)
+}
+
+#[tauri::command]
+async fn get_relic_values(
+    refinement: Option<String>,
+    limit: Option<usize>,
+    relics: tauri::State<'_, Arc<Mutex<RelicState>>>,
+) -> Result<Vec<RelicValue>, GlobleError> {
+    let relics = relics.lock()?.clone();
+    let mut values = relics
+        .get_relic_values(refinement.unwrap_or("intact".to_string()).as_str())
+        .await?;
+    values.truncate(limit.unwrap_or(100));
+    Ok(values)
+}
+
+#[tauri::command]
+async fn update_relic_drops(
+    relics: tauri::State<'_, Arc<Mutex<RelicState>>>,
+) -> Result<usize, GlobleError> {
+    let relics = relics.lock()?.clone();
+    Ok(relics.update_relic_drops().await?)
+}
+
+#[tauri::command]
+async fn search_riven_auctions(
+    filter: RivenSearchFilter,
+    rivens: tauri::State<'_, Arc<Mutex<RivenState>>>,
+) -> Result<Vec<Auction>, GlobleError> {
+    let rivens = rivens.lock()?.clone();
+    Ok(rivens.search(&filter).await?)
+}
+
+#[tauri::command]
+async fn get_my_riven_auctions(
+    refresh: bool,
+    rivens: tauri::State<'_, Arc<Mutex<RivenState>>>,
+) -> Result<Vec<Auction>, GlobleError> {
+    let rivens = rivens.lock()?.clone();
+    if refresh {
+        Ok(rivens.update_my_auctions().await?)
+    } else {
+        rivens.get_my_auctions()
+    }
+}
+
+#[tauri::command]
+async fn estimate_riven_price(
+    riven: RivenItem,
+    rivens: tauri::State<'_, Arc<Mutex<RivenState>>>,
+) -> Result<Option<RivenPriceEstimate>, GlobleError> {
+    let rivens = rivens.lock()?.clone();
+    Ok(rivens.estimate_price(&riven).await?)
+}
+
+#[tauri::command]
+async fn get_order_book_history(
+    item: String,
+    days: Option<i64>,
+    order_book: tauri::State<'_, Arc<Mutex<OrderBookState>>>,
+) -> Result<Vec<OrderBookSnapshot>, GlobleError> {
+    let order_book = order_book.lock()?.clone();
+    Ok(order_book.get_history(&item, days.unwrap_or(7)).await?)
+}
+
+#[tauri::command]
+async fn get_price_forecasts(
+    item: Option<String>,
+    forecast: tauri::State<'_, Arc<Mutex<ForecastState>>>,
+) -> Result<Vec<ItemForecast>, GlobleError> {
+    let forecast = forecast.lock()?.clone();
+    match item {
+        Some(item) => Ok(forecast.get_forecast(&item)?.into_iter().collect()),
+        None => Ok(forecast.get_forecasts()?.into_values().collect()),
+    }
+}
+
+#[tauri::command]
+fn get_opportunity_scores(
+    live_scraper: tauri::State<'_, Arc<Mutex<LiveScraper>>>,
+) -> Result<Vec<OpportunityScore>, GlobleError> {
+    let live_scraper = live_scraper.lock()?.clone();
+    live_scraper.get_scores()
+}
+
+#[tauri::command]
+fn get_wfm_socket_state(
+    socket: tauri::State<'_, Arc<Mutex<WFMSocketState>>>,
+) -> Result<Value, GlobleError> {
+    let socket = socket.lock()?.clone();
+    Ok(json!({
+        "connected": socket.is_connected(),
+        "status": socket.get_status()?,
+    }))
+}
+
+#[tauri::command]
+fn set_online_status(
+    status: String,
+    status_state: tauri::State<'_, Arc<Mutex<StatusState>>>,
+) -> Result<(), GlobleError> {
+    let status_state = status_state.lock()?.clone();
+    status_state.set_status(status.as_str(), "manual")
+}
+
+#[tauri::command]
+async fn reconcile_orders(
+    reconciliation: tauri::State<'_, Arc<Mutex<ReconciliationState>>>,
+) -> Result<Vec<Mismatch>, GlobleError> {
+    let reconciliation = reconciliation.lock()?.clone();
+    Ok(reconciliation.reconcile().await?)
+}
+
+#[tauri::command]
+async fn fix_order_mismatch(
+    mismatch: Mismatch,
+    fix: ReconcileFix,
+    price: Option<i64>,
+    reconciliation: tauri::State<'_, Arc<Mutex<ReconciliationState>>>,
+) -> Result<(), GlobleError> {
+    let reconciliation = reconciliation.lock()?.clone();
+    Ok(reconciliation.apply_fix(&mismatch, &fix, price).await?)
+}
+
+#[tauri::command]
+async fn fix_all_order_mismatches(
+    reconciliation: tauri::State<'_, Arc<Mutex<ReconciliationState>>>,
+) -> Result<Vec<Mismatch>, GlobleError> {
+    let reconciliation = reconciliation.lock()?.clone();
+    Ok(reconciliation.fix_all().await?)
+}
+
+#[tauri::command]
+async fn get_item_statistics(
+    item: String,
+    wfm: tauri::State<'_, Arc<Mutex<WFMClientState>>>,
+) -> Result<ItemStatistics, GlobleError> {
+    let wfm = wfm.lock()?.clone();
+    Ok(wfm.get_item_statistics(&item).await?)
+}
+
+#[tauri::command]
+async fn get_item_dropsources(
+    item: String,
+    wfm: tauri::State<'_, Arc<Mutex<WFMClientState>>>,
+) -> Result<Vec<DropSource>, GlobleError> {
+    let wfm = wfm.lock()?.clone();
+    Ok(wfm.get_item_dropsources(&item).await?)
+}
+
+#[tauri::command]
+async fn get_user_profile(
+    user: String,
+    wfm: tauri::State<'_, Arc<Mutex<WFMClientState>>>,
+) -> Result<UserProfile, GlobleError> {
+    let wfm = wfm.lock()?.clone();
+    Ok(wfm.get_user_profile(&user).await?)
+}
+
+#[tauri::command]
+async fn get_user_reviews(
+    user: String,
+    page: Option<i64>,
+    wfm: tauri::State<'_, Arc<Mutex<WFMClientState>>>,
+) -> Result<Vec<Review>, GlobleError> {
+    let wfm = wfm.lock()?.clone();
+    Ok(wfm.get_user_reviews(&user, page.unwrap_or(1)).await?)
+}
+
+#[tauri::command]
+async fn get_auction(
+    auction_id: String,
+    wfm: tauri::State<'_, Arc<Mutex<WFMClientState>>>,
+) -> Result<Auction, GlobleError> {
+    let wfm = wfm.lock()?.clone();
+    Ok(wfm.get_auction(&auction_id).await?)
+}
+
+#[tauri::command]
+async fn get_auction_bids(
+    auction_id: String,
+    wfm: tauri::State<'_, Arc<Mutex<WFMClientState>>>,
+) -> Result<Vec<AuctionBid>, GlobleError> {
+    let wfm = wfm.lock()?.clone();
+    Ok(wfm.get_auction_bids(&auction_id).await?)
+}
+
+#[tauri::command]
+async fn get_closed_orders(
+    user: Option<String>,
+    wfm: tauri::State<'_, Arc<Mutex<WFMClientState>>>,
+) -> Result<ClosedOrders, GlobleError> {
+    let wfm = wfm.lock()?.clone();
+    Ok(wfm.get_closed_orders(user.as_deref()).await?)
+}
+
+#[tauri::command]
+async fn bulk_post_inventory(
+    mode: BulkPriceMode,
+    min_profit: i64,
+    bulk: tauri::State<'_, Arc<Mutex<BulkOrderState>>>,
+) -> Result<BulkOrderSummary, GlobleError> {
+    let bulk = bulk.lock()?.clone();
+    Ok(bulk.post_inventory(mode, min_profit).await?)
+}
+
+#[tauri::command]
+async fn bulk_reprice_orders(
+    percent: f64,
+    order_type: Option<String>,
+    bulk: tauri::State<'_, Arc<Mutex<BulkOrderState>>>,
+) -> Result<BulkOrderSummary, GlobleError> {
+    let bulk = bulk.lock()?.clone();
+    Ok(bulk.reprice_orders(percent, order_type).await?)
+}
+
+#[tauri::command]
+async fn bulk_set_orders_visible(
+    visible: bool,
+    order_type: Option<String>,
+    bulk: tauri::State<'_, Arc<Mutex<BulkOrderState>>>,
+) -> Result<BulkOrderSummary, GlobleError> {
+    let bulk = bulk.lock()?.clone();
+    Ok(bulk.set_orders_visible(visible, order_type).await?)
+}
+
+#[tauri::command]
+async fn bulk_close_orders(
+    order_ids: Vec<String>,
+    bulk: tauri::State<'_, Arc<Mutex<BulkOrderState>>>,
+) -> Result<BulkOrderSummary, GlobleError> {
+    let bulk = bulk.lock()?.clone();
+    Ok(bulk.close_orders(order_ids).await?)
+}
+
+#[tauri::command]
+async fn bulk_delete_buy_orders(
+    bulk: tauri::State<'_, Arc<Mutex<BulkOrderState>>>,
+) -> Result<BulkOrderSummary, GlobleError> {
+    let bulk = bulk.lock()?.clone();
+    Ok(bulk.delete_buy_orders().await?)
+}
+
 #[tauri::command(async)]
 async fn generate_price_history(
     days: i64,
@@ -209,6 +702,19 @@ async fn setup_async(
     ));
     app.manage(auth_arc.clone());
 
+    // create and manage Notification state
+    let notification_arc = Arc::new(Mutex::new(NotificationState::new(Arc::clone(
+        &settings_arc,
+    ))));
+    app.manage(notification_arc.clone());
+
+    // create and manage AlertEngine state
+    let alerts_arc = Arc::new(Mutex::new(AlertEngine::new(
+        Arc::clone(&settings_arc),
+        Arc::clone(&notification_arc),
+    )));
+    app.manage(alerts_arc.clone());
+
     // create and manage Warframe Market API client state
     let wfm_client = Arc::new(Mutex::new(WFMClientState::new(Arc::clone(&auth_arc))));
     app.manage(wfm_client.clone());
@@ -219,7 +725,7 @@ async fn setup_async(
 
     // create and manage DatabaseClient state
     let database_client = Arc::new(Mutex::new(
-        DatabaseClient::new(cache_arc, wfm_client.clone())
+        DatabaseClient::new(Arc::clone(&cache_arc), wfm_client.clone())
             .await
             .unwrap(),
     ));
@@ -227,11 +733,89 @@ async fn setup_async(
 
     // create and manage PriceScraper state
     let price_scraper: Arc<Mutex<PriceScraper>> = Arc::new(Mutex::new(PriceScraper::new(
+        Arc::clone(&settings_arc),
         Arc::clone(&wfm_client),
         Arc::clone(&auth_arc),
     )));
     app.manage(price_scraper.clone());
 
+    // create and manage ForecastState state
+    let forecast_arc = Arc::new(Mutex::new(ForecastState::new(
+        Arc::clone(&settings_arc),
+        Arc::clone(&price_scraper),
+    )));
+    app.manage(forecast_arc.clone());
+
+    // create and manage ArbitrageState state
+    let arbitrage_arc = Arc::new(Mutex::new(ArbitrageState::new(
+        Arc::clone(&cache_arc),
+        Arc::clone(&price_scraper),
+        Arc::clone(&wfm_client),
+    )));
+    app.manage(arbitrage_arc.clone());
+
+    // create and manage DucatState state
+    let ducat_arc = Arc::new(Mutex::new(DucatState::new(
+        Arc::clone(&settings_arc),
+        Arc::clone(&cache_arc),
+        Arc::clone(&price_scraper),
+        Arc::clone(&wfm_client),
+        Arc::clone(&database_client),
+    )));
+    app.manage(ducat_arc.clone());
+
+    // create and manage RelicState state
+    let relic_arc = Arc::new(Mutex::new(RelicState::new(
+        Arc::clone(&settings_arc),
+        Arc::clone(&cache_arc),
+        Arc::clone(&price_scraper),
+    )));
+    app.manage(relic_arc.clone());
+
+    // create and manage RivenState state
+    let riven_arc = Arc::new(Mutex::new(RivenState::new(
+        Arc::clone(&wfm_client),
+        Arc::clone(&auth_arc),
+    )));
+    app.manage(riven_arc.clone());
+
+    // create and manage OrderBookState state
+    let order_book_arc = Arc::new(Mutex::new(OrderBookState::new(
+        Arc::clone(&settings_arc),
+        Arc::clone(&database_client),
+    )));
+    app.manage(order_book_arc.clone());
+
+    // create and manage Warframe Market socket state
+    let socket_arc = Arc::new(Mutex::new(WFMSocketState::new(
+        Arc::clone(&settings_arc),
+        Arc::clone(&auth_arc),
+    )));
+    app.manage(socket_arc.clone());
+
+    // create and manage StatusState state
+    let status_arc = Arc::new(Mutex::new(StatusState::new(
+        Arc::clone(&settings_arc),
+        Arc::clone(&socket_arc),
+    )));
+    app.manage(status_arc.clone());
+
+    // create and manage ReconciliationState state
+    let reconciliation = ReconciliationState::new(
+        Arc::clone(&settings_arc),
+        Arc::clone(&wfm_client),
+        Arc::clone(&database_client),
+    );
+    app.manage(Arc::new(Mutex::new(reconciliation)));
+
+    // create and manage BulkOrderState state
+    let bulk_orders = BulkOrderState::new(
+        Arc::clone(&wfm_client),
+        Arc::clone(&database_client),
+        Arc::clone(&price_scraper),
+    );
+    app.manage(Arc::new(Mutex::new(bulk_orders)));
+
     // create and manage LiveScraper state
     let live_scraper = LiveScraper::new(
         Arc::clone(&settings_arc),
@@ -239,11 +823,24 @@ async fn setup_async(
         Arc::clone(&wfm_client),
         Arc::clone(&auth_arc),
         Arc::clone(&database_client),
+        Arc::clone(&notification_arc),
+        Arc::clone(&alerts_arc),
+        Arc::clone(&arbitrage_arc),
+        Arc::clone(&order_book_arc),
+        Arc::clone(&forecast_arc),
+        Arc::clone(&socket_arc),
+        Arc::clone(&status_arc),
     );
     app.manage(Arc::new(Mutex::new(live_scraper)));
 
     // create and manage WhisperScraper state
-    let whisper_scraper = WhisperScraper::new(Arc::clone(&settings_arc));
+    let whisper_scraper = WhisperScraper::new(
+        Arc::clone(&settings_arc),
+        Arc::clone(&notification_arc),
+        Arc::clone(&status_arc),
+        Arc::clone(&wfm_client),
+        Arc::clone(&database_client),
+    );
     app.manage(Arc::new(Mutex::new(whisper_scraper)));
 
     Ok(database_client)
@@ -264,13 +861,57 @@ fn main() {
             setup,
             login,
             update_settings,
+            validate_settings,
+            get_settings_presets,
+            save_settings_preset,
+            delete_settings_preset,
+            preview_settings_preset,
+            export_settings,
+            preview_settings_import,
             create_invantory_entry,
             create_transaction_entry,
             delete_invantory_entry,
             sell_invantory_entry,
             toggle_whisper_scraper,
             toggle_live_scraper,
-            generate_price_history
+            generate_price_history,
+            send_test_notification,
+            get_alert_history,
+            search_items,
+            get_set_arbitrage,
+            get_ducat_analysis,
+            get_trash_to_baro,
+            get_ducat_buy_candidates,
+            get_relic_values,
+            update_relic_drops,
+            search_riven_auctions,
+            get_my_riven_auctions,
+            estimate_riven_price,
+            get_order_book_history,
+            get_price_forecasts,
+            get_opportunity_scores,
+            get_wfm_socket_state,
+            set_online_status,
+            pause_live_scraper,
+            resume_live_scraper,
+            get_live_scraper_status,
+            reconcile_orders,
+            fix_order_mismatch,
+            fix_all_order_mismatches,
+            get_item_statistics,
+            get_item_dropsources,
+            get_user_profile,
+            get_user_reviews,
+            get_auction,
+            get_auction_bids,
+            get_closed_orders,
+            set_user_blocked,
+            record_whisper_sent,
+            bulk_post_inventory,
+            bulk_reprice_orders,
+            bulk_set_orders_visible,
+            bulk_close_orders,
+            bulk_delete_buy_orders
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
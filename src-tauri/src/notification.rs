@@ -0,0 +1,743 @@
+use async_trait::async_trait;
+use lettre::{
+    message::header::ContentType, transport::smtp::authentication::Credentials, AsyncSmtpTransport,
+    AsyncTransport, Message, Tokio1Executor,
+};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    helper, logger,
+    settings::{NotificationSinkKind, NotificationSinkSettings, SettingsState},
+    structs::GlobleError,
+};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Notification {
+    pub event: String,
+    pub title: String,
+    pub message: String,
+}
+
+impl Notification {
+    pub fn new(event: &str, title: &str, message: &str) -> Self {
+        Notification {
+            event: event.to_string(),
+            title: title.to_string(),
+            message: message.to_string(),
+        }
+    }
+}
+
+/// A destination notifications can be delivered to.
+/// Every sink gets the same `Notification` and decides how to format it for its service.
+#[async_trait]
+pub trait NotificationSink: Send + Sync {
+    fn name(&self) -> String;
+    async fn send(&self, notification: &Notification) -> Result<(), GlobleError>;
+}
+
+async fn check_response(
+    sink: &str,
+    response: Result<reqwest::Response, reqwest::Error>,
+) -> Result<(), GlobleError> {
+    let response = response?;
+    let status = response.status();
+    if !status.is_success() {
+        let url = response.url().to_string();
+        return Err(GlobleError::HttpError(
+            status,
+            format!("{}: {}", sink, response.text().await.unwrap_or_default()),
+            url,
+        ));
+    }
+    Ok(())
+}
+
+pub struct DiscordSink {
+    webhook: String,
+    ping: bool,
+}
+#[async_trait]
+impl NotificationSink for DiscordSink {
+    fn name(&self) -> String {
+        "Discord".to_string()
+    }
+    async fn send(&self, notification: &Notification) -> Result<(), GlobleError> {
+        let client = Client::new();
+        let mut message = notification.message.clone();
+        if self.ping {
+            // Ask Discord for the webhook owner so we can mention them
+            let res = client
+                .get(self.webhook.as_str())
+                .send()
+                .await?
+                .json::<Value>()
+                .await?;
+            if let Some(id) = res.get("user").and_then(|user| user.get("id")) {
+                message = format!("{} <@{}>", message, id.as_str().unwrap_or_default());
+            }
+        }
+        let res = client
+            .post(self.webhook.as_str())
+            .json(&json!({ "content": message }))
+            .send()
+            .await;
+        check_response("Discord", res).await
+    }
+}
+
+/// Posts the notification as a plain JSON document to any url.
+pub struct WebhookSink {
+    url: String,
+    headers: HashMap<String, String>,
+}
+#[async_trait]
+impl NotificationSink for WebhookSink {
+    fn name(&self) -> String {
+        "Webhook".to_string()
+    }
+    async fn send(&self, notification: &Notification) -> Result<(), GlobleError> {
+        let mut request = Client::new().post(self.url.as_str()).json(&json!({
+            "event": notification.event,
+            "title": notification.title,
+            "message": notification.message,
+            "timestamp": chrono::Local::now().to_rfc3339(),
+        }));
+        for (key, value) in self.headers.iter() {
+            request = request.header(key.as_str(), value.as_str());
+        }
+        check_response("Webhook", request.send().await).await
+    }
+}
+
+pub enum PushService {
+    Ntfy,
+    Gotify,
+}
+
+/// Push endpoints in the style of ntfy (`POST <url>/<topic>` with a text body)
+/// and Gotify (`POST <url>/message?token=` with a JSON body).
+pub struct PushSink {
+    service: PushService,
+    url: String,
+    token: Option<String>,
+}
+#[async_trait]
+impl NotificationSink for PushSink {
+    fn name(&self) -> String {
+        match self.service {
+            PushService::Ntfy => "Ntfy".to_string(),
+            PushService::Gotify => "Gotify".to_string(),
+        }
+    }
+    async fn send(&self, notification: &Notification) -> Result<(), GlobleError> {
+        let client = Client::new();
+        let request = match self.service {
+            PushService::Ntfy => {
+                let request = client
+                    .post(self.url.as_str())
+                    .header("Title", notification.title.as_str())
+                    .header("Tags", notification.event.as_str())
+                    .body(notification.message.clone());
+                match &self.token {
+                    Some(token) => request.bearer_auth(token),
+                    None => request,
+                }
+            }
+            PushService::Gotify => {
+                let url = format!(
+                    "{}/message?token={}",
+                    self.url.trim_end_matches('/'),
+                    self.token.clone().unwrap_or_default()
+                );
+                client.post(url).json(&json!({
+                    "title": notification.title,
+                    "message": notification.message,
+                    "priority": 5,
+                    "extras": { "quantframe::event": notification.event },
+                }))
+            }
+        };
+        check_response(self.name().as_str(), request.send().await).await
+    }
+}
+
+pub struct EmailSink {
+    host: String,
+    port: u16,
+    tls: bool,
+    username: Option<String>,
+    password: Option<String>,
+    from: String,
+    to: String,
+}
+#[async_trait]
+impl NotificationSink for EmailSink {
+    fn name(&self) -> String {
+        "Email".to_string()
+    }
+    async fn send(&self, notification: &Notification) -> Result<(), GlobleError> {
+        let email = Message::builder()
+            .from(
+                self.from
+                    .parse()
+                    .map_err(|e| GlobleError::ParseError(format!("From: {:?}", e)))?,
+            )
+            .to(self
+                .to
+                .parse()
+                .map_err(|e| GlobleError::ParseError(format!("To: {:?}", e)))?)
+            .subject(format!("Quantframe: {}", notification.title))
+            .header(ContentType::TEXT_PLAIN)
+            .body(notification.message.clone())
+            .map_err(|e| GlobleError::OtherError(format!("{:?}", e)))?;
+
+        // Plain SMTP is allowed so the sink can be pointed at a local test server
+        let builder = if self.tls {
+            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(self.host.as_str())
+                .map_err(|e| GlobleError::OtherError(format!("{:?}", e)))?
+        } else {
+            AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(self.host.as_str())
+        };
+        let mut builder = builder.port(self.port);
+        if let (Some(username), Some(password)) = (&self.username, &self.password) {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+        builder
+            .build()
+            .send(email)
+            .await
+            .map_err(|e| GlobleError::OtherError(format!("{:?}", e)))?;
+        Ok(())
+    }
+}
+
+pub struct DesktopSink;
+#[async_trait]
+impl NotificationSink for DesktopSink {
+    fn name(&self) -> String {
+        "Desktop".to_string()
+    }
+    async fn send(&self, notification: &Notification) -> Result<(), GlobleError> {
+        helper::send_desktop_notification(
+            notification.title.as_str(),
+            notification.message.as_str(),
+        )
+    }
+}
+
+pub fn create_sink(kind: &NotificationSinkKind) -> Box<dyn NotificationSink> {
+    match kind.clone() {
+        NotificationSinkKind::Discord { webhook, ping } => Box::new(DiscordSink { webhook, ping }),
+        NotificationSinkKind::Webhook { url, headers } => Box::new(WebhookSink { url, headers }),
+        NotificationSinkKind::Ntfy { url, token } => Box::new(PushSink {
+            service: PushService::Ntfy,
+            url,
+            token,
+        }),
+        NotificationSinkKind::Gotify { url, token } => Box::new(PushSink {
+            service: PushService::Gotify,
+            url,
+            token: Some(token),
+        }),
+        NotificationSinkKind::Email {
+            host,
+            port,
+            tls,
+            username,
+            password,
+            from,
+            to,
+        } => Box::new(EmailSink {
+            host,
+            port,
+            tls,
+            username,
+            password,
+            from,
+            to,
+        }),
+        NotificationSinkKind::Desktop => Box::new(DesktopSink),
+    }
+}
+
+/// A sink without events receives all of them.
+fn accepts_event(sink: &NotificationSinkSettings, event: &str) -> bool {
+    sink.events.is_empty() || sink.events.iter().any(|accepted| accepted == event)
+}
+
+#[derive(Clone, Debug)]
+pub struct NotificationState {
+    log_file: String,
+    settings: Arc<Mutex<SettingsState>>,
+    last_sent: Arc<Mutex<HashMap<String, Instant>>>,
+}
+
+impl NotificationState {
+    pub fn new(settings: Arc<Mutex<SettingsState>>) -> Self {
+        NotificationState {
+            log_file: "notifications.log".to_string(),
+            settings,
+            last_sent: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Returns the configured sinks, including the legacy Discord `webhook` setting.
+    fn get_sinks(&self) -> Result<Vec<NotificationSinkSettings>, GlobleError> {
+        let settings = self.settings.lock()?.clone();
+        let mut sinks = settings.notifications.sinks.clone();
+        if settings.webhook != "" {
+            sinks.push(NotificationSinkSettings {
+                name: "discord".to_string(),
+                enabled: true,
+                events: vec![],
+                rate_limit_seconds: None,
+                kind: NotificationSinkKind::Discord {
+                    webhook: settings.webhook.clone(),
+                    ping: settings.ping_on_notif,
+                },
+            });
+        }
+        Ok(sinks)
+    }
+
+    /// Returns true if the sink already sent this event within its rate limit window,
+    /// otherwise records the send time and returns false. Sinks are told apart by their index
+    /// in `get_sinks`, names are not unique.
+    fn is_rate_limited(
+        &self,
+        index: usize,
+        sink: &NotificationSinkSettings,
+        event: &str,
+    ) -> Result<bool, GlobleError> {
+        let default_limit = self.settings.lock()?.notifications.rate_limit_seconds;
+        let limit = sink.rate_limit_seconds.unwrap_or(default_limit).max(0) as u64;
+        let key = format!("{}:{}", index, event);
+        let mut last_sent = self.last_sent.lock()?;
+        if let Some(sent) = last_sent.get(&key) {
+            if sent.elapsed() < Duration::from_secs(limit) {
+                return Ok(true);
+            }
+        }
+        last_sent.insert(key, Instant::now());
+        Ok(false)
+    }
+
+    /// Sends the notification to every enabled sink that accepts the event.
+    pub async fn send(&self, notification: Notification) -> Result<(), GlobleError> {
+        for (index, sink_settings) in self.get_sinks()?.into_iter().enumerate() {
+            if !sink_settings.enabled {
+                continue;
+            }
+            if !accepts_event(&sink_settings, &notification.event) {
+                continue;
+            }
+            if self.is_rate_limited(index, &sink_settings, &notification.event)? {
+                logger::debug_file(
+                    "Notification",
+                    format!(
+                        "Rate limited event: {}, Sink: {}",
+                        notification.event, sink_settings.name
+                    )
+                    .as_str(),
+                    Some(self.log_file.as_str()),
+                );
+                continue;
+            }
+            let sink = create_sink(&sink_settings.kind);
+            match sink.send(&notification).await {
+                Ok(_) => {}
+                Err(e) => {
+                    logger::error(
+                        "Notification",
+                        format!(
+                            "Sink: {} ({}), Event: {}, Error: {:?}",
+                            sink_settings.name,
+                            sink.name(),
+                            notification.event,
+                            e
+                        )
+                        .as_str(),
+                        true,
+                        Some(self.log_file.as_str()),
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Fire and forget version of `send`, safe to call from non async code.
+    pub fn notify(&self, event: &str, title: &str, message: &str) {
+        let notification = Notification::new(event, title, message);
+        let state = self.clone();
+        tauri::async_runtime::spawn(async move {
+            if let Err(e) = state.send(notification).await {
+                logger::error_con("Notification", format!("{:?}", e).as_str());
+            }
+        });
+    }
+
+    /// Sends a test notification to a single configured sink, ignoring filters and rate limits.
+    pub async fn send_test(&self, index: usize) -> Result<(), GlobleError> {
+        let sinks = self.get_sinks()?;
+        let sink_settings = match sinks.get(index) {
+            Some(sink) => sink,
+            None => {
+                return Err(GlobleError::OtherError(format!(
+                    "No notification sink at index {}",
+                    index
+                )))
+            }
+        };
+        create_sink(&sink_settings.kind)
+            .send(&Notification::new(
+                "test",
+                "Test notification",
+                format!("This is a test notification for {}", sink_settings.name).as_str(),
+            ))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+        net::TcpListener,
+        task::JoinHandle,
+    };
+
+    struct Request {
+        head: String,
+        body: String,
+    }
+
+    /// Answers one request per response body and returns what was received.
+    async fn spawn_http_server(responses: Vec<&'static str>) -> (String, JoinHandle<Vec<Request>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let handle = tokio::spawn(async move {
+            let mut requests = vec![];
+            for response in responses {
+                let (stream, _) = listener.accept().await.unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut head = String::new();
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).await.unwrap();
+                    if line == "\r\n" || line.is_empty() {
+                        break;
+                    }
+                    head.push_str(&line);
+                }
+                let length = head
+                    .lines()
+                    .find_map(|line| {
+                        let (key, value) = line.split_once(':')?;
+                        if key.eq_ignore_ascii_case("content-length") {
+                            value.trim().parse::<usize>().ok()
+                        } else {
+                            None
+                        }
+                    })
+                    .unwrap_or(0);
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).await.unwrap();
+                let reply = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    response.len(),
+                    response
+                );
+                reader.get_mut().write_all(reply.as_bytes()).await.unwrap();
+                requests.push(Request {
+                    head,
+                    body: String::from_utf8(body).unwrap(),
+                });
+            }
+            requests
+        });
+        (url, handle)
+    }
+
+    /// Accepts a single mail and returns the DATA section.
+    async fn spawn_smtp_server() -> (u16, JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut reader = BufReader::new(stream);
+            reader
+                .get_mut()
+                .write_all(b"220 localhost ESMTP\r\n")
+                .await
+                .unwrap();
+            let mut data = String::new();
+            let mut in_data = false;
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line).await.unwrap() == 0 {
+                    break;
+                }
+                let reply: &[u8] = if in_data {
+                    if line != ".\r\n" {
+                        data.push_str(&line);
+                        continue;
+                    }
+                    in_data = false;
+                    b"250 queued\r\n"
+                } else {
+                    let command = line.to_uppercase();
+                    if command.starts_with("DATA") {
+                        in_data = true;
+                        b"354 go ahead\r\n"
+                    } else if command.starts_with("QUIT") {
+                        reader.get_mut().write_all(b"221 bye\r\n").await.unwrap();
+                        break;
+                    } else {
+                        b"250 ok\r\n"
+                    }
+                };
+                reader.get_mut().write_all(reply).await.unwrap();
+            }
+            data
+        });
+        (port, handle)
+    }
+
+    fn notification() -> Notification {
+        Notification::new("order_sold", "Order sold", "Sold loki_prime_set for 120")
+    }
+
+    fn sink_settings(
+        name: &str,
+        events: Vec<&str>,
+        rate_limit: Option<i64>,
+    ) -> NotificationSinkSettings {
+        NotificationSinkSettings {
+            name: name.to_string(),
+            enabled: true,
+            events: events.into_iter().map(|event| event.to_string()).collect(),
+            rate_limit_seconds: rate_limit,
+            kind: NotificationSinkKind::Desktop,
+        }
+    }
+
+    #[tokio::test]
+    async fn discord_posts_content() {
+        let (url, server) = spawn_http_server(vec![""]).await;
+        let sink = create_sink(&NotificationSinkKind::Discord {
+            webhook: url,
+            ping: false,
+        });
+        sink.send(&notification()).await.unwrap();
+
+        let requests = server.await.unwrap();
+        assert!(requests[0].head.starts_with("POST / "));
+        let body: Value = serde_json::from_str(&requests[0].body).unwrap();
+        assert_eq!(body, json!({ "content": "Sold loki_prime_set for 120" }));
+    }
+
+    #[tokio::test]
+    async fn discord_pings_webhook_owner() {
+        let (url, server) = spawn_http_server(vec![r#"{"user":{"id":"42"}}"#, ""]).await;
+        let sink = create_sink(&NotificationSinkKind::Discord {
+            webhook: url,
+            ping: true,
+        });
+        sink.send(&notification()).await.unwrap();
+
+        let requests = server.await.unwrap();
+        assert!(requests[0].head.starts_with("GET / "));
+        let body: Value = serde_json::from_str(&requests[1].body).unwrap();
+        assert_eq!(body["content"], "Sold loki_prime_set for 120 <@42>");
+    }
+
+    #[tokio::test]
+    async fn webhook_posts_event_and_headers() {
+        let (url, server) = spawn_http_server(vec![""]).await;
+        let sink = create_sink(&NotificationSinkKind::Webhook {
+            url,
+            headers: HashMap::from([("X-Secret".to_string(), "abc".to_string())]),
+        });
+        sink.send(&notification()).await.unwrap();
+
+        let requests = server.await.unwrap();
+        assert!(requests[0].head.to_lowercase().contains("x-secret: abc"));
+        let body: Value = serde_json::from_str(&requests[0].body).unwrap();
+        assert_eq!(body["event"], "order_sold");
+        assert_eq!(body["title"], "Order sold");
+        assert_eq!(body["message"], "Sold loki_prime_set for 120");
+        assert!(body["timestamp"].is_string());
+    }
+
+    #[tokio::test]
+    async fn ntfy_posts_text_body() {
+        let (url, server) = spawn_http_server(vec![""]).await;
+        let sink = create_sink(&NotificationSinkKind::Ntfy {
+            url: format!("{}/quantframe", url),
+            token: Some("secret".to_string()),
+        });
+        sink.send(&notification()).await.unwrap();
+
+        let requests = server.await.unwrap();
+        let head = requests[0].head.to_lowercase();
+        assert!(head.starts_with("post /quantframe "));
+        assert!(head.contains("title: order sold"));
+        assert!(head.contains("tags: order_sold"));
+        assert!(head.contains("authorization: bearer secret"));
+        assert_eq!(requests[0].body, "Sold loki_prime_set for 120");
+    }
+
+    #[tokio::test]
+    async fn gotify_posts_json_with_token() {
+        let (url, server) = spawn_http_server(vec![""]).await;
+        let sink = create_sink(&NotificationSinkKind::Gotify {
+            url: format!("{}/", url),
+            token: "secret".to_string(),
+        });
+        sink.send(&notification()).await.unwrap();
+
+        let requests = server.await.unwrap();
+        assert!(requests[0].head.starts_with("POST /message?token=secret "));
+        let body: Value = serde_json::from_str(&requests[0].body).unwrap();
+        assert_eq!(body["title"], "Order sold");
+        assert_eq!(body["message"], "Sold loki_prime_set for 120");
+        assert_eq!(body["extras"]["quantframe::event"], "order_sold");
+    }
+
+    #[tokio::test]
+    async fn http_error_is_returned() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buffer = [0; 1024];
+            let _ = stream.read(&mut buffer).await;
+            stream
+                .write_all(b"HTTP/1.1 500 Internal Server Error\r\nContent-Length: 4\r\nConnection: close\r\n\r\nfail")
+                .await
+                .unwrap();
+        });
+        let sink = create_sink(&NotificationSinkKind::Webhook {
+            url,
+            headers: HashMap::new(),
+        });
+        assert!(sink.send(&notification()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn email_is_sent_over_smtp() {
+        let (port, server) = spawn_smtp_server().await;
+        let sink = create_sink(&NotificationSinkKind::Email {
+            host: "127.0.0.1".to_string(),
+            port,
+            tls: false,
+            username: None,
+            password: None,
+            from: "quantframe@localhost".to_string(),
+            to: "trader@localhost".to_string(),
+        });
+        sink.send(&notification()).await.unwrap();
+
+        let data = server.await.unwrap();
+        assert!(data.contains("Subject: Quantframe: Order sold"));
+        assert!(data.contains("To: trader@localhost"));
+        assert!(data.contains("Sold loki_prime_set for 120"));
+    }
+
+    #[test]
+    fn event_filter() {
+        assert!(accepts_event(
+            &sink_settings("all", vec![], None),
+            "order_sold"
+        ));
+        let filtered = sink_settings("sales", vec!["order_sold"], None);
+        assert!(accepts_event(&filtered, "order_sold"));
+        assert!(!accepts_event(&filtered, "price_alert"));
+    }
+
+    #[test]
+    fn rate_limit_per_sink_and_event() {
+        let state = NotificationState::new(Arc::new(Mutex::new(SettingsState::default())));
+        let sink = sink_settings("discord", vec![], None);
+        assert!(!state.is_rate_limited(0, &sink, "order_sold").unwrap());
+        assert!(state.is_rate_limited(0, &sink, "order_sold").unwrap());
+        // Other events and other sinks have their own window
+        assert!(!state.is_rate_limited(0, &sink, "price_alert").unwrap());
+        let other = sink_settings("ntfy", vec![], None);
+        assert!(!state.is_rate_limited(1, &other, "order_sold").unwrap());
+    }
+
+    #[test]
+    fn rate_limit_same_name() {
+        // A user sink named like the legacy webhook sink keeps its own window
+        let mut settings = SettingsState::default();
+        settings.webhook = "http://127.0.0.1/legacy".to_string();
+        settings.notifications.sinks = vec![sink_settings("discord", vec![], None)];
+        let state = NotificationState::new(Arc::new(Mutex::new(settings)));
+        let sinks = state.get_sinks().unwrap();
+        assert_eq!(sinks.len(), 2);
+        assert_eq!(sinks[0].name, sinks[1].name);
+        assert!(!state.is_rate_limited(0, &sinks[0], "order_sold").unwrap());
+        assert!(!state.is_rate_limited(1, &sinks[1], "order_sold").unwrap());
+    }
+
+    #[test]
+    fn debug_hides_secrets() {
+        let kinds = vec![
+            NotificationSinkKind::Discord {
+                webhook: "https://discord.com/api/webhooks/secret-hook".to_string(),
+                ping: false,
+            },
+            NotificationSinkKind::Webhook {
+                url: "http://localhost".to_string(),
+                headers: HashMap::from([("X-Secret".to_string(), "secret-header".to_string())]),
+            },
+            NotificationSinkKind::Ntfy {
+                url: "http://localhost".to_string(),
+                token: Some("secret-ntfy".to_string()),
+            },
+            NotificationSinkKind::Gotify {
+                url: "http://localhost".to_string(),
+                token: "secret-gotify".to_string(),
+            },
+            NotificationSinkKind::Email {
+                host: "localhost".to_string(),
+                port: 25,
+                tls: false,
+                username: Some("trader".to_string()),
+                password: Some("secret-password".to_string()),
+                from: "quantframe@localhost".to_string(),
+                to: "trader@localhost".to_string(),
+            },
+        ];
+        for kind in kinds {
+            let text = format!("{:?}", sink_settings_with_kind(kind));
+            assert!(!text.contains("secret-"), "{}", text);
+        }
+    }
+
+    fn sink_settings_with_kind(kind: NotificationSinkKind) -> NotificationSinkSettings {
+        NotificationSinkSettings {
+            kind,
+            ..sink_settings("sink", vec![], None)
+        }
+    }
+
+    #[test]
+    fn rate_limit_override() {
+        let state = NotificationState::new(Arc::new(Mutex::new(SettingsState::default())));
+        let sink = sink_settings("discord", vec![], Some(0));
+        assert!(!state.is_rate_limited(0, &sink, "order_sold").unwrap());
+        assert!(!state.is_rate_limited(0, &sink, "order_sold").unwrap());
+    }
+}
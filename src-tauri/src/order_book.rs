@@ -0,0 +1,296 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    database::DatabaseClient,
+    logger,
+    settings::SettingsState,
+    structs::{GlobleError, OrderBookSnapshot, OrderByItem},
+};
+
+// Depth levels in percent away from the best price
+const DEPTH_LEVELS: [f64; 3] = [5.0, 10.0, 20.0];
+
+// Last snapshot of an item, used for throttling and for the churn between snapshots
+#[derive(Clone, Debug)]
+struct LastSnapshot {
+    taken_at: Instant,
+    order_ids: HashSet<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct OrderBookState {
+    log_file: String,
+    settings: Arc<Mutex<SettingsState>>,
+    db: Arc<Mutex<DatabaseClient>>,
+    last_snapshots: Arc<Mutex<HashMap<String, LastSnapshot>>>,
+}
+
+impl OrderBookState {
+    pub fn new(settings: Arc<Mutex<SettingsState>>, db: Arc<Mutex<DatabaseClient>>) -> Self {
+        OrderBookState {
+            log_file: "order_book.log".to_string(),
+            settings,
+            db,
+            last_snapshots: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Stores a snapshot of the order book, at most once per `interval_minutes` per item.
+    /// The orders of `own_name` are left out, we are not our own competition.
+    pub async fn record(
+        &self,
+        item: &str,
+        orders: &[OrderByItem],
+        own_name: &str,
+    ) -> Result<Option<OrderBookSnapshot>, GlobleError> {
+        let settings = self.settings.lock()?.clone().order_book;
+        if !settings.enabled || orders.is_empty() {
+            return Ok(None);
+        }
+        let interval = Duration::from_secs(settings.interval_minutes.max(0) as u64 * 60);
+        let last = self.last_snapshots.lock()?.get(item).cloned();
+        if let Some(last) = &last {
+            if last.taken_at.elapsed() < interval {
+                return Ok(None);
+            }
+        }
+
+        let (snapshot, order_ids) = create_snapshot(
+            item,
+            orders,
+            own_name,
+            last.as_ref().map(|last| &last.order_ids),
+        );
+        self.last_snapshots.lock()?.insert(
+            item.to_string(),
+            LastSnapshot {
+                taken_at: Instant::now(),
+                order_ids,
+            },
+        );
+        let db = self.db.lock()?.clone();
+        let snapshot = db.create_order_book_snapshot(snapshot).await?;
+        logger::info_file(
+            "OrderBook",
+            format!(
+                "Item: {}, Bid: {:?}, Ask: {:?}, Sellers: {}, Buyers: {}",
+                item,
+                snapshot.best_bid,
+                snapshot.best_ask,
+                snapshot.online_sellers,
+                snapshot.online_buyers
+            )
+            .as_str(),
+            Some(self.log_file.as_str()),
+        );
+        Ok(Some(snapshot))
+    }
+
+    pub async fn get_history(
+        &self,
+        item: &str,
+        days: i64,
+    ) -> Result<Vec<OrderBookSnapshot>, GlobleError> {
+        let db = self.db.lock()?.clone();
+        let since = (chrono::Utc::now() - chrono::Duration::days(days)).to_rfc3339();
+        db.get_order_book_snapshots(item, &since).await
+    }
+
+    /// Deletes the snapshots older than the retention period.
+    pub async fn clean_up(&self) -> Result<u64, GlobleError> {
+        let retention_days = self.settings.lock()?.order_book.retention_days;
+        let db = self.db.lock()?.clone();
+        let before = (chrono::Utc::now() - chrono::Duration::days(retention_days)).to_rfc3339();
+        let deleted = db.delete_order_book_snapshots_before(&before).await?;
+        if deleted > 0 {
+            logger::info_con(
+                "OrderBook",
+                format!("Deleted {} old order book snapshots", deleted).as_str(),
+            );
+        }
+        Ok(deleted)
+    }
+}
+
+fn get_depth(orders: &[&OrderByItem], best: Option<i64>, percent: f64, sell: bool) -> i64 {
+    let best = match best {
+        Some(best) => best as f64,
+        None => return 0,
+    };
+    let limit = if sell {
+        best * (1.0 + percent / 100.0)
+    } else {
+        best * (1.0 - percent / 100.0)
+    };
+    orders
+        .iter()
+        .filter(|order| {
+            let price = order.platinum as f64;
+            if sell {
+                price <= limit
+            } else {
+                price >= limit
+            }
+        })
+        .map(|order| order.quantity)
+        .sum()
+}
+
+/// Summarizes the online orders of the highest mod rank other than our own, `previous_ids` are
+/// the order ids of the previous snapshot. Returns the snapshot and the order ids to compare the
+/// next one with.
+fn create_snapshot(
+    item: &str,
+    orders: &[OrderByItem],
+    own_name: &str,
+    previous_ids: Option<&HashSet<String>>,
+) -> (OrderBookSnapshot, HashSet<String>) {
+    let mod_rank = orders.iter().filter_map(|order| order.mod_rank).max();
+    let online: Vec<&OrderByItem> = orders
+        .iter()
+        .filter(|order| {
+            order.mod_rank == mod_rank
+                && order.user.status != "offline"
+                && order.user.ingame_name != own_name
+        })
+        .collect();
+    let sells: Vec<&OrderByItem> = online
+        .iter()
+        .filter(|order| order.order_type == "sell")
+        .cloned()
+        .collect();
+    let buys: Vec<&OrderByItem> = online
+        .iter()
+        .filter(|order| order.order_type == "buy")
+        .cloned()
+        .collect();
+    let ingame_sells: Vec<&OrderByItem> = sells
+        .iter()
+        .filter(|order| order.user.status == "ingame")
+        .cloned()
+        .collect();
+    let best_ask = sells.iter().map(|order| order.platinum).min();
+    let best_bid = buys.iter().map(|order| order.platinum).max();
+
+    let order_ids: HashSet<String> = online.iter().map(|order| order.id.clone()).collect();
+    let (new_orders, removed_orders) = match previous_ids {
+        Some(previous_ids) => (
+            order_ids.difference(previous_ids).count() as i64,
+            previous_ids.difference(&order_ids).count() as i64,
+        ),
+        None => (0, 0),
+    };
+    let count_users = |orders: &[&OrderByItem]| {
+        orders
+            .iter()
+            .map(|order| order.user.ingame_name.clone())
+            .collect::<HashSet<_>>()
+            .len() as i64
+    };
+
+    let snapshot = OrderBookSnapshot {
+        id: -1,
+        item_url: item.to_string(),
+        mod_rank,
+        datetime: chrono::Utc::now().to_rfc3339(),
+        best_bid,
+        best_ask,
+        spread: match (best_ask, best_bid) {
+            (Some(ask), Some(bid)) => Some(ask - bid),
+            _ => None,
+        },
+        sell_depth_5: get_depth(&sells, best_ask, DEPTH_LEVELS[0], true),
+        sell_depth_10: get_depth(&sells, best_ask, DEPTH_LEVELS[1], true),
+        sell_depth_20: get_depth(&sells, best_ask, DEPTH_LEVELS[2], true),
+        buy_depth_5: get_depth(&buys, best_bid, DEPTH_LEVELS[0], false),
+        buy_depth_10: get_depth(&buys, best_bid, DEPTH_LEVELS[1], false),
+        buy_depth_20: get_depth(&buys, best_bid, DEPTH_LEVELS[2], false),
+        sell_orders: sells.len() as i64,
+        buy_orders: buys.len() as i64,
+        online_sellers: count_users(&sells),
+        online_buyers: count_users(&buys),
+        ingame_sellers: count_users(&ingame_sells),
+        new_orders,
+        removed_orders,
+    };
+    (snapshot, order_ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structs::User;
+
+    fn order(id: &str, user: &str, status: &str, order_type: &str, platinum: i64) -> OrderByItem {
+        OrderByItem {
+            order_type: order_type.to_string(),
+            quantity: 1,
+            platinum,
+            mod_rank: None,
+            user: User {
+                reputation: 10,
+                ingame_name: user.to_string(),
+                id: user.to_string(),
+                status: status.to_string(),
+            },
+            platform: "pc".to_string(),
+            creation_date: String::new(),
+            last_update: String::new(),
+            visible: true,
+            id: id.to_string(),
+            region: "en".to_string(),
+        }
+    }
+
+    fn orders() -> Vec<OrderByItem> {
+        vec![
+            order("1", "me", "ingame", "sell", 90),
+            order("2", "seller", "ingame", "sell", 100),
+            order("3", "website_seller", "online", "sell", 95),
+            order("4", "offline_seller", "offline", "sell", 80),
+            order("5", "me", "ingame", "buy", 85),
+            order("6", "buyer", "ingame", "buy", 70),
+        ]
+    }
+
+    #[test]
+    fn own_orders_are_left_out() {
+        let (snapshot, order_ids) = create_snapshot("loki_prime_set", &orders(), "me", None);
+        assert_eq!(snapshot.online_sellers, 2);
+        assert_eq!(snapshot.ingame_sellers, 1);
+        assert_eq!(snapshot.online_buyers, 1);
+        assert_eq!(snapshot.best_ask, Some(95));
+        assert_eq!(snapshot.best_bid, Some(70));
+        assert_eq!(snapshot.spread, Some(25));
+        assert!(!order_ids.contains("1") && !order_ids.contains("5"));
+    }
+
+    #[test]
+    fn counts_everyone_else() {
+        let (snapshot, _) = create_snapshot("loki_prime_set", &orders(), "someone_else", None);
+        assert_eq!(snapshot.online_sellers, 3);
+        assert_eq!(snapshot.ingame_sellers, 2);
+        assert_eq!(snapshot.best_ask, Some(90));
+    }
+
+    #[test]
+    fn churn_against_previous_snapshot() {
+        let previous: HashSet<String> = ["2", "7"].iter().map(|id| id.to_string()).collect();
+        let (snapshot, _) = create_snapshot("loki_prime_set", &orders(), "me", Some(&previous));
+        // Orders 3 and 6 are new, order 7 is gone
+        assert_eq!(snapshot.new_orders, 2);
+        assert_eq!(snapshot.removed_orders, 1);
+    }
+
+    #[test]
+    fn depth_around_best_price() {
+        let (snapshot, _) = create_snapshot("loki_prime_set", &orders(), "me", None);
+        // 95 and 100 are within 10% of the best ask of 95, only 95 within 5%
+        assert_eq!(snapshot.sell_depth_5, 1);
+        assert_eq!(snapshot.sell_depth_10, 2);
+    }
+}
@@ -0,0 +1,259 @@
+use chrono::{DateTime, Utc};
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    price_scraper::ItemPriceSummary,
+    settings::OutlierSettings,
+    structs::OrderByItem,
+    trust::{get_order_age_hours, ExcludedOrder},
+};
+
+// Fewer orders than this give no meaningful quartiles
+const MIN_IQR_ORDERS: usize = 4;
+
+/// Linear interpolated quantile of sorted values, `q` from 0 to 1.
+pub fn quantile(sorted: &[f64], q: f64) -> Option<f64> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let position = q.clamp(0.0, 1.0) * (sorted.len() - 1) as f64;
+    let lower = position.floor() as usize;
+    let upper = position.ceil() as usize;
+    let weight = position - lower as f64;
+    Some(sorted[lower] * (1.0 - weight) + sorted[upper] * weight)
+}
+
+/// Returns why the order looks fake or stale, None when it can be used for pricing.
+fn get_suspicious_reason(
+    order: &OrderByItem,
+    settings: &OutlierSettings,
+    book: &HashMap<String, Vec<f64>>,
+    reference: Option<&ItemPriceSummary>,
+    now: DateTime<Utc>,
+) -> Option<String> {
+    let price = order.platinum as f64;
+    let is_sell = order.order_type == "sell";
+    let prices = book
+        .get(&order.order_type)
+        .map(|p| p.as_slice())
+        .unwrap_or(&[]);
+
+    if prices.len() >= MIN_IQR_ORDERS {
+        let q1 = quantile(prices, 0.25)?;
+        let q3 = quantile(prices, 0.75)?;
+        let iqr = q3 - q1;
+        if is_sell && price < q1 - settings.iqr_multiplier * iqr {
+            return Some(format!("below the order book range {:.0} - {:.0}", q1, q3));
+        }
+        if !is_sell && price > q3 + settings.iqr_multiplier * iqr {
+            return Some(format!("above the order book range {:.0} - {:.0}", q1, q3));
+        }
+    }
+
+    if let Some(reference) = reference.filter(|r| r.median > 0.0) {
+        // Stable items have almost no deviation, a normal undercut should not count as outlier
+        let std = reference
+            .median_std
+            .max(reference.median * settings.min_std_percent / 100.0);
+        let z_score = (price - reference.median) / std;
+        if (is_sell && z_score < -settings.z_score_threshold)
+            || (!is_sell && z_score > settings.z_score_threshold)
+        {
+            return Some(format!(
+                "z-score {:.1} against the price history median {:.0}",
+                z_score, reference.median
+            ));
+        }
+    }
+
+    if settings.stale_order_hours > 0 {
+        if let (Some(age), Some(median)) = (get_order_age_hours(order, now), quantile(prices, 0.5))
+        {
+            let undercuts = if is_sell {
+                price < median
+            } else {
+                price > median
+            };
+            if age > settings.stale_order_hours as f64 && undercuts {
+                return Some(format!(
+                    "not updated for {:.0} hours and beats the median {:.0}",
+                    age, median
+                ));
+            }
+        }
+    }
+    None
+}
+
+/// Removes suspicious orders from the order book before it is used for pricing: prices outside
+/// the interquartile range of the book, prices far from the price history, stale orders that
+/// beat the median, and users that never answer our whispers. Only the ingame orders of the
+/// highest mod rank are checked, those are the ones pricing looks at.
+pub fn filter_orders(
+    orders: Vec<OrderByItem>,
+    settings: &OutlierSettings,
+    reference: Option<&ItemPriceSummary>,
+    unresponsive: &HashSet<String>,
+    own_name: &str,
+    now: DateTime<Utc>,
+) -> (Vec<OrderByItem>, Vec<ExcludedOrder>) {
+    if !settings.enabled {
+        return (orders, vec![]);
+    }
+    let mod_rank = orders.iter().map(|order| order.mod_rank).max().flatten();
+    let is_checked = |order: &OrderByItem| {
+        order.user.status == "ingame"
+            && order.mod_rank == mod_rank
+            && order.user.ingame_name != own_name
+    };
+
+    let mut book: HashMap<String, Vec<f64>> = HashMap::new();
+    for order in orders.iter().filter(|order| is_checked(order)) {
+        book.entry(order.order_type.clone())
+            .or_default()
+            .push(order.platinum as f64);
+    }
+    for prices in book.values_mut() {
+        prices.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    }
+
+    let mut kept = vec![];
+    let mut excluded = vec![];
+    for order in orders {
+        if !is_checked(&order) {
+            kept.push(order);
+            continue;
+        }
+        let reason = if unresponsive.contains(&order.user.ingame_name.to_lowercase()) {
+            Some("does not answer whispers".to_string())
+        } else {
+            get_suspicious_reason(&order, settings, &book, reference, now)
+        };
+        match reason {
+            Some(reason) => excluded.push(ExcludedOrder::new(&order, &reason)),
+            None => kept.push(order),
+        }
+    }
+    (kept, excluded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structs::User;
+
+    fn order(id: &str, user: &str, order_type: &str, platinum: i64) -> OrderByItem {
+        OrderByItem {
+            order_type: order_type.to_string(),
+            quantity: 1,
+            platinum,
+            mod_rank: None,
+            user: User {
+                reputation: 10,
+                ingame_name: user.to_string(),
+                id: user.to_string(),
+                status: "ingame".to_string(),
+            },
+            platform: "pc".to_string(),
+            creation_date: "2023-08-01T00:00:00.000+00:00".to_string(),
+            last_update: "2023-08-01T00:00:00.000+00:00".to_string(),
+            visible: true,
+            id: id.to_string(),
+            region: "en".to_string(),
+        }
+    }
+
+    fn now() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2023-08-01T01:00:00.000+00:00")
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    fn kept_ids(kept: &[OrderByItem]) -> Vec<String> {
+        kept.iter().map(|order| order.id.clone()).collect()
+    }
+
+    #[test]
+    fn quantile_interpolates() {
+        assert_eq!(quantile(&[], 0.5), None);
+        assert_eq!(quantile(&[10.0, 20.0, 30.0, 40.0], 0.5), Some(25.0));
+        assert_eq!(quantile(&[10.0, 20.0, 30.0, 40.0], 0.0), Some(10.0));
+        assert_eq!(quantile(&[10.0, 20.0, 30.0, 40.0], 1.0), Some(40.0));
+    }
+
+    #[test]
+    fn unresponsive_users_are_excluded() {
+        let orders = vec![
+            order("1", "Ghost", "sell", 50),
+            order("2", "seller", "sell", 55),
+            order("3", "buyer", "buy", 40),
+        ];
+        // Names are matched lowercase
+        let unresponsive: HashSet<String> = vec!["ghost".to_string()].into_iter().collect();
+        let (kept, excluded) = filter_orders(
+            orders,
+            &OutlierSettings::default(),
+            None,
+            &unresponsive,
+            "me",
+            now(),
+        );
+        assert_eq!(kept_ids(&kept), vec!["2", "3"]);
+        assert_eq!(excluded.len(), 1);
+        assert_eq!(excluded[0].username, "Ghost");
+    }
+
+    #[test]
+    fn own_orders_are_never_excluded() {
+        let orders = vec![
+            order("1", "me", "sell", 50),
+            order("2", "seller", "sell", 55),
+        ];
+        let unresponsive: HashSet<String> = vec!["me".to_string()].into_iter().collect();
+        let (kept, excluded) = filter_orders(
+            orders,
+            &OutlierSettings::default(),
+            None,
+            &unresponsive,
+            "me",
+            now(),
+        );
+        assert_eq!(kept_ids(&kept), vec!["1", "2"]);
+        assert!(excluded.is_empty());
+    }
+
+    #[test]
+    fn disabled_keeps_everything() {
+        let orders = vec![order("1", "ghost", "sell", 50)];
+        let unresponsive: HashSet<String> = vec!["ghost".to_string()].into_iter().collect();
+        let settings = OutlierSettings {
+            enabled: false,
+            ..OutlierSettings::default()
+        };
+        let (kept, excluded) = filter_orders(orders, &settings, None, &unresponsive, "me", now());
+        assert_eq!(kept_ids(&kept), vec!["1"]);
+        assert!(excluded.is_empty());
+    }
+
+    #[test]
+    fn fake_sell_order_below_the_book_is_excluded() {
+        let orders = vec![
+            order("1", "a", "sell", 5),
+            order("2", "b", "sell", 50),
+            order("3", "c", "sell", 52),
+            order("4", "d", "sell", 54),
+            order("5", "e", "sell", 56),
+        ];
+        let (kept, excluded) = filter_orders(
+            orders,
+            &OutlierSettings::default(),
+            None,
+            &HashSet::new(),
+            "me",
+            now(),
+        );
+        assert_eq!(kept_ids(&kept), vec!["2", "3", "4", "5"]);
+        assert_eq!(excluded.len(), 1);
+        assert_eq!(excluded[0].order_id, "1");
+    }
+}
@@ -1,8 +1,10 @@
+use crate::price_source::{create_price_source, merge_price_frames, PriceSourceContext};
+use crate::settings::SettingsState;
 use crate::structs::GlobleError;
 use crate::{helper, logger};
 use polars::prelude::*;
-use reqwest::{Client, Method, Url};
-use serde_json::{json, Value};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
 
 use std::path::Path;
 use std::sync::Mutex;
@@ -17,16 +19,31 @@ use crate::{auth::AuthState, wfm_client::WFMClientState};
 
 // Structs for the Warframe Market API
 
-#[derive(Clone)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ItemPriceSummary {
+    pub url_name: String,
+    pub median: f64,
+    // Standard deviation of the daily medians
+    pub median_std: f64,
+    pub avg_price: f64,
+    pub volume: f64,
+}
+
+#[derive(Clone, Debug)]
 pub struct PriceScraper {
     csv_path: String,
     csv_backop_path: String,
+    settings: Arc<Mutex<SettingsState>>,
     wfm: Arc<Mutex<WFMClientState>>,
     auth: Arc<Mutex<AuthState>>,
 }
 
 impl PriceScraper {
-    pub fn new(wfm: Arc<Mutex<WFMClientState>>, auth: Arc<Mutex<AuthState>>) -> Self {
+    pub fn new(
+        settings: Arc<Mutex<SettingsState>>,
+        wfm: Arc<Mutex<WFMClientState>>,
+        auth: Arc<Mutex<AuthState>>,
+    ) -> Self {
         PriceScraper {
             csv_path: helper::get_app_roaming_path()
                 .join("price_data.csv")
@@ -38,6 +55,7 @@ impl PriceScraper {
                 .to_str()
                 .unwrap()
                 .to_string(),
+            settings,
             wfm,
             auth,
         }
@@ -54,63 +72,57 @@ impl PriceScraper {
             .has_header(true)
             .finish()
     }
-    /// Returns a JSON object containing price data for the given platform and day.
-    /// The `platform` argument should be one of "pc", "ps4", or "xb1".
-    /// The `day` argument should be a string in the format "YYYY-MM-DD".
-    /// If the request fails, returns a `GlobleError` with information about the error.
-    async fn get_price_by_day(&self, platform: &str, day: &str) -> Result<Value, GlobleError> {
-        let mut url = format!("https://relics.run/history/price_history_{}.json", day);
-        if platform != "pc" {
-            url = format!(
-                "https://relics.run/history/{}/price_history_{}.json",
-                platform, day
-            );
-        }
-        let client = Client::new();
-        let request = client.request(Method::GET, Url::parse(&url).unwrap());
-        let response = request.send().await;
-        if let Err(e) = response {
-            return Err(GlobleError::ReqwestError(e));
-        }
-        let response_data = response.unwrap();
-        let status = response_data.status();
-        if status == 429 {
-            // Sleep for 3 second
-            tokio::time::sleep(tokio::time::Duration::from_secs(3)).await;
-            return Err(GlobleError::TooManyRequests(
-                "Too Many Requests".to_string(),
-            ));
-        }
-        if status != 200 {
-            return Err(GlobleError::HttpError(
-                status,
-                response_data.text().await.unwrap(),
-                url.to_string(),
-            ));
-        }
-        let response = response_data.json::<Value>().await.unwrap();
-        Ok(response)
+    /// When the price history file was last written, None when there is no price history.
+    pub fn get_price_history_modified(&self) -> Option<std::time::SystemTime> {
+        std::fs::metadata(&self.csv_path)
+            .or_else(|_| std::fs::metadata(&self.csv_backop_path))
+            .and_then(|metadata| metadata.modified())
+            .ok()
     }
-    /// Returns true if the given vector of item data is valid for price scraping, false otherwise.
-    /// A valid item data vector must have at least one element, and the first element must have either 3 or 6 keys.
-    /// The first element must also have a "mod_rank" key.
-    fn is_valid_price_data(&self, _name: &str, item_datas: &Vec<Value>) -> bool {
-        if item_datas.len() == 0 {
-            return false;
-        }
-        // Check if the first element has a "mod_rank" key
-        let is_mod = match item_datas[0].get("mod_rank") {
-            Some(_mod_rank) => true,
-            None => false,
-        };
+    /// Returns the closed order statistics of every item in the price history,
+    /// averaged over all days and keyed by url_name.
+    pub fn get_price_summary(&self) -> Result<HashMap<String, ItemPriceSummary>, GlobleError> {
+        let df = self
+            .get_price_historys()?
+            .lazy()
+            .filter(col("order_type").eq(lit("closed")))
+            .groupby(&["name"])
+            .agg(&[
+                col("median").mean().alias("median"),
+                col("median").std(1).alias("median_std"),
+                col("avg_price").mean().alias("avg_price"),
+                col("volume").mean().alias("volume"),
+            ])
+            .collect()?;
 
-        if is_mod && item_datas.len() == 6 {
-            return true;
-        }
-        if !is_mod && item_datas.len() == 3 {
-            return true;
+        let names = df.column("name")?.utf8()?.clone();
+        let medians = df.column("median")?.f64()?.clone();
+        let median_stds = df.column("median_std")?.f64()?.clone();
+        let avg_prices = df.column("avg_price")?.f64()?.clone();
+        let volumes = df.column("volume")?.f64()?.clone();
+
+        let mut summary: HashMap<String, ItemPriceSummary> = HashMap::new();
+        for ((((name, median), median_std), avg_price), volume) in names
+            .into_iter()
+            .zip(medians.into_iter())
+            .zip(median_stds.into_iter())
+            .zip(avg_prices.into_iter())
+            .zip(volumes.into_iter())
+        {
+            if let Some(name) = name {
+                summary.insert(
+                    name.to_string(),
+                    ItemPriceSummary {
+                        url_name: name.to_string(),
+                        median: median.unwrap_or(0.0),
+                        median_std: median_std.unwrap_or(0.0),
+                        avg_price: avg_price.unwrap_or(0.0),
+                        volume: volume.unwrap_or(0.0),
+                    },
+                );
+            }
         }
-        return false;
+        Ok(summary)
     }
     /// Returns a map of item names to their corresponding IDs, based on the `items` list.
     /// The map is represented as a `HashMap` with `String` keys and values.
@@ -131,6 +143,93 @@ impl PriceScraper {
             .collect();
         Ok((item_map_url, item_map_id))
     }
+    /// Returns the items to fetch from sources that need a request per item: the items of the
+    /// current price history and the whitelist, or the first tradable items without a history.
+    fn get_statistics_items(
+        &self,
+        id_map: &HashMap<String, String>,
+    ) -> Result<Vec<String>, GlobleError> {
+        let settings = self.settings.lock()?.clone();
+        let max_items = settings.price_source.max_statistics_items.max(0) as usize;
+        let mut items: Vec<String> = vec![];
+        if let Ok(df) = self.get_price_historys() {
+            let names = df.column("name")?.utf8()?.clone();
+            for name in names.into_iter().flatten() {
+                if !items.iter().any(|item| item == name) {
+                    items.push(name.to_string());
+                }
+            }
+        }
+        for name in settings.whitelist.iter() {
+            if !items.contains(name) {
+                items.push(name.clone());
+            }
+        }
+        items.retain(|item| id_map.contains_key(item));
+        if items.is_empty() {
+            items = id_map.keys().cloned().collect();
+            items.sort();
+        }
+        items.truncate(max_items);
+        Ok(items)
+    }
+    /// Fetches the price history from the primary source. The fallback source is used when
+    /// the primary one fails, or merged in to fill its gaps when merging is enabled.
+    async fn fetch_price_history(
+        &self,
+        context: &PriceSourceContext,
+    ) -> Result<DataFrame, GlobleError> {
+        let settings = self.settings.lock()?.clone().price_source;
+        let primary = create_price_source(&settings.primary, Arc::clone(&self.wfm));
+        let primary_df = match primary
+            .fetch(context)
+            .await
+            .and_then(helper::merge_dataframes)
+        {
+            Ok(df) => Some(df),
+            Err(e) => {
+                logger::error_con(
+                    "PriceScraper:generate",
+                    format!("Source: {}, Error: {:?}", primary.name(), e).as_str(),
+                );
+                None
+            }
+        };
+
+        let fallback = match &settings.fallback {
+            Some(kind) if *kind != settings.primary => kind,
+            _ => {
+                return primary_df.ok_or_else(|| {
+                    GlobleError::OtherError(format!("{} has no price data", primary.name()))
+                })
+            }
+        };
+        if let Some(df) = primary_df.clone() {
+            if !settings.merge {
+                return Ok(df);
+            }
+        }
+        let fallback = create_price_source(fallback, Arc::clone(&self.wfm));
+        logger::info_con(
+            "PriceScraper:generate",
+            format!("Getting price data from {}", fallback.name()).as_str(),
+        );
+        let fallback_df = fallback
+            .fetch(context)
+            .await
+            .and_then(helper::merge_dataframes);
+        match (primary_df, fallback_df) {
+            (Some(primary_df), Ok(fallback_df)) => merge_price_frames(primary_df, fallback_df),
+            (Some(primary_df), Err(e)) => {
+                logger::error_con(
+                    "PriceScraper:generate",
+                    format!("Source: {}, Error: {:?}", fallback.name(), e).as_str(),
+                );
+                Ok(primary_df)
+            }
+            (None, fallback_df) => fallback_df,
+        }
+    }
     pub async fn generate(&self, days: i64) -> Result<i64, GlobleError> {
         println!("Generating csv file for {} days.", days);
         let auth = self.auth.lock().unwrap().clone();
@@ -152,177 +251,15 @@ impl PriceScraper {
             );
             fs::copy(csv_path, csv_backop_path)?;
         }
-        let last_days = helper::last_x_days(days).clone();
-        let mut dataframes: Vec<DataFrame> = Vec::new();
         let (url_map, id_map) = self.get_items_map_url_map().await?;
-
-        let mut found_data = 0;
-
-        for day in last_days.clone() {
-            if found_data >= 7 {
-                // Should only get 7 days of data
-                continue;
-            }
-            helper::send_message_to_window(
-                "price_scraper_update_progress",
-                Some(json!({"current": found_data, "total": days, "day": day})),
-            );
-            // Get the price data for the day for all items
-            let items = self.get_price_by_day(auth.platform.as_str(), &day).await;
-            match items {
-                Ok(items) => {
-                    found_data += 1;
-                    logger::info_con(
-                        "PriceScraper:generate",
-                        format!("Getting data for day: {}", day).as_str(),
-                    );
-
-                    if let Value::Object(map) = &items {
-                        for (item_name, item_data_list) in map {
-                            if let Value::Array(array) = item_data_list {
-                                if !self.is_valid_price_data(&item_name, array) {
-                                    // println!("Invalid price data for item: {}", item_name);
-                                    continue;
-                                }
-
-                                // Get the url_name and id for the item
-                                let url_name = url_map
-                                    .get(item_name)
-                                    .unwrap_or(&"not_found".to_string())
-                                    .clone();
-
-                                // Get the id for the item
-                                let id = id_map
-                                    .get(&url_name)
-                                    .unwrap_or(&"not_found".to_string())
-                                    .clone();
-
-                                let name_vec: Vec<Option<String>> = array
-                                    .iter()
-                                    .map(|_item_data| Some(url_name.clone()))
-                                    .collect();
-
-                                let id_vec: Vec<Option<String>> =
-                                    array.iter().map(|_item_data| Some(id.clone())).collect();
-
-                                let order_type_vec: Vec<Option<String>> = array
-                                    .iter()
-                                    .map(|item_data| {
-                                        item_data
-                                            .get("order_type")
-                                            .and_then(|v| v.as_str())
-                                            .map(String::from)
-                                    })
-                                    .collect();
-
-                                let volume_vec: Vec<Option<i64>> = array
-                                    .iter()
-                                    .map(|item_data| {
-                                        item_data
-                                            .get("volume")
-                                            .and_then(|v| v.as_i64())
-                                            .map(i64::from)
-                                    })
-                                    .collect();
-
-                                let datetime_vec: Vec<Option<String>> = array
-                                    .iter()
-                                    .map(|item_data| {
-                                        item_data
-                                            .get("datetime")
-                                            .and_then(|v| v.as_str())
-                                            .map(String::from)
-                                    })
-                                    .collect();
-
-                                let max_price_vec: Vec<Option<f64>> = array
-                                    .iter()
-                                    .map(|item_data| {
-                                        item_data.get("max_price").and_then(|v| v.as_f64())
-                                    })
-                                    .collect();
-
-                                let min_price_vec: Vec<Option<f64>> = array
-                                    .iter()
-                                    .map(|item_data| {
-                                        item_data.get("min_price").and_then(|v| v.as_f64())
-                                    })
-                                    .collect();
-
-                                let avg_price_vec: Vec<Option<f64>> = array
-                                    .iter()
-                                    .map(|item_data| {
-                                        item_data.get("avg_price").and_then(|v| v.as_f64())
-                                    })
-                                    .collect();
-
-                                let mod_rank_vec: Vec<Option<f64>> = array
-                                    .iter()
-                                    .map(|item_data| {
-                                        item_data.get("mod_rank").and_then(|v| v.as_f64())
-                                    })
-                                    .collect();
-
-                                let median_vec: Vec<Option<f64>> = array
-                                    .iter()
-                                    .map(|item_data| {
-                                        item_data.get("median").and_then(|v| v.as_f64())
-                                    })
-                                    .collect();
-
-                                let df = DataFrame::new_no_checks(vec![
-                                    Series::new("name", name_vec),
-                                    Series::new("datetime", datetime_vec),
-                                    Series::new("order_type", order_type_vec),
-                                    Series::new("volume", volume_vec),
-                                    Series::new("min_price", min_price_vec),
-                                    Series::new("max_price", max_price_vec),
-                                    Series::new("avg_price", avg_price_vec),
-                                    Series::new("mod_rank", mod_rank_vec),
-                                    Series::new("median", median_vec),
-                                    Series::new("item_id", id_vec),
-                                ]);
-                                // dump_dataframe(&mut df, format!("{}.csv", item_name).as_str())?;
-
-                                let df: DataFrame = df
-                                    .clone()
-                                    .lazy()
-                                    .fill_nan(lit(0.0).alias("max_price"))
-                                    .fill_nan(lit(0.0).alias("min_price"))
-                                    .with_column(
-                                        (col("max_price") - col("min_price")).alias("range"),
-                                    )
-                                    .collect()?;
-
-                                // Filter out items that are mod_rank 0.
-                                let df = df
-                                    .clone()
-                                    .lazy()
-                                    .filter(col("mod_rank").neq(0).or(col("mod_rank").is_null()))
-                                    .collect()?;
-                                // dump_dataframe(&mut df, format!("{} {}.csv", day, item_name).as_str())?;
-                                dataframes.push(df);
-                            }
-                        }
-                    }
-                }
-                Err(_e) => {
-                    logger::error_con(
-                        "PriceScraper:generate",
-                        format!("Error getting price data for day: {}", day).as_str(),
-                    );
-                }
-            }
-        }
-        logger::info_con(
-            "PriceScraper:generate",
-            format!(
-                "Finished getting price data for all days. Merging dataframes... {:?}",
-                dataframes.len()
-            )
-            .as_str(),
-        );
-        let mut full_df = helper::merge_dataframes(dataframes)?;
+        let context = PriceSourceContext {
+            platform: auth.platform.clone(),
+            days,
+            items: self.get_statistics_items(&id_map)?,
+            url_map,
+            id_map,
+        };
+        let mut full_df = self.fetch_price_history(&context).await?;
         helper::send_message_to_window(
             "price_scraper_update_complete",
             Some(json!({"total_items": full_df.height()})),
@@ -0,0 +1,590 @@
+use async_trait::async_trait;
+use polars::prelude::*;
+use reqwest::{Client, Method, Url};
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+use crate::{
+    helper, logger,
+    settings::PriceSourceKind,
+    structs::{GlobleError, ItemStatistic},
+    wfm_client::WFMClientState,
+};
+
+// The price history keeps one week of daily data
+const HISTORY_DAYS: usize = 7;
+
+/// Everything a source needs to fetch and name the price data.
+#[derive(Clone, Debug)]
+pub struct PriceSourceContext {
+    pub platform: String,
+    // Days to look back, the newest HISTORY_DAYS days with data are used
+    pub days: i64,
+    // Item name to url_name
+    pub url_map: HashMap<String, String>,
+    // url_name to item id
+    pub id_map: HashMap<String, String>,
+    // Items to fetch for sources that need a request per item
+    pub items: Vec<String>,
+}
+
+/// One row of the price history, every source is normalized to it.
+#[derive(Clone, Debug)]
+pub struct PriceEntry {
+    pub datetime: Option<String>,
+    // "closed", "buy" or "sell"
+    pub order_type: Option<String>,
+    pub volume: Option<i64>,
+    pub min_price: Option<f64>,
+    pub max_price: Option<f64>,
+    pub avg_price: Option<f64>,
+    pub mod_rank: Option<f64>,
+    pub median: Option<f64>,
+}
+
+/// A source of daily price statistics.
+/// Every source returns one DataFrame per item in the schema PriceScraper writes.
+#[async_trait]
+pub trait PriceSource: Send + Sync {
+    fn name(&self) -> String;
+    async fn fetch(&self, context: &PriceSourceContext) -> Result<Vec<DataFrame>, GlobleError>;
+}
+
+/// Builds the price history rows of a single item.
+pub fn create_price_frame(
+    url_name: &str,
+    id: &str,
+    entries: &[PriceEntry],
+) -> Result<DataFrame, GlobleError> {
+    let df = DataFrame::new_no_checks(vec![
+        Series::new(
+            "name",
+            entries
+                .iter()
+                .map(|_| Some(url_name.to_string()))
+                .collect::<Vec<Option<String>>>(),
+        ),
+        Series::new(
+            "datetime",
+            entries
+                .iter()
+                .map(|e| e.datetime.clone())
+                .collect::<Vec<Option<String>>>(),
+        ),
+        Series::new(
+            "order_type",
+            entries
+                .iter()
+                .map(|e| e.order_type.clone())
+                .collect::<Vec<Option<String>>>(),
+        ),
+        Series::new(
+            "volume",
+            entries
+                .iter()
+                .map(|e| e.volume)
+                .collect::<Vec<Option<i64>>>(),
+        ),
+        Series::new(
+            "min_price",
+            entries
+                .iter()
+                .map(|e| e.min_price)
+                .collect::<Vec<Option<f64>>>(),
+        ),
+        Series::new(
+            "max_price",
+            entries
+                .iter()
+                .map(|e| e.max_price)
+                .collect::<Vec<Option<f64>>>(),
+        ),
+        Series::new(
+            "avg_price",
+            entries
+                .iter()
+                .map(|e| e.avg_price)
+                .collect::<Vec<Option<f64>>>(),
+        ),
+        Series::new(
+            "mod_rank",
+            entries
+                .iter()
+                .map(|e| e.mod_rank)
+                .collect::<Vec<Option<f64>>>(),
+        ),
+        Series::new(
+            "median",
+            entries
+                .iter()
+                .map(|e| e.median)
+                .collect::<Vec<Option<f64>>>(),
+        ),
+        Series::new(
+            "item_id",
+            entries
+                .iter()
+                .map(|_| Some(id.to_string()))
+                .collect::<Vec<Option<String>>>(),
+        ),
+    ]);
+
+    let df: DataFrame = df
+        .lazy()
+        .fill_nan(lit(0.0).alias("max_price"))
+        .fill_nan(lit(0.0).alias("min_price"))
+        .with_column((col("max_price") - col("min_price")).alias("range"))
+        // Filter out items that are mod_rank 0.
+        .filter(col("mod_rank").neq(0).or(col("mod_rank").is_null()))
+        .collect()?;
+    Ok(df)
+}
+
+/// Key of a price history row, a row is unique per item, day, order type and rank.
+fn get_row_keys(df: &DataFrame) -> Result<Vec<String>, GlobleError> {
+    let names = df.column("name")?.utf8()?.clone();
+    let datetimes = df.column("datetime")?.utf8()?.clone();
+    let order_types = df.column("order_type")?.utf8()?.clone();
+    let mod_ranks = df.column("mod_rank")?.cast(&DataType::Float64)?;
+    let mod_ranks = mod_ranks.f64()?.clone();
+    Ok(names
+        .into_iter()
+        .zip(datetimes.into_iter())
+        .zip(order_types.into_iter())
+        .zip(mod_ranks.into_iter())
+        .map(|(((name, datetime), order_type), mod_rank)| {
+            format!(
+                "{}|{}|{}|{:?}",
+                name.unwrap_or_default(),
+                datetime.unwrap_or_default().get(..10).unwrap_or_default(),
+                order_type.unwrap_or_default(),
+                mod_rank
+            )
+        })
+        .collect())
+}
+
+/// Adds the rows of `secondary` that `primary` does not have, primary rows always win.
+pub fn merge_price_frames(
+    primary: DataFrame,
+    secondary: DataFrame,
+) -> Result<DataFrame, GlobleError> {
+    if primary.height() == 0 {
+        return Ok(secondary);
+    }
+    if secondary.height() == 0 {
+        return Ok(primary);
+    }
+    let known: HashSet<String> = get_row_keys(&primary)?.into_iter().collect();
+    let mask: BooleanChunked = get_row_keys(&secondary)?
+        .iter()
+        .map(|key| Some(!known.contains(key)))
+        .collect();
+    let missing = secondary.filter(&mask)?;
+    helper::merge_dataframes(vec![primary, missing])
+}
+
+pub fn create_price_source(
+    kind: &PriceSourceKind,
+    wfm: Arc<Mutex<WFMClientState>>,
+) -> Box<dyn PriceSource> {
+    match kind {
+        PriceSourceKind::RelicsRun => Box::new(RelicsRunSource {}),
+        PriceSourceKind::WarframeMarket => Box::new(WarframeMarketSource { wfm }),
+    }
+}
+
+/// Daily dumps of the Warframe Market statistics of every item, published by relics.run.
+pub struct RelicsRunSource {}
+
+impl RelicsRunSource {
+    /// Returns a JSON object containing price data for the given platform and day.
+    /// The `platform` argument should be one of "pc", "ps4", or "xb1".
+    /// The `day` argument should be a string in the format "YYYY-MM-DD".
+    /// If the request fails, returns a `GlobleError` with information about the error.
+    async fn get_price_by_day(&self, platform: &str, day: &str) -> Result<Value, GlobleError> {
+        let mut url = format!("https://relics.run/history/price_history_{}.json", day);
+        if platform != "pc" {
+            url = format!(
+                "https://relics.run/history/{}/price_history_{}.json",
+                platform, day
+            );
+        }
+        let client = Client::new();
+        let request = client.request(Method::GET, Url::parse(&url).unwrap());
+        let response = request.send().await;
+        if let Err(e) = response {
+            return Err(GlobleError::ReqwestError(e));
+        }
+        let response_data = response.unwrap();
+        let status = response_data.status();
+        if status == 429 {
+            // Sleep for 3 second
+            tokio::time::sleep(tokio::time::Duration::from_secs(3)).await;
+            return Err(GlobleError::TooManyRequests(
+                "Too Many Requests".to_string(),
+            ));
+        }
+        if status != 200 {
+            return Err(GlobleError::HttpError(
+                status,
+                response_data.text().await.unwrap(),
+                url.to_string(),
+            ));
+        }
+        let response = response_data.json::<Value>().await.unwrap();
+        Ok(response)
+    }
+    /// Returns true if the given vector of item data is valid for price scraping, false otherwise.
+    /// A valid item data vector must have at least one element, and the first element must have either 3 or 6 keys.
+    /// The first element must also have a "mod_rank" key.
+    fn is_valid_price_data(&self, _name: &str, item_datas: &Vec<Value>) -> bool {
+        if item_datas.len() == 0 {
+            return false;
+        }
+        // Check if the first element has a "mod_rank" key
+        let is_mod = match item_datas[0].get("mod_rank") {
+            Some(_mod_rank) => true,
+            None => false,
+        };
+
+        if is_mod && item_datas.len() == 6 {
+            return true;
+        }
+        if !is_mod && item_datas.len() == 3 {
+            return true;
+        }
+        return false;
+    }
+}
+
+#[async_trait]
+impl PriceSource for RelicsRunSource {
+    fn name(&self) -> String {
+        "relics.run".to_string()
+    }
+
+    async fn fetch(&self, context: &PriceSourceContext) -> Result<Vec<DataFrame>, GlobleError> {
+        let mut dataframes: Vec<DataFrame> = Vec::new();
+        let mut found_data = 0;
+
+        for day in helper::last_x_days(context.days) {
+            if found_data >= HISTORY_DAYS {
+                // Should only get 7 days of data
+                break;
+            }
+            helper::send_message_to_window(
+                "price_scraper_update_progress",
+                Some(json!({"current": found_data, "total": context.days, "day": day})),
+            );
+            // Get the price data for the day for all items
+            let items = match self.get_price_by_day(context.platform.as_str(), &day).await {
+                Ok(items) => items,
+                Err(_e) => {
+                    logger::error_con(
+                        "PriceScraper:RelicsRun",
+                        format!("Error getting price data for day: {}", day).as_str(),
+                    );
+                    continue;
+                }
+            };
+            found_data += 1;
+            logger::info_con(
+                "PriceScraper:RelicsRun",
+                format!("Getting data for day: {}", day).as_str(),
+            );
+
+            if let Value::Object(map) = &items {
+                for (item_name, item_data_list) in map {
+                    if let Value::Array(array) = item_data_list {
+                        if !self.is_valid_price_data(&item_name, array) {
+                            continue;
+                        }
+                        // Get the url_name and id for the item
+                        let url_name = context
+                            .url_map
+                            .get(item_name)
+                            .cloned()
+                            .unwrap_or("not_found".to_string());
+                        let id = context
+                            .id_map
+                            .get(&url_name)
+                            .cloned()
+                            .unwrap_or("not_found".to_string());
+
+                        let entries: Vec<PriceEntry> = array
+                            .iter()
+                            .map(|item_data| PriceEntry {
+                                datetime: item_data
+                                    .get("datetime")
+                                    .and_then(|v| v.as_str())
+                                    .map(String::from),
+                                order_type: item_data
+                                    .get("order_type")
+                                    .and_then(|v| v.as_str())
+                                    .map(String::from),
+                                volume: item_data.get("volume").and_then(|v| v.as_i64()),
+                                min_price: item_data.get("min_price").and_then(|v| v.as_f64()),
+                                max_price: item_data.get("max_price").and_then(|v| v.as_f64()),
+                                avg_price: item_data.get("avg_price").and_then(|v| v.as_f64()),
+                                mod_rank: item_data.get("mod_rank").and_then(|v| v.as_f64()),
+                                median: item_data.get("median").and_then(|v| v.as_f64()),
+                            })
+                            .collect();
+                        dataframes.push(create_price_frame(&url_name, &id, &entries)?);
+                    }
+                }
+            }
+        }
+        if found_data == 0 {
+            return Err(GlobleError::OtherError(
+                "relics.run has no price data for the requested days".to_string(),
+            ));
+        }
+        Ok(dataframes)
+    }
+}
+
+/// The `items/{url}/statistics` endpoint of Warframe Market, one request per item.
+pub struct WarframeMarketSource {
+    wfm: Arc<Mutex<WFMClientState>>,
+}
+
+fn convert_statistic(statistic: &ItemStatistic, order_type: &str) -> PriceEntry {
+    PriceEntry {
+        datetime: Some(statistic.datetime.clone()),
+        order_type: Some(order_type.to_string()),
+        volume: Some(statistic.volume),
+        min_price: Some(statistic.min_price),
+        max_price: Some(statistic.max_price),
+        avg_price: Some(statistic.avg_price),
+        mod_rank: statistic.mod_rank.map(|rank| rank as f64),
+        median: Some(statistic.median),
+    }
+}
+
+#[async_trait]
+impl PriceSource for WarframeMarketSource {
+    fn name(&self) -> String {
+        "Warframe Market".to_string()
+    }
+
+    async fn fetch(&self, context: &PriceSourceContext) -> Result<Vec<DataFrame>, GlobleError> {
+        let wfm = self.wfm.lock()?.clone();
+        // Only the same days relics.run would have been asked for
+        let days: HashSet<String> = helper::last_x_days(context.days.min(HISTORY_DAYS as i64))
+            .into_iter()
+            .collect();
+        let mut dataframes: Vec<DataFrame> = Vec::new();
+
+        for (index, url_name) in context.items.iter().enumerate() {
+            helper::send_message_to_window(
+                "price_scraper_update_progress",
+                Some(json!({"current": index, "total": context.items.len(), "item": url_name})),
+            );
+            let statistics = match wfm.get_item_statistics(url_name).await {
+                Ok(statistics) => statistics,
+                Err(e) => {
+                    logger::warning(
+                        "PriceScraper:WarframeMarket",
+                        format!("Skipping item: {}, Error: {:?}", url_name, e).as_str(),
+                        true,
+                        None,
+                    );
+                    continue;
+                }
+            };
+            let in_range = |statistic: &&ItemStatistic| {
+                days.contains(statistic.datetime.get(..10).unwrap_or_default())
+            };
+            let mut entries: Vec<PriceEntry> = statistics
+                .closed
+                .days_90
+                .iter()
+                .filter(in_range)
+                .map(|statistic| convert_statistic(statistic, "closed"))
+                .collect();
+            entries.extend(
+                statistics
+                    .live
+                    .days_90
+                    .iter()
+                    .filter(in_range)
+                    .map(|statistic| {
+                        let order_type = statistic.order_type.clone().unwrap_or_default();
+                        convert_statistic(statistic, order_type.as_str())
+                    }),
+            );
+            if entries.is_empty() {
+                continue;
+            }
+            let id = context
+                .id_map
+                .get(url_name)
+                .cloned()
+                .unwrap_or("not_found".to_string());
+            dataframes.push(create_price_frame(url_name, &id, &entries)?);
+        }
+        if dataframes.is_empty() {
+            return Err(GlobleError::OtherError(
+                "Warframe Market returned no statistics".to_string(),
+            ));
+        }
+        Ok(dataframes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(datetime: &str, order_type: &str, mod_rank: Option<f64>, price: f64) -> PriceEntry {
+        PriceEntry {
+            datetime: Some(datetime.to_string()),
+            order_type: Some(order_type.to_string()),
+            volume: Some(10),
+            min_price: Some(price - 5.0),
+            max_price: Some(price + 5.0),
+            avg_price: Some(price),
+            mod_rank,
+            median: Some(price),
+        }
+    }
+
+    fn avg_prices(df: &DataFrame) -> Vec<Option<f64>> {
+        df.column("avg_price")
+            .unwrap()
+            .f64()
+            .unwrap()
+            .into_iter()
+            .collect()
+    }
+
+    #[test]
+    fn price_frame_has_the_price_scraper_schema() {
+        let df = create_price_frame(
+            "serration",
+            "serration_id",
+            &[entry("2023-08-01T00:00:00.000+00:00", "closed", None, 20.0)],
+        )
+        .unwrap();
+        assert_eq!(
+            df.get_column_names(),
+            vec![
+                "name",
+                "datetime",
+                "order_type",
+                "volume",
+                "min_price",
+                "max_price",
+                "avg_price",
+                "mod_rank",
+                "median",
+                "item_id",
+                "range",
+            ]
+        );
+        assert_eq!(df.column("volume").unwrap().dtype(), &DataType::Int64);
+        assert_eq!(df.column("mod_rank").unwrap().dtype(), &DataType::Float64);
+        assert_eq!(
+            df.column("range").unwrap().f64().unwrap().get(0),
+            Some(10.0)
+        );
+        assert_eq!(
+            df.column("item_id").unwrap().utf8().unwrap().get(0),
+            Some("serration_id")
+        );
+    }
+
+    #[test]
+    fn price_frame_drops_unranked_mod_rows() {
+        let df = create_price_frame(
+            "serration",
+            "serration_id",
+            &[
+                entry("2023-08-01T00:00:00.000+00:00", "closed", Some(0.0), 20.0),
+                entry("2023-08-01T00:00:00.000+00:00", "closed", Some(10.0), 80.0),
+                entry("2023-08-01T00:00:00.000+00:00", "closed", None, 30.0),
+            ],
+        )
+        .unwrap();
+        assert_eq!(avg_prices(&df), vec![Some(80.0), Some(30.0)]);
+    }
+
+    #[test]
+    fn price_frame_fills_missing_prices() {
+        let mut missing = entry("2023-08-01T00:00:00.000+00:00", "closed", None, 20.0);
+        missing.min_price = Some(f64::NAN);
+        missing.max_price = Some(f64::NAN);
+        let df = create_price_frame("serration", "serration_id", &[missing]).unwrap();
+        assert_eq!(df.column("range").unwrap().f64().unwrap().get(0), Some(0.0));
+    }
+
+    #[test]
+    fn merge_keeps_primary_rows() {
+        let primary = create_price_frame(
+            "serration",
+            "serration_id",
+            &[entry("2023-08-01T00:00:00.000+00:00", "closed", None, 20.0)],
+        )
+        .unwrap();
+        // Same day with a different time, the primary row wins
+        let secondary = create_price_frame(
+            "serration",
+            "serration_id",
+            &[
+                entry("2023-08-01T12:00:00.000+00:00", "closed", None, 25.0),
+                entry("2023-08-02T00:00:00.000+00:00", "closed", None, 22.0),
+                entry("2023-08-01T00:00:00.000+00:00", "sell", None, 30.0),
+            ],
+        )
+        .unwrap();
+        let merged = merge_price_frames(primary, secondary).unwrap();
+        assert_eq!(
+            avg_prices(&merged),
+            vec![Some(20.0), Some(22.0), Some(30.0)]
+        );
+    }
+
+    #[test]
+    fn merge_keeps_ranks_apart() {
+        let primary = create_price_frame(
+            "arcane_energize",
+            "arcane_energize_id",
+            &[entry(
+                "2023-08-01T00:00:00.000+00:00",
+                "closed",
+                Some(5.0),
+                300.0,
+            )],
+        )
+        .unwrap();
+        let secondary = create_price_frame(
+            "arcane_energize",
+            "arcane_energize_id",
+            &[
+                entry("2023-08-01T00:00:00.000+00:00", "closed", Some(5.0), 310.0),
+                entry("2023-08-01T00:00:00.000+00:00", "closed", Some(3.0), 100.0),
+            ],
+        )
+        .unwrap();
+        let merged = merge_price_frames(primary, secondary).unwrap();
+        assert_eq!(avg_prices(&merged), vec![Some(300.0), Some(100.0)]);
+    }
+
+    #[test]
+    fn merge_with_an_empty_frame() {
+        let frame = create_price_frame(
+            "serration",
+            "serration_id",
+            &[entry("2023-08-01T00:00:00.000+00:00", "closed", None, 20.0)],
+        )
+        .unwrap();
+        let empty = create_price_frame("serration", "serration_id", &[]).unwrap();
+        let merged = merge_price_frames(empty.clone(), frame.clone()).unwrap();
+        assert_eq!(avg_prices(&merged), vec![Some(20.0)]);
+        let merged = merge_price_frames(frame, empty).unwrap();
+        assert_eq!(avg_prices(&merged), vec![Some(20.0)]);
+    }
+}
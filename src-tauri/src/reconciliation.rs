@@ -0,0 +1,516 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::{
+    database::DatabaseClient,
+    helper, logger,
+    settings::SettingsState,
+    structs::{GlobleError, Invantory, Order},
+    wfm_client::WFMClientState,
+};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MismatchKind {
+    // Sell order on Warframe Market for an item that is not in the inventory
+    OrphanOrder,
+    // Item in the inventory without a sell order
+    UnlistedInventory,
+    // The inventory has a listed price but there is no sell order anymore
+    StaleListing,
+    QuantityDrift,
+    PriceDrift,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReconcileFix {
+    // Needs the price the item was bought for
+    AddInventory,
+    DeleteOrder,
+    // Needs a price unless the inventory has a listed price
+    PostOrder,
+    ClearListedPrice,
+    UpdateOrderQuantity,
+    UpdateInventoryQuantity,
+    UpdateListedPrice,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Mismatch {
+    pub kind: MismatchKind,
+    pub item_url: String,
+    pub item_id: String,
+    // Mod rank of the order or inventory entry, 0 for unranked items
+    pub rank: i64,
+    pub inventory_id: Option<i64>,
+    pub order_id: Option<String>,
+    pub owned: Option<i64>,
+    pub order_quantity: Option<i64>,
+    pub listed_price: Option<i64>,
+    pub order_price: Option<i64>,
+    pub visible: Option<bool>,
+    // The first fix is the one applied by fix all
+    pub fixes: Vec<ReconcileFix>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ReconciliationState {
+    log_file: String,
+    settings: Arc<Mutex<SettingsState>>,
+    wfm: Arc<Mutex<WFMClientState>>,
+    db: Arc<Mutex<DatabaseClient>>,
+}
+
+/// Compares the sell orders with the inventory. Blacklisted items are managed by hand, like
+/// `delete_all_orders` their orders and inventory entries are left alone.
+fn find_mismatches(
+    orders: Vec<Order>,
+    inventorys: &[Invantory],
+    blacklist: &[String],
+) -> Vec<Mismatch> {
+    // Ranked variants of an item, like an unranked and a max rank arcane, are separate
+    // orders and separate inventory entries
+    let mut sell_orders: HashMap<(String, i64), Order> = HashMap::new();
+    for order in orders {
+        if blacklist.contains(&order.item.url_name) {
+            continue;
+        }
+        sell_orders.insert(
+            (order.item.url_name.clone(), order.mod_rank.unwrap_or(0)),
+            order,
+        );
+    }
+
+    let mut mismatches = vec![];
+    for inventory in inventorys.iter() {
+        if blacklist.contains(&inventory.item_url) {
+            continue;
+        }
+        let order = match sell_orders.remove(&(inventory.item_url.clone(), inventory.rank)) {
+            Some(order) => order,
+            None => {
+                let (kind, fixes) = match inventory.listed_price {
+                    Some(_) => (
+                        MismatchKind::StaleListing,
+                        vec![ReconcileFix::ClearListedPrice, ReconcileFix::PostOrder],
+                    ),
+                    None => (
+                        MismatchKind::UnlistedInventory,
+                        vec![ReconcileFix::PostOrder],
+                    ),
+                };
+                mismatches.push(Mismatch {
+                    kind,
+                    item_url: inventory.item_url.clone(),
+                    item_id: inventory.item_id.clone(),
+                    rank: inventory.rank,
+                    inventory_id: Some(inventory.id),
+                    order_id: None,
+                    owned: Some(inventory.owned),
+                    order_quantity: None,
+                    listed_price: inventory.listed_price,
+                    order_price: None,
+                    visible: None,
+                    fixes,
+                });
+                continue;
+            }
+        };
+        let mismatch = Mismatch {
+            kind: MismatchKind::QuantityDrift,
+            item_url: inventory.item_url.clone(),
+            item_id: inventory.item_id.clone(),
+            rank: inventory.rank,
+            inventory_id: Some(inventory.id),
+            order_id: Some(order.id.clone()),
+            owned: Some(inventory.owned),
+            order_quantity: Some(order.quantity),
+            listed_price: inventory.listed_price,
+            order_price: Some(order.platinum),
+            visible: Some(order.visible),
+            fixes: vec![
+                ReconcileFix::UpdateOrderQuantity,
+                ReconcileFix::UpdateInventoryQuantity,
+            ],
+        };
+        if order.quantity != inventory.owned {
+            mismatches.push(mismatch.clone());
+        }
+        if inventory.listed_price != Some(order.platinum) {
+            mismatches.push(Mismatch {
+                kind: MismatchKind::PriceDrift,
+                fixes: vec![ReconcileFix::UpdateListedPrice],
+                ..mismatch
+            });
+        }
+    }
+
+    // What is left are sell orders without an inventory entry
+    for order in sell_orders.into_values() {
+        mismatches.push(Mismatch {
+            kind: MismatchKind::OrphanOrder,
+            item_url: order.item.url_name.clone(),
+            item_id: order.item.id.clone(),
+            rank: order.mod_rank.unwrap_or(0),
+            inventory_id: None,
+            order_id: Some(order.id.clone()),
+            owned: None,
+            order_quantity: Some(order.quantity),
+            listed_price: None,
+            order_price: Some(order.platinum),
+            visible: Some(order.visible),
+            // Deleting an order cannot be undone, so the order is added to the inventory first
+            fixes: vec![ReconcileFix::AddInventory, ReconcileFix::DeleteOrder],
+        });
+    }
+    mismatches
+}
+
+impl ReconciliationState {
+    pub fn new(
+        settings: Arc<Mutex<SettingsState>>,
+        wfm: Arc<Mutex<WFMClientState>>,
+        db: Arc<Mutex<DatabaseClient>>,
+    ) -> Self {
+        ReconciliationState {
+            log_file: "reconciliation.log".to_string(),
+            settings,
+            wfm,
+            db,
+        }
+    }
+
+    /// Compares our sell orders on Warframe Market with the inventory. Buy orders are not
+    /// tracked in the inventory, so they are left alone.
+    pub async fn reconcile(&self) -> Result<Vec<Mismatch>, GlobleError> {
+        let wfm = self.wfm.lock()?.clone();
+        let db = self.db.lock()?.clone();
+        let blacklist = self.settings.lock()?.blacklist.clone();
+        let orders = wfm.get_user_ordres().await?;
+        let inventorys = db.get_inventorys().await?;
+        let mismatches = find_mismatches(orders.sell_orders, &inventorys, &blacklist);
+
+        logger::info(
+            "Reconciliation",
+            format!("Found {} mismatches", mismatches.len()).as_str(),
+            true,
+            Some(self.log_file.as_str()),
+        );
+        helper::send_message_to_window(
+            "reconciliation",
+            Some(json!({ "mismatches": mismatches.len() })),
+        );
+        Ok(mismatches)
+    }
+
+    /// Applies one of the fixes offered by the mismatch. `price` is used when posting an order.
+    pub async fn apply_fix(
+        &self,
+        mismatch: &Mismatch,
+        fix: &ReconcileFix,
+        price: Option<i64>,
+    ) -> Result<(), GlobleError> {
+        if !mismatch.fixes.contains(fix) {
+            return Err(GlobleError::OtherError(format!(
+                "Fix {:?} does not apply to {:?}",
+                fix, mismatch.kind
+            )));
+        }
+        let wfm = self.wfm.lock()?.clone();
+        let db = self.db.lock()?.clone();
+        let missing =
+            |field: &str| GlobleError::OtherError(format!("The mismatch has no {}", field));
+
+        match fix {
+            ReconcileFix::AddInventory => {
+                let price = price.ok_or_else(|| {
+                    GlobleError::OtherError(
+                        "The bought price is needed to add the order to the inventory".to_string(),
+                    )
+                })?;
+                let inventory = db
+                    .create_inventory_entry(
+                        mismatch.item_url.clone(),
+                        false,
+                        mismatch.order_quantity.ok_or_else(|| missing("order"))?,
+                        price,
+                        mismatch.rank,
+                    )
+                    .await?;
+                db.update_inventory_listed_price(inventory.id, mismatch.order_price)
+                    .await?;
+            }
+            ReconcileFix::DeleteOrder => {
+                let order_id = mismatch.order_id.as_ref().ok_or_else(|| missing("order"))?;
+                wfm.delete_order(order_id, &mismatch.item_url, &mismatch.item_id, "sell")
+                    .await?;
+            }
+            ReconcileFix::PostOrder => {
+                let price = price.or(mismatch.listed_price).ok_or_else(|| {
+                    GlobleError::OtherError("A price is needed to post the order".to_string())
+                })?;
+                let owned = mismatch.owned.ok_or_else(|| missing("inventory"))?;
+                let inventory_id = mismatch.inventory_id.ok_or_else(|| missing("inventory"))?;
+                let rank = Some(mismatch.rank as f64).filter(|rank| *rank > 0.0);
+                wfm.post_ordre(
+                    &mismatch.item_url,
+                    &mismatch.item_id,
+                    "sell",
+                    price,
+                    owned,
+                    true,
+                    rank,
+                )
+                .await?;
+                db.update_inventory_listed_price(inventory_id, Some(price))
+                    .await?;
+            }
+            ReconcileFix::ClearListedPrice => {
+                db.update_inventory_listed_price(
+                    mismatch.inventory_id.ok_or_else(|| missing("inventory"))?,
+                    None,
+                )
+                .await?;
+            }
+            ReconcileFix::UpdateOrderQuantity => {
+                let order_id = mismatch.order_id.as_ref().ok_or_else(|| missing("order"))?;
+                wfm.update_order_listing(
+                    order_id,
+                    mismatch.order_price.ok_or_else(|| missing("order price"))?,
+                    mismatch.owned.ok_or_else(|| missing("inventory"))?,
+                    mismatch.visible.unwrap_or(true),
+                    &mismatch.item_url,
+                    &mismatch.item_id,
+                    "sell",
+                )
+                .await?;
+            }
+            ReconcileFix::UpdateInventoryQuantity => {
+                db.update_inventory_owned(
+                    mismatch.inventory_id.ok_or_else(|| missing("inventory"))?,
+                    mismatch.order_quantity.ok_or_else(|| missing("order"))?,
+                )
+                .await?;
+            }
+            ReconcileFix::UpdateListedPrice => {
+                db.update_inventory_listed_price(
+                    mismatch.inventory_id.ok_or_else(|| missing("inventory"))?,
+                    mismatch.order_price,
+                )
+                .await?;
+            }
+        }
+        logger::info(
+            "Reconciliation",
+            format!(
+                "Applied fix {:?} to {:?} of {} (rank {})",
+                fix, mismatch.kind, mismatch.item_url, mismatch.rank
+            )
+            .as_str(),
+            true,
+            Some(self.log_file.as_str()),
+        );
+        Ok(())
+    }
+
+    /// Applies the first fix of every mismatch, mismatches that need a price are skipped.
+    /// Orphan orders always need the bought price, so they are left for the user to decide.
+    /// Returns the mismatches that could not be fixed.
+    pub async fn fix_all(&self) -> Result<Vec<Mismatch>, GlobleError> {
+        let mut remaining = vec![];
+        for mismatch in self.reconcile().await? {
+            let fix = match mismatch.fixes.first() {
+                Some(fix) => fix.clone(),
+                None => continue,
+            };
+            let needs_price = match fix {
+                ReconcileFix::AddInventory => true,
+                ReconcileFix::PostOrder => mismatch.listed_price.is_none(),
+                _ => false,
+            };
+            if needs_price {
+                remaining.push(mismatch);
+                continue;
+            }
+            if let Err(e) = self.apply_fix(&mismatch, &fix, None).await {
+                logger::error(
+                    "Reconciliation",
+                    format!("Item: {}, Error: {:?}", mismatch.item_url, e).as_str(),
+                    true,
+                    Some(self.log_file.as_str()),
+                );
+                remaining.push(mismatch);
+            }
+        }
+        Ok(remaining)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn order(id: &str, url_name: &str, rank: Option<i64>, platinum: i64, quantity: i64) -> Order {
+        serde_json::from_value(json!({
+            "id": id,
+            "platinum": platinum,
+            "visible": true,
+            "last_update": "2023-08-01T00:00:00.000+00:00",
+            "region": "en",
+            "platform": "pc",
+            "creation_date": "2023-08-01T00:00:00.000+00:00",
+            "order_type": "sell",
+            "quantity": quantity,
+            "mod_rank": rank,
+            "item": {
+                "id": format!("{}_id", url_name),
+                "url_name": url_name,
+                "icon": "",
+                "icon_format": "",
+                "thumb": "",
+                "tags": [],
+                "en": { "item_name": url_name }
+            }
+        }))
+        .unwrap()
+    }
+
+    fn inventory(id: i64, url_name: &str, rank: i64, owned: i64, listed: Option<i64>) -> Invantory {
+        Invantory {
+            id,
+            item_id: format!("{}_id", url_name),
+            item_url: url_name.to_string(),
+            item_name: url_name.to_string(),
+            rank,
+            price: 10.0,
+            listed_price: listed,
+            owned,
+            listed_at: None,
+        }
+    }
+
+    fn kinds(mismatches: &[Mismatch]) -> Vec<(MismatchKind, String, i64)> {
+        let mut kinds: Vec<_> = mismatches
+            .iter()
+            .map(|m| (m.kind.clone(), m.item_url.clone(), m.rank))
+            .collect();
+        kinds.sort_by(|a, b| {
+            (format!("{:?}", a.0), &a.1, a.2).cmp(&(format!("{:?}", b.0), &b.1, b.2))
+        });
+        kinds
+    }
+
+    #[test]
+    fn matching_order_is_not_a_mismatch() {
+        let orders = vec![order("o1", "rhino_prime_set", None, 50, 2)];
+        let inventorys = vec![inventory(1, "rhino_prime_set", 0, 2, Some(50))];
+        assert!(find_mismatches(orders, &inventorys, &[]).is_empty());
+    }
+
+    #[test]
+    fn unlisted_and_stale_inventory() {
+        let inventorys = vec![
+            inventory(1, "rhino_prime_set", 0, 1, None),
+            inventory(2, "serration", 0, 1, Some(20)),
+        ];
+        let mismatches = find_mismatches(vec![], &inventorys, &[]);
+        assert_eq!(
+            kinds(&mismatches),
+            vec![
+                (MismatchKind::StaleListing, "serration".to_string(), 0),
+                (
+                    MismatchKind::UnlistedInventory,
+                    "rhino_prime_set".to_string(),
+                    0
+                ),
+            ]
+        );
+        let stale = mismatches
+            .iter()
+            .find(|m| m.kind == MismatchKind::StaleListing)
+            .unwrap();
+        assert_eq!(
+            stale.fixes,
+            vec![ReconcileFix::ClearListedPrice, ReconcileFix::PostOrder]
+        );
+    }
+
+    #[test]
+    fn quantity_and_price_drift() {
+        let orders = vec![order("o1", "rhino_prime_set", None, 55, 3)];
+        let inventorys = vec![inventory(1, "rhino_prime_set", 0, 2, Some(50))];
+        let mismatches = find_mismatches(orders, &inventorys, &[]);
+        assert_eq!(
+            kinds(&mismatches),
+            vec![
+                (MismatchKind::PriceDrift, "rhino_prime_set".to_string(), 0),
+                (
+                    MismatchKind::QuantityDrift,
+                    "rhino_prime_set".to_string(),
+                    0
+                ),
+            ]
+        );
+        for mismatch in mismatches.iter() {
+            assert_eq!(mismatch.order_id, Some("o1".to_string()));
+            assert_eq!(mismatch.inventory_id, Some(1));
+        }
+    }
+
+    #[test]
+    fn ranks_are_matched_separately() {
+        let orders = vec![order("o1", "arcane_energize", Some(5), 300, 1)];
+        let inventorys = vec![inventory(1, "arcane_energize", 0, 1, None)];
+        let mismatches = find_mismatches(orders, &inventorys, &[]);
+        assert_eq!(
+            kinds(&mismatches),
+            vec![
+                (MismatchKind::OrphanOrder, "arcane_energize".to_string(), 5),
+                (
+                    MismatchKind::UnlistedInventory,
+                    "arcane_energize".to_string(),
+                    0
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn orphan_order_is_added_before_it_is_deleted() {
+        let orders = vec![order("o1", "serration", None, 20, 1)];
+        let mismatches = find_mismatches(orders, &[], &[]);
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(mismatches[0].kind, MismatchKind::OrphanOrder);
+        assert_eq!(mismatches[0].order_price, Some(20));
+        assert_eq!(
+            mismatches[0].fixes.first(),
+            Some(&ReconcileFix::AddInventory)
+        );
+    }
+
+    #[test]
+    fn blacklisted_items_are_skipped() {
+        let orders = vec![
+            order("o1", "serration", None, 20, 1),
+            order("o2", "rhino_prime_set", None, 55, 3),
+        ];
+        let inventorys = vec![
+            inventory(1, "rhino_prime_set", 0, 2, Some(50)),
+            inventory(2, "arcane_energize", 0, 1, None),
+        ];
+        let blacklist = vec!["serration".to_string(), "rhino_prime_set".to_string()];
+        let mismatches = find_mismatches(orders, &inventorys, &blacklist);
+        assert_eq!(
+            kinds(&mismatches),
+            vec![(
+                MismatchKind::UnlistedInventory,
+                "arcane_energize".to_string(),
+                0
+            )]
+        );
+    }
+}
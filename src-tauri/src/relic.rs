@@ -0,0 +1,436 @@
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use crate::{
+    cache::CacheState, helper, logger, price_scraper::PriceScraper, settings::SettingsState,
+    structs::GlobleError,
+};
+
+// Bump when the layout of the drop table file changes, older files are downloaded again
+const RELIC_DROPS_VERSION: i64 = 1;
+const RELIC_DROPS_URL: &str = "https://drops.warframestat.us/data/relics.json";
+
+// Chance in percent of each single reward by rarity (common, uncommon, rare)
+const REFINEMENTS: [(&str, [f64; 3]); 4] = [
+    ("intact", [25.33, 11.0, 2.0]),
+    ("exceptional", [23.33, 13.0, 4.0]),
+    ("flawless", [20.0, 17.0, 6.0]),
+    ("radiant", [16.67, 20.0, 10.0]),
+];
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RelicReward {
+    pub item_name: String,
+    // "common", "uncommon" or "rare"
+    pub rarity: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RelicDrops {
+    // Full relic name as shown in game, e.g. "Lith A1 Relic"
+    pub name: String,
+    pub rewards: Vec<RelicReward>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct RelicDropsFile {
+    version: i64,
+    updated_at: String,
+    relics: Vec<RelicDrops>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RelicRewardValue {
+    pub item_name: String,
+    // None when the reward is not tradable, e.g. Forma Blueprint
+    pub url_name: Option<String>,
+    pub rarity: String,
+    pub price: f64,
+    pub ducats: i64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RelicRefinementValue {
+    pub refinement: String,
+    pub expected_plat: f64,
+    pub expected_ducats: f64,
+    // "open" when the rewards are worth more than the relic itself, "sell" when they are not and
+    // "unknown" when the relic has no price to compare with
+    pub action: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RelicValue {
+    pub url_name: Option<String>,
+    pub name: String,
+    // None when the relic has no price history
+    pub relic_price: Option<f64>,
+    pub relic_volume: f64,
+    pub refinements: Vec<RelicRefinementValue>,
+    pub rewards: Vec<RelicRewardValue>,
+}
+
+#[derive(Clone, Debug)]
+pub struct RelicState {
+    log_file: String,
+    settings: Arc<Mutex<SettingsState>>,
+    cache: Arc<Mutex<CacheState>>,
+    price_scraper: Arc<Mutex<PriceScraper>>,
+    relics: Arc<Mutex<Vec<RelicDrops>>>,
+}
+
+impl RelicState {
+    pub fn new(
+        settings: Arc<Mutex<SettingsState>>,
+        cache: Arc<Mutex<CacheState>>,
+        price_scraper: Arc<Mutex<PriceScraper>>,
+    ) -> Self {
+        RelicState {
+            log_file: "relics.log".to_string(),
+            settings,
+            cache,
+            price_scraper,
+            relics: Arc::new(Mutex::new(vec![])),
+        }
+    }
+
+    fn get_file_path() -> PathBuf {
+        let cache_path = helper::get_app_roaming_path().join("cache");
+        if !cache_path.exists() {
+            fs::create_dir_all(&cache_path).unwrap();
+        }
+        cache_path.join("relic_drops.json")
+    }
+
+    fn read_from_file(&self) -> Result<bool, GlobleError> {
+        let path = Self::get_file_path();
+        if !path.exists() {
+            return Ok(false);
+        }
+        let mut file = File::open(path)?;
+        let mut content = String::new();
+        file.read_to_string(&mut content)?;
+        let drops: RelicDropsFile = serde_json::from_str(&content)?;
+        if drops.version != RELIC_DROPS_VERSION || drops.relics.is_empty() {
+            return Ok(false);
+        }
+        *self.relics.lock()? = drops.relics;
+        Ok(true)
+    }
+
+    fn save_to_file(&self) -> Result<(), GlobleError> {
+        let drops = RelicDropsFile {
+            version: RELIC_DROPS_VERSION,
+            updated_at: chrono::Local::now().to_string(),
+            relics: self.relics.lock()?.clone(),
+        };
+        let json = serde_json::to_string(&drops)?;
+        // Write next to the file and rename it, so a crash never leaves half a drop table
+        let path = Self::get_file_path();
+        let temp_path = path.with_extension("json.tmp");
+        let mut file = File::create(&temp_path)?;
+        file.write_all(json.as_bytes())?;
+        file.sync_all()?;
+        drop(file);
+        fs::rename(temp_path, path)?;
+        Ok(())
+    }
+
+    /// Downloads the relic drop tables and stores them in the cache folder.
+    pub async fn update_relic_drops(&self) -> Result<usize, GlobleError> {
+        let response = Client::new().get(RELIC_DROPS_URL).send().await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let url = response.url().to_string();
+            return Err(GlobleError::HttpError(
+                status,
+                response.text().await.unwrap_or_default(),
+                url,
+            ));
+        }
+        let json: Value = response.json().await?;
+        let relics = parse_relic_drops(&json);
+        if relics.is_empty() {
+            return Err(GlobleError::OtherError(
+                "No relics found in the drop tables".to_string(),
+            ));
+        }
+        let count = relics.len();
+        *self.relics.lock()? = relics;
+        self.save_to_file()?;
+        logger::info_con(
+            "Relics",
+            format!("Updated {} relic drop tables", count).as_str(),
+        );
+        Ok(count)
+    }
+
+    /// Loads the drop tables from the cache folder, downloading them when missing.
+    pub async fn load_relic_drops(&self) -> Result<(), GlobleError> {
+        if !self.relics.lock()?.is_empty() || self.read_from_file()? {
+            return Ok(());
+        }
+        self.update_relic_drops().await?;
+        Ok(())
+    }
+
+    /// Returns the expected value of every relic at each refinement level,
+    /// ranked by the expected platinum of the `refinement` level.
+    pub async fn get_relic_values(&self, refinement: &str) -> Result<Vec<RelicValue>, GlobleError> {
+        self.load_relic_drops().await?;
+        if !REFINEMENTS.iter().any(|(name, _)| *name == refinement) {
+            return Err(GlobleError::OtherError(format!(
+                "Unknown refinement: {}",
+                refinement
+            )));
+        }
+        let cache = self.cache.lock()?.clone();
+        let summary = self.price_scraper.lock()?.get_price_summary()?;
+        let relics = self.relics.lock()?.clone();
+
+        let mut values = vec![];
+        for relic in relics.iter() {
+            let relic_item = cache.get_item_by_name(&relic.name);
+            let relic_stats = relic_item
+                .as_ref()
+                .and_then(|item| summary.get(&item.url_name));
+            let relic_price = relic_stats.map(|stats| stats.median);
+
+            let rewards: Vec<RelicRewardValue> = relic
+                .rewards
+                .iter()
+                .map(|reward| {
+                    let item = cache.get_item_by_name(&reward.item_name);
+                    let price = item
+                        .as_ref()
+                        .and_then(|item| summary.get(&item.url_name))
+                        .map(|stats| stats.median)
+                        .unwrap_or(0.0);
+                    RelicRewardValue {
+                        item_name: reward.item_name.clone(),
+                        url_name: item.as_ref().map(|item| item.url_name.clone()),
+                        rarity: reward.rarity.clone(),
+                        price,
+                        ducats: item.and_then(|item| item.ducats).unwrap_or(0),
+                    }
+                })
+                .collect();
+
+            let refinements = REFINEMENTS
+                .iter()
+                .map(|(name, chances)| get_refinement_value(name, chances, &rewards, relic_price))
+                .collect();
+
+            values.push(RelicValue {
+                url_name: relic_item.map(|item| item.url_name),
+                name: relic.name.clone(),
+                relic_price,
+                relic_volume: relic_stats.map(|stats| stats.volume).unwrap_or(0.0),
+                refinements,
+                rewards,
+            });
+        }
+
+        let plat_per_ducat = self.settings.lock()?.ducats.plat_per_ducat;
+        let get_score = |value: &RelicValue| {
+            value
+                .refinements
+                .iter()
+                .find(|r| r.refinement == refinement)
+                .map(|r| r.expected_plat.max(r.expected_ducats * plat_per_ducat))
+                .unwrap_or(0.0)
+        };
+        values.sort_by(|a, b| {
+            get_score(b)
+                .partial_cmp(&get_score(a))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        logger::info_file(
+            "Relics",
+            format!("Valued {} relics at {}", values.len(), refinement).as_str(),
+            Some(self.log_file.as_str()),
+        );
+        Ok(values)
+    }
+}
+
+/// The expected value of opening the relic at one refinement, compared with selling it.
+fn get_refinement_value(
+    refinement: &str,
+    chances: &[f64; 3],
+    rewards: &[RelicRewardValue],
+    relic_price: Option<f64>,
+) -> RelicRefinementValue {
+    let (expected_plat, expected_ducats) =
+        rewards.iter().fold((0.0, 0.0), |(plat, ducats), reward| {
+            let chance = get_reward_chance(chances, &reward.rarity) / 100.0;
+            (
+                plat + chance * reward.price,
+                ducats + chance * reward.ducats as f64,
+            )
+        });
+    RelicRefinementValue {
+        refinement: refinement.to_string(),
+        expected_plat,
+        expected_ducats,
+        action: get_action(expected_plat, relic_price).to_string(),
+    }
+}
+
+/// Without a price for the relic we can not tell that opening it beats selling it.
+fn get_action(expected_plat: f64, relic_price: Option<f64>) -> &'static str {
+    match relic_price {
+        Some(price) if expected_plat > price => "open",
+        Some(_) => "sell",
+        None => "unknown",
+    }
+}
+
+fn get_reward_chance(chances: &[f64; 3], rarity: &str) -> f64 {
+    match rarity {
+        "common" => chances[0],
+        "uncommon" => chances[1],
+        "rare" => chances[2],
+        _ => 0.0,
+    }
+}
+
+/// Converts the warframestat drop tables into our format. The tables list every relic once
+/// per refinement, only the intact entries are used since the rarities are the same.
+fn parse_relic_drops(json: &Value) -> Vec<RelicDrops> {
+    let mut relics = vec![];
+    let entries = match json.get("relics").and_then(|v| v.as_array()) {
+        Some(entries) => entries,
+        None => return relics,
+    };
+    for entry in entries {
+        if entry.get("state").and_then(|v| v.as_str()) != Some("Intact") {
+            continue;
+        }
+        let tier = entry.get("tier").and_then(|v| v.as_str()).unwrap_or("");
+        let relic_name = entry
+            .get("relicName")
+            .and_then(|v| v.as_str())
+            .unwrap_or("");
+        if tier.is_empty() || relic_name.is_empty() {
+            continue;
+        }
+        let rewards = entry
+            .get("rewards")
+            .and_then(|v| v.as_array())
+            .map(|rewards| {
+                rewards
+                    .iter()
+                    .filter_map(|reward| {
+                        Some(RelicReward {
+                            item_name: reward.get("itemName")?.as_str()?.to_string(),
+                            rarity: reward.get("rarity")?.as_str()?.to_lowercase(),
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        relics.push(RelicDrops {
+            name: format!("{} {} Relic", tier, relic_name),
+            rewards,
+        });
+    }
+    relics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn reward(rarity: &str, price: f64, ducats: i64) -> RelicRewardValue {
+        RelicRewardValue {
+            item_name: format!("{} reward", rarity),
+            url_name: None,
+            rarity: rarity.to_string(),
+            price,
+            ducats,
+        }
+    }
+
+    fn rewards() -> Vec<RelicRewardValue> {
+        vec![
+            reward("common", 10.0, 15),
+            reward("common", 10.0, 15),
+            reward("common", 10.0, 15),
+            reward("uncommon", 20.0, 45),
+            reward("uncommon", 20.0, 45),
+            reward("rare", 100.0, 100),
+        ]
+    }
+
+    fn intact() -> [f64; 3] {
+        REFINEMENTS[0].1
+    }
+
+    #[test]
+    fn expected_value_weights_rewards_by_chance() {
+        let value = get_refinement_value("intact", &intact(), &rewards(), Some(10.0));
+        // 3 * 25.33% * 10 + 2 * 11% * 20 + 2% * 100
+        assert!((value.expected_plat - 13.999).abs() < 1e-9);
+        // 3 * 25.33% * 15 + 2 * 11% * 45 + 2% * 100
+        assert!((value.expected_ducats - 23.2985).abs() < 1e-9);
+        assert_eq!(value.refinement, "intact");
+    }
+
+    #[test]
+    fn radiant_favors_the_rare_reward() {
+        let intact = get_refinement_value("intact", &intact(), &rewards(), None);
+        let radiant = get_refinement_value("radiant", &REFINEMENTS[3].1, &rewards(), None);
+        assert!(radiant.expected_plat > intact.expected_plat);
+    }
+
+    #[test]
+    fn open_or_sell() {
+        assert_eq!(get_action(14.0, Some(10.0)), "open");
+        assert_eq!(get_action(14.0, Some(20.0)), "sell");
+        assert_eq!(get_action(14.0, Some(14.0)), "sell");
+        // A relic without a price may be worth more than its rewards
+        assert_eq!(get_action(14.0, None), "unknown");
+        let value = get_refinement_value("intact", &intact(), &rewards(), None);
+        assert_eq!(value.action, "unknown");
+    }
+
+    #[test]
+    fn unknown_rarity_has_no_chance() {
+        assert_eq!(get_reward_chance(&intact(), "legendary"), 0.0);
+    }
+
+    #[test]
+    fn parses_intact_drop_tables() {
+        let json = json!({
+            "relics": [
+                {
+                    "tier": "Lith",
+                    "relicName": "A1",
+                    "state": "Intact",
+                    "rewards": [
+                        { "itemName": "Akstiletto Prime Barrel", "rarity": "Uncommon", "chance": 11 },
+                        { "itemName": "Forma Blueprint", "rarity": "Common", "chance": 25.33 }
+                    ]
+                },
+                {
+                    "tier": "Lith",
+                    "relicName": "A1",
+                    "state": "Radiant",
+                    "rewards": []
+                }
+            ]
+        });
+        let relics = parse_relic_drops(&json);
+        assert_eq!(relics.len(), 1);
+        assert_eq!(relics[0].name, "Lith A1 Relic");
+        assert_eq!(relics[0].rewards.len(), 2);
+        assert_eq!(relics[0].rewards[0].rarity, "uncommon");
+    }
+}
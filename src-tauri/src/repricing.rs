@@ -0,0 +1,206 @@
+use chrono::{DateTime, Utc};
+
+use crate::settings::RepricingSettings;
+
+/// Hours since the date, None when it can not be parsed.
+pub fn get_age_hours(date: &str, now: DateTime<Utc>) -> Option<f64> {
+    let date = DateTime::parse_from_rfc3339(date).ok()?;
+    Some((now - date.with_timezone(&Utc)).num_minutes() as f64 / 60.0)
+}
+
+/// Full days the order has been up after the grace period. Whole days only, so the price moves
+/// once a day instead of on every live scraper iteration.
+fn get_overdue_days(since: Option<&str>, settings: &RepricingSettings, now: DateTime<Utc>) -> i64 {
+    match since.and_then(|since| get_age_hours(since, now)) {
+        Some(age) => ((age - settings.grace_hours as f64) / 24.0)
+            .floor()
+            .max(0.0) as i64,
+        None => 0,
+    }
+}
+
+/// Lowers the sell price by the daily decay for every day the listing stayed unsold, but never
+/// below the cost basis plus the floor profit. A price that is already below the floor is kept.
+pub fn apply_sell_decay(
+    price: i64,
+    cost_basis: f64,
+    listed_at: Option<&str>,
+    settings: &RepricingSettings,
+    now: DateTime<Utc>,
+) -> i64 {
+    if !settings.enabled {
+        return price;
+    }
+    let days = get_overdue_days(listed_at, settings, now);
+    let floor = cost_basis.ceil() as i64 + settings.sell_floor_profit;
+    if days == 0 || price <= floor {
+        return price;
+    }
+    (price - settings.sell_decay_per_day * days).max(floor)
+}
+
+/// Raises the buy price by the daily increase for every day the order stayed unfilled, but never
+/// above the average closed price minus the ceiling profit. A price that is already above the
+/// ceiling is kept.
+pub fn apply_buy_increase(
+    price: i64,
+    closed_avg: f64,
+    created_at: Option<&str>,
+    settings: &RepricingSettings,
+    now: DateTime<Utc>,
+) -> i64 {
+    if !settings.enabled {
+        return price;
+    }
+    let days = get_overdue_days(created_at, settings, now);
+    let ceiling = closed_avg.floor() as i64 - settings.buy_ceiling_profit;
+    if days == 0 || price >= ceiling {
+        return price;
+    }
+    (price + settings.buy_increase_per_day * days).min(ceiling)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> RepricingSettings {
+        RepricingSettings {
+            enabled: true,
+            grace_hours: 24,
+            sell_decay_per_day: 2,
+            sell_floor_profit: 5,
+            buy_increase_per_day: 1,
+            buy_ceiling_profit: 10,
+        }
+    }
+
+    fn now() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2023-08-10T12:00:00+00:00")
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    // The date the given number of hours before now
+    fn hours_ago(hours: i64) -> String {
+        (now() - chrono::Duration::hours(hours)).to_rfc3339()
+    }
+
+    #[test]
+    fn age_hours() {
+        assert_eq!(get_age_hours(&hours_ago(30), now()), Some(30.0));
+        assert_eq!(get_age_hours("not a date", now()), None);
+    }
+
+    #[test]
+    fn sell_decay_waits_for_the_grace_period() {
+        let settings = settings();
+        for hours in [0, 12, 24, 47] {
+            assert_eq!(
+                apply_sell_decay(100, 50.0, Some(&hours_ago(hours)), &settings, now()),
+                100,
+                "{} hours",
+                hours
+            );
+        }
+    }
+
+    #[test]
+    fn sell_decay_steps_once_a_day() {
+        let settings = settings();
+        // Grace ends after 24 hours, then one step per full day
+        let steps = [(48, 98), (60, 98), (71, 98), (72, 96), (96, 94), (144, 90)];
+        for (hours, price) in steps {
+            assert_eq!(
+                apply_sell_decay(100, 50.0, Some(&hours_ago(hours)), &settings, now()),
+                price,
+                "{} hours",
+                hours
+            );
+        }
+    }
+
+    #[test]
+    fn sell_decay_stops_at_the_floor() {
+        let settings = settings();
+        // Floor is the rounded up cost basis plus the floor profit, 51 + 5
+        assert_eq!(
+            apply_sell_decay(60, 50.5, Some(&hours_ago(24 * 30)), &settings, now()),
+            56
+        );
+        // Already below the floor, the price is left alone
+        assert_eq!(
+            apply_sell_decay(52, 50.5, Some(&hours_ago(24 * 30)), &settings, now()),
+            52
+        );
+    }
+
+    #[test]
+    fn sell_decay_without_a_date_or_disabled() {
+        let mut settings = settings();
+        assert_eq!(apply_sell_decay(100, 50.0, None, &settings, now()), 100);
+        assert_eq!(
+            apply_sell_decay(100, 50.0, Some("not a date"), &settings, now()),
+            100
+        );
+        settings.enabled = false;
+        assert_eq!(
+            apply_sell_decay(100, 50.0, Some(&hours_ago(24 * 30)), &settings, now()),
+            100
+        );
+    }
+
+    #[test]
+    fn buy_increase_steps_once_a_day() {
+        let settings = settings();
+        let steps = [(0, 50), (47, 50), (48, 51), (71, 51), (72, 52), (144, 55)];
+        for (hours, price) in steps {
+            assert_eq!(
+                apply_buy_increase(50, 100.0, Some(&hours_ago(hours)), &settings, now()),
+                price,
+                "{} hours",
+                hours
+            );
+        }
+    }
+
+    #[test]
+    fn buy_increase_stops_at_the_ceiling() {
+        let settings = settings();
+        // Ceiling is the rounded down closed average minus the ceiling profit, 80 - 10
+        assert_eq!(
+            apply_buy_increase(60, 80.9, Some(&hours_ago(24 * 30)), &settings, now()),
+            70
+        );
+        // Already above the ceiling, the price is left alone
+        assert_eq!(
+            apply_buy_increase(75, 80.9, Some(&hours_ago(24 * 30)), &settings, now()),
+            75
+        );
+    }
+
+    #[test]
+    fn buy_increase_without_a_date_or_disabled() {
+        let mut settings = settings();
+        assert_eq!(apply_buy_increase(50, 100.0, None, &settings, now()), 50);
+        settings.enabled = false;
+        assert_eq!(
+            apply_buy_increase(50, 100.0, Some(&hours_ago(24 * 30)), &settings, now()),
+            50
+        );
+    }
+
+    #[test]
+    fn a_longer_grace_period_delays_the_first_step() {
+        let mut settings = settings();
+        settings.grace_hours = 48;
+        assert_eq!(
+            apply_sell_decay(100, 50.0, Some(&hours_ago(71)), &settings, now()),
+            100
+        );
+        assert_eq!(
+            apply_sell_decay(100, 50.0, Some(&hours_ago(72)), &settings, now()),
+            98
+        );
+    }
+}
@@ -0,0 +1,399 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+
+use crate::{
+    auth::AuthState,
+    helper, logger,
+    structs::{Auction, GlobleError, RivenItem},
+    wfm_client::WFMClientState,
+};
+
+// Auctions sharing less than this part of their attributes are not compared
+const MIN_SIMILARITY: f64 = 0.5;
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct RivenSearchFilter {
+    pub weapon_url_name: String,
+    #[serde(default)]
+    pub positive_stats: Vec<String>,
+    // Use "none" to only find rivens without a negative attribute
+    #[serde(default)]
+    pub negative_stats: Vec<String>,
+    #[serde(default)]
+    pub polarity: Option<String>,
+    #[serde(default)]
+    pub mastery_rank_min: Option<i64>,
+    #[serde(default)]
+    pub mastery_rank_max: Option<i64>,
+    #[serde(default)]
+    pub re_rolls_max: Option<i64>,
+    // The price range is not supported by the API and is filtered after the search
+    #[serde(default)]
+    pub min_price: Option<i64>,
+    #[serde(default)]
+    pub max_price: Option<i64>,
+    // Only auctions with a buyout price
+    #[serde(default)]
+    pub buyout_only: bool,
+}
+
+impl RivenSearchFilter {
+    pub fn to_query(&self) -> Vec<(String, String)> {
+        let mut query = vec![("weapon_url_name".to_string(), self.weapon_url_name.clone())];
+        if !self.positive_stats.is_empty() {
+            query.push(("positive_stats".to_string(), self.positive_stats.join(",")));
+        }
+        if !self.negative_stats.is_empty() {
+            query.push(("negative_stats".to_string(), self.negative_stats.join(",")));
+        }
+        if let Some(polarity) = &self.polarity {
+            query.push(("polarity".to_string(), polarity.clone()));
+        }
+        if let Some(rank) = self.mastery_rank_min {
+            query.push(("mastery_rank_min".to_string(), rank.to_string()));
+        }
+        if let Some(rank) = self.mastery_rank_max {
+            query.push(("mastery_rank_max".to_string(), rank.to_string()));
+        }
+        if let Some(re_rolls) = self.re_rolls_max {
+            query.push(("re_rolls_max".to_string(), re_rolls.to_string()));
+        }
+        if self.buyout_only {
+            query.push(("buyout_policy".to_string(), "direct".to_string()));
+        }
+        query.push(("sort_by".to_string(), "price_asc".to_string()));
+        query
+    }
+
+    pub fn matches(&self, auction: &Auction) -> bool {
+        let price = get_auction_price(auction);
+        price >= self.min_price.unwrap_or(i64::MIN) && price <= self.max_price.unwrap_or(i64::MAX)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RivenPriceEstimate {
+    pub weapon_url_name: String,
+    pub estimated_price: i64,
+    // First and third quartile of the comparable prices
+    pub low: i64,
+    pub high: i64,
+    pub comparables: Vec<Auction>,
+}
+
+/// The price an auction can be bought for, the buyout price for direct sells
+/// and otherwise the current top bid or the starting price.
+pub fn get_auction_price(auction: &Auction) -> i64 {
+    if auction.is_direct_sell {
+        return auction.buyout_price.unwrap_or(auction.starting_price);
+    }
+    auction
+        .top_bid
+        .unwrap_or(auction.starting_price)
+        .max(auction.starting_price)
+}
+
+/// Share of attributes the rivens have in common, 1.0 when they have the same attributes.
+fn get_similarity(a: &RivenItem, b: &RivenItem) -> f64 {
+    let shared = a
+        .attributes
+        .iter()
+        .filter(|attribute| {
+            b.attributes.iter().any(|other| {
+                other.url_name == attribute.url_name && other.positive == attribute.positive
+            })
+        })
+        .count();
+    let total = a.attributes.len().max(b.attributes.len());
+    if total == 0 {
+        return 0.0;
+    }
+    shared as f64 / total as f64
+}
+
+fn get_percentile(prices: &[i64], percentile: f64) -> i64 {
+    let index = ((prices.len() - 1) as f64 * percentile).round() as usize;
+    prices[index]
+}
+
+/// Estimates the price of the riven from the auctions of the same weapon with similar
+/// attributes. Closed auctions and auctions of `exclude_owner` are ignored.
+pub fn estimate_riven_price(
+    riven: &RivenItem,
+    auctions: &[Auction],
+    exclude_owner: &str,
+) -> Option<RivenPriceEstimate> {
+    let mut comparables: Vec<(f64, Auction)> = auctions
+        .iter()
+        .filter(|auction| !auction.closed && auction.visible)
+        .filter(|auction| auction.owner.ingame_name != exclude_owner)
+        .filter(|auction| auction.item.weapon_url_name == riven.weapon_url_name)
+        .map(|auction| (get_similarity(riven, &auction.item), auction.clone()))
+        .filter(|(similarity, _)| *similarity >= MIN_SIMILARITY)
+        .collect();
+    if comparables.is_empty() {
+        return None;
+    }
+    // Prefer the closest matches, fall back to all comparables when there are only a few
+    comparables.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+    let best = comparables[0].0;
+    let closest: Vec<(f64, Auction)> = comparables
+        .iter()
+        .filter(|(similarity, _)| *similarity >= best)
+        .cloned()
+        .collect();
+    if closest.len() >= 3 {
+        comparables = closest;
+    }
+
+    let mut prices: Vec<i64> = comparables
+        .iter()
+        .map(|(_, auction)| get_auction_price(auction))
+        .collect();
+    prices.sort();
+    Some(RivenPriceEstimate {
+        weapon_url_name: riven.weapon_url_name.clone(),
+        estimated_price: get_percentile(&prices, 0.5),
+        low: get_percentile(&prices, 0.25),
+        high: get_percentile(&prices, 0.75),
+        comparables: comparables
+            .into_iter()
+            .map(|(_, auction)| auction)
+            .collect(),
+    })
+}
+
+#[derive(Clone, Debug)]
+pub struct RivenState {
+    log_file: String,
+    wfm: Arc<Mutex<WFMClientState>>,
+    auth: Arc<Mutex<AuthState>>,
+    my_auctions: Arc<Mutex<Vec<Auction>>>,
+}
+
+impl RivenState {
+    pub fn new(wfm: Arc<Mutex<WFMClientState>>, auth: Arc<Mutex<AuthState>>) -> Self {
+        RivenState {
+            log_file: "rivens.log".to_string(),
+            wfm,
+            auth,
+            my_auctions: Arc::new(Mutex::new(vec![])),
+        }
+    }
+
+    pub async fn search(&self, filter: &RivenSearchFilter) -> Result<Vec<Auction>, GlobleError> {
+        let wfm = self.wfm.lock()?.clone();
+        let auctions = wfm.search_riven_auctions(filter.to_query()).await?;
+        Ok(auctions
+            .into_iter()
+            .filter(|auction| filter.matches(auction))
+            .collect())
+    }
+
+    /// Refreshes our own riven auctions and notifies the window about changes.
+    pub async fn update_my_auctions(&self) -> Result<Vec<Auction>, GlobleError> {
+        let wfm = self.wfm.lock()?.clone();
+        let auctions: Vec<Auction> = wfm
+            .get_user_auctions()
+            .await?
+            .into_iter()
+            .filter(|auction| auction.item.item_type == "riven")
+            .collect();
+
+        let mut my_auctions = self.my_auctions.lock()?;
+        for auction in auctions.iter() {
+            match my_auctions.iter().find(|old| old.id == auction.id) {
+                Some(old) if get_auction_price(old) != get_auction_price(auction) => {
+                    logger::info(
+                        "Rivens",
+                        format!(
+                            "Auction: {}, Price: {} -> {}",
+                            auction.item.name,
+                            get_auction_price(old),
+                            get_auction_price(auction)
+                        )
+                        .as_str(),
+                        true,
+                        Some(self.log_file.as_str()),
+                    );
+                }
+                Some(_) => {}
+                None => logger::info(
+                    "Rivens",
+                    format!("New Auction: {}", auction.item.name).as_str(),
+                    true,
+                    Some(self.log_file.as_str()),
+                ),
+            }
+        }
+        *my_auctions = auctions.clone();
+        helper::send_message_to_window("riven_auctions", Some(json!(auctions)));
+        Ok(auctions)
+    }
+
+    pub fn get_my_auctions(&self) -> Result<Vec<Auction>, GlobleError> {
+        Ok(self.my_auctions.lock()?.clone())
+    }
+
+    /// Estimates the price of the riven from the current auctions with its positive attributes.
+    pub async fn estimate_price(
+        &self,
+        riven: &RivenItem,
+    ) -> Result<Option<RivenPriceEstimate>, GlobleError> {
+        let in_game_name = self.auth.lock()?.ingame_name.clone();
+        let filter = RivenSearchFilter {
+            weapon_url_name: riven.weapon_url_name.clone(),
+            positive_stats: riven
+                .attributes
+                .iter()
+                .filter(|attribute| attribute.positive)
+                .map(|attribute| attribute.url_name.clone())
+                .collect(),
+            ..Default::default()
+        };
+        let mut auctions = self.search(&filter).await?;
+        // Too few exact matches, compare against every riven of the weapon instead
+        if auctions.len() < 3 {
+            auctions = self
+                .search(&RivenSearchFilter {
+                    weapon_url_name: riven.weapon_url_name.clone(),
+                    ..Default::default()
+                })
+                .await?;
+        }
+        let estimate = estimate_riven_price(riven, &auctions, &in_game_name);
+        if let Some(estimate) = &estimate {
+            logger::info_file(
+                "Rivens",
+                format!(
+                    "Estimate: {}, Price: {} ({} - {}), Comparables: {}",
+                    riven.weapon_url_name,
+                    estimate.estimated_price,
+                    estimate.low,
+                    estimate.high,
+                    estimate.comparables.len()
+                )
+                .as_str(),
+                Some(self.log_file.as_str()),
+            );
+        }
+        Ok(estimate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use crate::structs::RivenAttribute;
+
+    fn get_auctions() -> Vec<Auction> {
+        fixtures::read_payload("wfm/auctions_search.json", Some("auctions"))
+    }
+
+    fn get_riven(weapon_url_name: &str) -> RivenItem {
+        let attribute = |url_name: &str, positive: bool, value: f64| RivenAttribute {
+            url_name: url_name.to_string(),
+            positive,
+            value,
+        };
+        RivenItem {
+            item_type: "riven".to_string(),
+            weapon_url_name: weapon_url_name.to_string(),
+            name: "crita-satiata".to_string(),
+            mod_rank: 0,
+            re_rolls: 0,
+            mastery_level: 12,
+            polarity: "madurai".to_string(),
+            attributes: vec![
+                attribute("critical_chance", true, 110.0),
+                attribute("multishot", true, 90.0),
+                attribute("zoom", false, -40.0),
+            ],
+        }
+    }
+
+    #[test]
+    fn parses_auctions() {
+        let auctions = get_auctions();
+        assert_eq!(auctions.len(), 9);
+        let first = &auctions[0];
+        assert_eq!(first.owner.ingame_name, "TraderOne");
+        assert_eq!(first.buyout_price, Some(100));
+        assert_eq!(first.item.item_type, "riven");
+        assert_eq!(first.item.weapon_url_name, "soma");
+        assert_eq!(first.item.attributes.len(), 3);
+        assert!(!first.item.attributes[2].positive);
+        // Lich items only share the type and weapon with rivens
+        let lich = &auctions[8];
+        assert_eq!(lich.item.item_type, "lich");
+        assert!(lich.item.attributes.is_empty());
+    }
+
+    #[test]
+    fn auction_price() {
+        let auctions = get_auctions();
+        // Direct sell uses the buyout price
+        assert_eq!(get_auction_price(&auctions[0]), 100);
+        // Auctions use the top bid when it is above the starting price
+        assert_eq!(get_auction_price(&auctions[2]), 200);
+        let mut auction = auctions[2].clone();
+        auction.top_bid = None;
+        assert_eq!(get_auction_price(&auction), 120);
+    }
+
+    #[test]
+    fn estimate_uses_closest_matches() {
+        let estimate = estimate_riven_price(&get_riven("soma"), &get_auctions(), "Me").unwrap();
+        // Closed, own, other weapon and dissimilar auctions are ignored, and the partial
+        // match is dropped because there are three exact matches
+        let ids: Vec<&str> = estimate
+            .comparables
+            .iter()
+            .map(|auction| auction.owner.ingame_name.as_str())
+            .collect();
+        assert_eq!(ids.len(), 3);
+        assert!(ids.contains(&"TraderOne"));
+        assert!(ids.contains(&"TraderTwo"));
+        assert!(ids.contains(&"TraderThree"));
+        assert_eq!(estimate.estimated_price, 150);
+        assert_eq!(estimate.low, 150);
+        assert_eq!(estimate.high, 200);
+    }
+
+    #[test]
+    fn estimate_falls_back_to_similar_matches() {
+        let auctions: Vec<Auction> = get_auctions()
+            .into_iter()
+            .filter(|auction| auction.owner.ingame_name != "TraderThree")
+            .collect();
+        let estimate = estimate_riven_price(&get_riven("soma"), &auctions, "Me").unwrap();
+        assert_eq!(estimate.comparables.len(), 3);
+        assert_eq!(estimate.estimated_price, 150);
+        assert_eq!(estimate.low, 150);
+        assert_eq!(estimate.high, 300);
+    }
+
+    #[test]
+    fn estimate_without_comparables() {
+        assert!(estimate_riven_price(&get_riven("lanka"), &get_auctions(), "Me").is_none());
+    }
+
+    #[test]
+    fn filter_price_range() {
+        let auctions = get_auctions();
+        let filter = RivenSearchFilter {
+            weapon_url_name: "soma".to_string(),
+            min_price: Some(120),
+            max_price: Some(300),
+            ..Default::default()
+        };
+        let matches: Vec<i64> = auctions
+            .iter()
+            .filter(|auction| filter.matches(auction))
+            .map(get_auction_price)
+            .collect();
+        assert_eq!(matches, vec![150, 200, 300]);
+    }
+}
@@ -0,0 +1,129 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::{
+    settings::SchedulerSettings,
+    structs::{ItemSchedule, OrderByItem},
+};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScheduleTier {
+    // We have an order up, the item is very profitable or competitors just moved
+    Hot,
+    Warm,
+    Cold,
+}
+
+pub fn get_tier(
+    settings: &SchedulerSettings,
+    has_order: bool,
+    expected_profit: f64,
+    changed: bool,
+) -> ScheduleTier {
+    if has_order || changed || expected_profit >= settings.hot_profit {
+        ScheduleTier::Hot
+    } else if expected_profit >= settings.warm_profit {
+        ScheduleTier::Warm
+    } else {
+        ScheduleTier::Cold
+    }
+}
+
+pub fn get_interval_seconds(settings: &SchedulerSettings, tier: &ScheduleTier) -> i64 {
+    match tier {
+        ScheduleTier::Hot => settings.hot_interval_seconds,
+        ScheduleTier::Warm => settings.warm_interval_seconds,
+        ScheduleTier::Cold => settings.cold_interval_seconds,
+    }
+    .max(0)
+}
+
+fn get_next_check(schedule: Option<&ItemSchedule>) -> Option<DateTime<Utc>> {
+    let next_check = DateTime::parse_from_rfc3339(&schedule?.next_check).ok()?;
+    Some(next_check.with_timezone(&Utc))
+}
+
+/// Returns the items that are due, the longest overdue first. Items that were never checked are
+/// due right away and keep their order in front of the rest.
+pub fn get_due_items(
+    items: &[String],
+    schedules: &HashMap<String, ItemSchedule>,
+    now: DateTime<Utc>,
+) -> Vec<String> {
+    let mut due: Vec<(Option<DateTime<Utc>>, String)> = items
+        .iter()
+        .map(|item| (get_next_check(schedules.get(item)), item.clone()))
+        .filter(|(next_check, _)| next_check.map_or(true, |next_check| next_check <= now))
+        .collect();
+    // None sorts before any date, the sort is stable
+    due.sort_by_key(|(next_check, _)| *next_check);
+    due.into_iter().map(|(_, item)| item).collect()
+}
+
+/// How long the live scraper can sleep before the next item is due.
+pub fn get_wait(
+    settings: &SchedulerSettings,
+    items: &[String],
+    schedules: &HashMap<String, ItemSchedule>,
+    now: DateTime<Utc>,
+) -> std::time::Duration {
+    let min_wait = settings.min_loop_seconds.max(1);
+    let max_wait = settings.max_idle_seconds.max(min_wait);
+    let wait = items
+        .iter()
+        .map(|item| match get_next_check(schedules.get(item)) {
+            Some(next_check) => (next_check - now).num_seconds(),
+            None => 0,
+        })
+        .min()
+        .unwrap_or(max_wait)
+        .clamp(min_wait, max_wait);
+    std::time::Duration::from_secs(wait as u64)
+}
+
+/// Best ingame buy and sell price of the other players.
+pub fn get_best_prices(orders: &[OrderByItem], own_name: &str) -> (Option<i64>, Option<i64>) {
+    let competitors = orders
+        .iter()
+        .filter(|order| order.user.status == "ingame" && order.user.ingame_name != own_name);
+    let mut best_buy: Option<i64> = None;
+    let mut best_sell: Option<i64> = None;
+    for order in competitors {
+        if order.order_type == "buy" {
+            best_buy = Some(best_buy.map_or(order.platinum, |best| best.max(order.platinum)));
+        } else {
+            best_sell = Some(best_sell.map_or(order.platinum, |best| best.min(order.platinum)));
+        }
+    }
+    (best_buy, best_sell)
+}
+
+/// Plans the next check of an item. `best_prices` is None when the order book was not fetched,
+/// the previous prices are kept and the book counts as unchanged.
+pub fn next_schedule(
+    item: &str,
+    previous: Option<&ItemSchedule>,
+    settings: &SchedulerSettings,
+    has_order: bool,
+    expected_profit: f64,
+    best_prices: Option<(Option<i64>, Option<i64>)>,
+    now: DateTime<Utc>,
+) -> ItemSchedule {
+    let previous_prices = previous.map(|previous| (previous.best_buy, previous.best_sell));
+    let changed = match (previous_prices, best_prices) {
+        (Some(previous), Some(current)) => previous != current,
+        _ => false,
+    };
+    let (best_buy, best_sell) = best_prices.or(previous_prices).unwrap_or((None, None));
+    let tier = get_tier(settings, has_order, expected_profit, changed);
+    let interval_seconds = get_interval_seconds(settings, &tier);
+    ItemSchedule {
+        item_url: item.to_string(),
+        next_check: (now + Duration::seconds(interval_seconds)).to_rfc3339(),
+        interval_seconds,
+        best_buy,
+        best_sell,
+    }
+}
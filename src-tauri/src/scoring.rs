@@ -0,0 +1,110 @@
+use serde::{Deserialize, Serialize};
+
+use crate::settings::ScoringSettings;
+
+// A margin of this many percent of the price, or this much platinum, scores full marks
+const FULL_MARGIN_PERCENT: f64 = 30.0;
+const FULL_MARGIN_PLAT: f64 = 30.0;
+// Daily volume that scores full marks, the score grows logarithmically up to it
+const FULL_VOLUME: f64 = 100.0;
+// Used when there is no order book snapshot or forecast for the item yet
+const UNKNOWN_SCORE: f64 = 0.5;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ScoreInput {
+    pub name: String,
+    pub closed_avg: f64,
+    pub max_buy: f64,
+    pub volume: f64,
+    // Forecasted daily volatility in percent
+    pub volatility: Option<f64>,
+    // Ingame sellers from the latest order book snapshot
+    pub sellers: Option<i64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OpportunityScore {
+    pub name: String,
+    // Weighted score from 0 to 100
+    pub score: f64,
+    // Profit when buying one above the best buy order and selling one below the average
+    pub expected_margin: f64,
+    pub time_to_sell_hours: Option<f64>,
+    pub volume: f64,
+    pub volatility: Option<f64>,
+    pub sellers: Option<i64>,
+    // Components of the score, each from 0 to 1
+    pub margin_score: f64,
+    pub volume_score: f64,
+    pub volatility_score: f64,
+    pub time_to_sell_score: f64,
+    pub competition_score: f64,
+}
+
+/// Scores a buy opportunity on margin, liquidity, price stability, time to sell and competition.
+pub fn score_opportunity(input: &ScoreInput, settings: &ScoringSettings) -> OpportunityScore {
+    let expected_margin = (input.closed_avg - 1.0) - (input.max_buy + 1.0);
+    let margin_score = if expected_margin <= 0.0 || input.closed_avg <= 0.0 {
+        0.0
+    } else {
+        let percent = expected_margin / input.closed_avg * 100.0;
+        0.5 * (percent / FULL_MARGIN_PERCENT).min(1.0)
+            + 0.5 * (expected_margin / FULL_MARGIN_PLAT).min(1.0)
+    };
+
+    let volume_score = ((1.0 + input.volume.max(0.0)).ln() / (1.0 + FULL_VOLUME).ln()).min(1.0);
+
+    let volatility_score = match input.volatility {
+        Some(volatility) => 1.0 / (1.0 + volatility.abs() / 10.0),
+        None => UNKNOWN_SCORE,
+    };
+
+    // Every seller ahead of us has to sell first, at the average daily volume
+    let time_to_sell_hours = input
+        .sellers
+        .map(|sellers| 24.0 * (sellers + 1) as f64 / input.volume.max(0.1));
+    let time_to_sell_score = match time_to_sell_hours {
+        Some(hours) => 1.0 / (1.0 + hours / 24.0),
+        None => UNKNOWN_SCORE,
+    };
+
+    let competition_score = match input.sellers {
+        Some(sellers) => 1.0 / (1.0 + sellers as f64 / 10.0),
+        None => UNKNOWN_SCORE,
+    };
+
+    let weights = [
+        (settings.margin_weight, margin_score),
+        (settings.volume_weight, volume_score),
+        (settings.volatility_weight, volatility_score),
+        (settings.time_to_sell_weight, time_to_sell_score),
+        (settings.competition_weight, competition_score),
+    ];
+    let total_weight: f64 = weights.iter().map(|(weight, _)| weight.max(0.0)).sum();
+    // Without a margin there is nothing to gain, however liquid the item is
+    let score = if total_weight > 0.0 && expected_margin > 0.0 {
+        weights
+            .iter()
+            .map(|(weight, score)| weight.max(0.0) * score)
+            .sum::<f64>()
+            / total_weight
+            * 100.0
+    } else {
+        0.0
+    };
+
+    OpportunityScore {
+        name: input.name.clone(),
+        score,
+        expected_margin,
+        time_to_sell_hours,
+        volume: input.volume,
+        volatility: input.volatility,
+        sellers: input.sellers,
+        margin_score,
+        volume_score,
+        volatility_score,
+        time_to_sell_score,
+        competition_score,
+    }
+}
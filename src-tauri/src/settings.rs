@@ -1,12 +1,22 @@
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::{self, Read, Write};
 use std::path::PathBuf;
 
-use crate::helper;
+use crate::{helper, logger};
 
+// Bump when fields are renamed or restructured and add the step to `migrate`
+pub const SETTINGS_VERSION: i64 = 1;
+
+// Missing fields are filled from the defaults, so older settings files keep loading
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct SettingsState {
+    // Files without a version predate versioning and count as version 0
+    #[serde(default)]
+    pub version: i64,
     pub volume_threshold: i64,
     pub range_threshold: i64,
     pub avg_price_cap: i64,
@@ -17,11 +27,531 @@ pub struct SettingsState {
     pub strict_whitelist: bool,
     pub ping_on_notif: bool,
     pub webhook: String,
+    #[serde(default)]
+    pub notifications: NotificationSettings,
+    #[serde(default)]
+    pub alert_rules: Vec<AlertRule>,
+    #[serde(default)]
+    pub arbitrage: ArbitrageSettings,
+    #[serde(default)]
+    pub ducats: DucatSettings,
+    #[serde(default)]
+    pub order_book: OrderBookSettings,
+    #[serde(default)]
+    pub forecast: ForecastSettings,
+    #[serde(default)]
+    pub scoring: ScoringSettings,
+    #[serde(default)]
+    pub websocket: WebsocketSettings,
+    #[serde(default)]
+    pub status: StatusSettings,
+    #[serde(default)]
+    pub live_scraper: LiveScraperSettings,
+    #[serde(default)]
+    pub price_source: PriceSourceSettings,
+    #[serde(default)]
+    pub trust: TrustSettings,
+    #[serde(default)]
+    pub outliers: OutlierSettings,
+    #[serde(default)]
+    pub repricing: RepricingSettings,
+    #[serde(default)]
+    pub allocation: AllocationSettings,
+    #[serde(default)]
+    pub scheduler: SchedulerSettings,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SchedulerSettings {
+    // Check items on their own cadence instead of all of them every loop
+    pub enabled: bool,
+    // Items with an order, high profit or a changed order book
+    pub hot_interval_seconds: i64,
+    pub warm_interval_seconds: i64,
+    pub cold_interval_seconds: i64,
+    // Expected profit in plat that makes an item hot or warm
+    pub hot_profit: f64,
+    pub warm_profit: f64,
+    // Bounds of the sleep between two loops
+    pub min_loop_seconds: i64,
+    pub max_idle_seconds: i64,
+}
+impl Default for SchedulerSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            hot_interval_seconds: 60,
+            warm_interval_seconds: 300,
+            cold_interval_seconds: 1800,
+            hot_profit: 25.0,
+            warm_profit: 5.0,
+            min_loop_seconds: 1,
+            max_idle_seconds: 30,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AllocationMethod {
+    // Knapsack over a scaled budget, close to optimal
+    ScaledDp,
+    // Best profit per platinum first, fast on any number of items
+    Greedy,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AllocationSettings {
+    pub method: AllocationMethod,
+    // The budget is divided into this many steps, more steps are more precise but slower
+    pub budget_steps: i64,
+    // Buy orders expected to make less plat than this are never placed
+    pub min_profit: f64,
+    // Profit multiplier per item url, items not listed have priority 1
+    pub priorities: HashMap<String, f64>,
+}
+impl Default for AllocationSettings {
+    fn default() -> Self {
+        Self {
+            method: AllocationMethod::ScaledDp,
+            budget_steps: 10000,
+            min_profit: 0.0,
+            priorities: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RepricingSettings {
+    // Lower unsold sell orders and raise unfilled buy orders over time
+    pub enabled: bool,
+    // Hours an order stays at the computed price before it starts to move
+    pub grace_hours: i64,
+    // Plat a sell order is lowered per day it stays unsold
+    pub sell_decay_per_day: i64,
+    // Sell orders never go below what we paid plus this much plat
+    pub sell_floor_profit: i64,
+    // Plat a buy order is raised per day it stays unfilled
+    pub buy_increase_per_day: i64,
+    // Buy orders never go above the average closed price minus this much plat
+    pub buy_ceiling_profit: i64,
+}
+impl Default for RepricingSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            grace_hours: 24,
+            sell_decay_per_day: 2,
+            sell_floor_profit: 5,
+            buy_increase_per_day: 1,
+            buy_ceiling_profit: 10,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OutlierSettings {
+    // Exclude fake and stale orders of the live order book from pricing
+    pub enabled: bool,
+    // Orders further than this many IQRs outside the quartiles of the order book are excluded
+    pub iqr_multiplier: f64,
+    // Orders further than this many standard deviations from the price history are excluded
+    pub z_score_threshold: f64,
+    // Lower bound of the standard deviation in percent of the median price
+    pub min_std_percent: f64,
+    // Orders not updated for this many hours are excluded when they beat the median, 0 disables
+    pub stale_order_hours: i64,
+    // Users that did not answer this many of our whispers are excluded, 0 disables
+    pub unanswered_whispers: i64,
+    // Time a user has to answer a whisper
+    pub whisper_response_hours: i64,
+}
+impl Default for OutlierSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            iqr_multiplier: 1.5,
+            z_score_threshold: 3.0,
+            min_std_percent: 10.0,
+            stale_order_hours: 72,
+            unanswered_whispers: 2,
+            whisper_response_hours: 24,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrustSettings {
+    // Orders and whispers of users below this reputation are ignored
+    pub min_reputation: i64,
+    // Users at or above this reputation are trusted without further checks
+    pub trusted_reputation: i64,
+    // How far below the best trusted price an untrusted user may go before the order is ignored
+    pub max_untrusted_undercut_percent: f64,
+    // Orders not updated for this many hours are ignored, 0 keeps all of them
+    pub max_order_age_hours: i64,
+    // Users we refuse to trade with
+    pub blocklist: Vec<String>,
+}
+impl Default for TrustSettings {
+    fn default() -> Self {
+        Self {
+            min_reputation: 0,
+            trusted_reputation: 5,
+            max_untrusted_undercut_percent: 10.0,
+            max_order_age_hours: 0,
+            blocklist: vec![],
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PriceSourceKind {
+    RelicsRun,
+    WarframeMarket,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PriceSourceSettings {
+    pub primary: PriceSourceKind,
+    // Used when the primary source fails, None disables the fallback
+    pub fallback: Option<PriceSourceKind>,
+    // Also fill the items and days the primary source is missing from the fallback
+    pub merge: bool,
+    // Warframe Market needs a request per item, at most this many items are fetched from it
+    pub max_statistics_items: i64,
+}
+impl Default for PriceSourceSettings {
+    fn default() -> Self {
+        Self {
+            primary: PriceSourceKind::RelicsRun,
+            fallback: Some(PriceSourceKind::WarframeMarket),
+            merge: false,
+            max_statistics_items: 300,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LiveScraperSettings {
+    // Delete all orders that are not blacklisted when the live scraper starts or stops
+    pub delete_orders_on_start: bool,
+    pub delete_orders_on_stop: bool,
+    // Transient errors in a row before the live scraper gives up
+    pub max_retries: i64,
+    // The wait after a transient error, doubled on every retry up to the maximum
+    pub backoff_seconds: i64,
+    pub max_backoff_seconds: i64,
+}
+impl Default for LiveScraperSettings {
+    fn default() -> Self {
+        Self {
+            delete_orders_on_start: true,
+            delete_orders_on_stop: false,
+            max_retries: 5,
+            backoff_seconds: 5,
+            max_backoff_seconds: 300,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StatusSettings {
+    // Go ingame when the game is running and invisible when it closes or the live scraper stops
+    pub automate: bool,
+    // The game counts as closed when EE.log has not changed for this long
+    pub idle_minutes: i64,
+}
+impl Default for StatusSettings {
+    fn default() -> Self {
+        Self {
+            automate: false,
+            idle_minutes: 15,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebsocketSettings {
+    // Keep the order books of the live scraper up to date over the Warframe Market socket
+    pub enabled: bool,
+    pub endpoint: String,
+    // The socket does not report removed orders, so books are fetched again after this long
+    pub full_refresh_minutes: i64,
+    // Orders only seen on the socket are dropped after this long, for the same reason
+    pub pushed_order_minutes: i64,
+}
+impl Default for WebsocketSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoint: "wss://warframe.market/socket".to_string(),
+            full_refresh_minutes: 10,
+            pushed_order_minutes: 5,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScoringSettings {
+    // Process the items with the highest opportunity score first
+    pub sort_by_score: bool,
+    // Items below this score are skipped, 0 keeps all of them
+    pub min_score: f64,
+    pub margin_weight: f64,
+    pub volume_weight: f64,
+    pub volatility_weight: f64,
+    pub time_to_sell_weight: f64,
+    pub competition_weight: f64,
+}
+impl Default for ScoringSettings {
+    fn default() -> Self {
+        Self {
+            sort_by_score: true,
+            min_score: 0.0,
+            margin_weight: 0.35,
+            volume_weight: 0.2,
+            volatility_weight: 0.15,
+            time_to_sell_weight: 0.15,
+            competition_weight: 0.15,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ForecastSettings {
+    // Use the forecasted price instead of the closed average in the live scraper
+    pub use_in_strategy: bool,
+    pub horizon_days: i64,
+    // Weight of the newest price in the exponentially weighted moving average
+    pub ewma_alpha: f64,
+    pub moving_average_days: i64,
+}
+impl Default for ForecastSettings {
+    fn default() -> Self {
+        Self {
+            use_in_strategy: false,
+            horizon_days: 3,
+            ewma_alpha: 0.3,
+            moving_average_days: 7,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OrderBookSettings {
+    // Store order book snapshots of the items checked by the live scraper
+    pub enabled: bool,
+    // Minimum time between two snapshots of the same item
+    pub interval_minutes: i64,
+    // Snapshots older than this are deleted
+    pub retention_days: i64,
+}
+impl Default for OrderBookSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_minutes: 15,
+            retention_days: 90,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DucatSettings {
+    // What one ducat is worth to you in platinum
+    pub plat_per_ducat: f64,
+    // Buying mode only targets parts that give at least this many ducats per platinum
+    pub min_ducats_per_plat: f64,
+    // Buying mode only targets parts worth at least this many ducats
+    pub min_ducats: i64,
+}
+impl Default for DucatSettings {
+    fn default() -> Self {
+        Self {
+            plat_per_ducat: 0.1,
+            min_ducats_per_plat: 10.0,
+            min_ducats: 45,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ArbitrageSettings {
+    // Add the items of the best set opportunities to the live scraper and check them first.
+    // They skip the range threshold, every other filter still applies
+    pub feed_live_scraper: bool,
+    pub min_profit: i64,
+    // How many opportunities are fed to the live scraper
+    pub max_sets: i64,
+}
+impl Default for ArbitrageSettings {
+    fn default() -> Self {
+        Self {
+            feed_live_scraper: false,
+            min_profit: 15,
+            max_sets: 5,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AlertRule {
+    pub id: String,
+    pub enabled: bool,
+    // The url_name of the item, None applies the rule to every item the live scraper checks
+    #[serde(default)]
+    pub item: Option<String>,
+    // How long a rule stays quiet after firing while the condition still holds
+    pub cooldown_minutes: i64,
+    #[serde(flatten)]
+    pub condition: AlertCondition,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "condition", rename_all = "snake_case")]
+pub enum AlertCondition {
+    // Lowest ingame sell order is below the price
+    SellsBelow { price: i64 },
+    // Lowest sell order minus highest buy order exceeds the spread
+    SpreadAbove { spread: i64 },
+    // Week price shift in percent of the average price exceeds the threshold, in either direction
+    PriceShiftAbove { percent: f64 },
+    // A seller lists below the price of our own buy order
+    ListedBelowMyBuy,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotificationSettings {
+    // Minimum seconds between two notifications of the same event on the same sink
+    pub rate_limit_seconds: i64,
+    pub sinks: Vec<NotificationSinkSettings>,
+}
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        Self {
+            rate_limit_seconds: 10,
+            sinks: vec![],
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NotificationSinkSettings {
+    pub name: String,
+    pub enabled: bool,
+    // Events this sink should receive, empty means all events
+    #[serde(default)]
+    pub events: Vec<String>,
+    // Overrides the global rate limit when set
+    #[serde(default)]
+    pub rate_limit_seconds: Option<i64>,
+    #[serde(flatten)]
+    pub kind: NotificationSinkKind,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NotificationSinkKind {
+    Discord {
+        webhook: String,
+        ping: bool,
+    },
+    Webhook {
+        url: String,
+        #[serde(default)]
+        headers: HashMap<String, String>,
+    },
+    Ntfy {
+        url: String,
+        #[serde(default)]
+        token: Option<String>,
+    },
+    Gotify {
+        url: String,
+        token: String,
+    },
+    Email {
+        host: String,
+        port: u16,
+        tls: bool,
+        #[serde(default)]
+        username: Option<String>,
+        #[serde(default)]
+        password: Option<String>,
+        from: String,
+        to: String,
+    },
+    Desktop,
+}
+// Hides webhook urls, tokens, headers and passwords so settings can be logged
+impl std::fmt::Debug for NotificationSinkKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        const HIDDEN: &str = "<hidden>";
+        match self {
+            NotificationSinkKind::Discord { ping, .. } => f
+                .debug_struct("Discord")
+                .field("webhook", &HIDDEN)
+                .field("ping", ping)
+                .finish(),
+            NotificationSinkKind::Webhook { url, headers } => f
+                .debug_struct("Webhook")
+                .field("url", url)
+                .field("headers", &headers.keys().collect::<Vec<_>>())
+                .finish(),
+            NotificationSinkKind::Ntfy { url, token } => f
+                .debug_struct("Ntfy")
+                .field("url", url)
+                .field("token", &token.as_ref().map(|_| HIDDEN))
+                .finish(),
+            NotificationSinkKind::Gotify { url, .. } => f
+                .debug_struct("Gotify")
+                .field("url", url)
+                .field("token", &HIDDEN)
+                .finish(),
+            NotificationSinkKind::Email {
+                host,
+                port,
+                tls,
+                username,
+                password,
+                from,
+                to,
+            } => f
+                .debug_struct("Email")
+                .field("host", host)
+                .field("port", port)
+                .field("tls", tls)
+                .field("username", username)
+                .field("password", &password.as_ref().map(|_| HIDDEN))
+                .field("from", from)
+                .field("to", to)
+                .finish(),
+            NotificationSinkKind::Desktop => f.write_str("Desktop"),
+        }
+    }
 }
 // Allow us to run AuthState::default()
 impl Default for SettingsState {
     fn default() -> Self {
         Self {
+            version: SETTINGS_VERSION,
             volume_threshold: 15,
             range_threshold: 10,
             avg_price_cap: 600,
@@ -32,6 +562,22 @@ impl Default for SettingsState {
             strict_whitelist: false,
             ping_on_notif: false,
             webhook: "".to_string(),
+            notifications: NotificationSettings::default(),
+            alert_rules: vec![],
+            arbitrage: ArbitrageSettings::default(),
+            ducats: DucatSettings::default(),
+            order_book: OrderBookSettings::default(),
+            forecast: ForecastSettings::default(),
+            scoring: ScoringSettings::default(),
+            websocket: WebsocketSettings::default(),
+            status: StatusSettings::default(),
+            live_scraper: LiveScraperSettings::default(),
+            price_source: PriceSourceSettings::default(),
+            trust: TrustSettings::default(),
+            outliers: OutlierSettings::default(),
+            repricing: RepricingSettings::default(),
+            allocation: AllocationSettings::default(),
+            scheduler: SchedulerSettings::default(),
         }
     }
 }
@@ -44,12 +590,21 @@ impl SettingsState {
     pub fn setup() -> io::Result<Self> {
         let path_ref = Self::get_file_path();
         if path_ref.exists() {
-            Self::read_from_file()
-        } else {
-            let default_settings = SettingsState::default();
-            default_settings.save_to_file()?;
-            Ok(default_settings)
+            match Self::read_from_file() {
+                Ok(settings) => return Ok(settings),
+                Err(e) => {
+                    // Keep the broken file around so the user can recover it
+                    logger::error_con(
+                        "Settings",
+                        format!("Could not read settings, using the defaults: {:?}", e).as_str(),
+                    );
+                    std::fs::rename(&path_ref, path_ref.with_extension("invalid.json"))?;
+                }
+            }
         }
+        let default_settings = SettingsState::default();
+        default_settings.save_to_file()?;
+        Ok(default_settings)
     }
 
     pub fn save_to_file(&self) -> io::Result<()> {
@@ -63,7 +618,458 @@ impl SettingsState {
         let mut file = File::open(Self::get_file_path())?;
         let mut content = String::new();
         file.read_to_string(&mut content)?;
-        let settings = serde_json::from_str(&content)?;
+        let value: Value = serde_json::from_str(&content)?;
+        let version = value.get("version").and_then(Value::as_i64).unwrap_or(0);
+        let mut settings: SettingsState = serde_json::from_value(migrate(value))?;
+        // Hand edited or older files can hold values the app can not work with
+        let errors = settings.validate();
+        for error in errors.iter() {
+            logger::warning_con(
+                "Settings",
+                format!(
+                    "Field: {}, Error: {}, using the default",
+                    error.field, error.message
+                )
+                .as_str(),
+            );
+        }
+        if !errors.is_empty() {
+            settings = settings.reset_fields(&errors)?;
+        }
+        if version < SETTINGS_VERSION || !errors.is_empty() {
+            settings.save_to_file()?;
+        }
         Ok(settings)
     }
+
+    /// Replaces the fields with their defaults. Fields the defaults do not have, like a
+    /// notification sink, are kept as they are.
+    fn reset_fields(&self, errors: &[SettingsFieldError]) -> io::Result<Self> {
+        let mut value = serde_json::to_value(self)?;
+        let defaults = serde_json::to_value(SettingsState::default())?;
+        for error in errors {
+            // "notifications.sinks[0].url" becomes "/notifications/sinks/0/url"
+            let pointer = format!(
+                "/{}",
+                error
+                    .field
+                    .replace('.', "/")
+                    .replace('[', "/")
+                    .replace(']', "")
+            );
+            if let (Some(default), Some(field)) =
+                (defaults.pointer(&pointer), value.pointer_mut(&pointer))
+            {
+                *field = default.clone();
+            }
+        }
+        Ok(serde_json::from_value(value)?)
+    }
+
+    /// Checks the values the app can not work with, an empty list means the settings are valid.
+    pub fn validate(&self) -> Vec<SettingsFieldError> {
+        let mut errors = vec![];
+        let mut check = |valid: bool, field: &str, message: &str| {
+            if !valid {
+                errors.push(SettingsFieldError {
+                    field: field.to_string(),
+                    message: message.to_string(),
+                });
+            }
+        };
+        check(
+            self.volume_threshold >= 0,
+            "volume_threshold",
+            "Must not be negative",
+        );
+        check(
+            self.range_threshold >= 0,
+            "range_threshold",
+            "Must not be negative",
+        );
+        check(
+            self.avg_price_cap > 0,
+            "avg_price_cap",
+            "Must be greater than 0",
+        );
+        check(
+            self.max_total_price_cap > 0,
+            "max_total_price_cap",
+            "Must be greater than 0",
+        );
+        check(
+            self.webhook.is_empty() || is_valid_url(&self.webhook, &["https"]),
+            "webhook",
+            "Must be an https URL",
+        );
+
+        check(
+            self.notifications.rate_limit_seconds >= 0,
+            "notifications.rate_limit_seconds",
+            "Must not be negative",
+        );
+        for (index, sink) in self.notifications.sinks.iter().enumerate() {
+            let field = |name: &str| format!("notifications.sinks[{}].{}", index, name);
+            match &sink.kind {
+                NotificationSinkKind::Discord { webhook, .. } => check(
+                    is_valid_url(webhook, &["https"]),
+                    &field("webhook"),
+                    "Must be an https URL",
+                ),
+                NotificationSinkKind::Webhook { url, .. }
+                | NotificationSinkKind::Ntfy { url, .. }
+                | NotificationSinkKind::Gotify { url, .. } => check(
+                    is_valid_url(url, &["http", "https"]),
+                    &field("url"),
+                    "Must be an http or https URL",
+                ),
+                NotificationSinkKind::Email { host, from, to, .. } => {
+                    check(!host.is_empty(), &field("host"), "Must not be empty");
+                    check(
+                        from.contains('@'),
+                        &field("from"),
+                        "Must be an email address",
+                    );
+                    check(to.contains('@'), &field("to"), "Must be an email address");
+                }
+                NotificationSinkKind::Desktop => {}
+            }
+        }
+
+        check(
+            is_valid_url(&self.websocket.endpoint, &["ws", "wss"]),
+            "websocket.endpoint",
+            "Must be a ws or wss URL",
+        );
+        check(
+            self.websocket.full_refresh_minutes >= 0,
+            "websocket.full_refresh_minutes",
+            "Must not be negative",
+        );
+        check(
+            self.websocket.pushed_order_minutes >= 0,
+            "websocket.pushed_order_minutes",
+            "Must not be negative",
+        );
+        check(
+            self.status.idle_minutes >= 1,
+            "status.idle_minutes",
+            "Must be at least 1",
+        );
+        check(
+            self.live_scraper.max_retries >= 0,
+            "live_scraper.max_retries",
+            "Must not be negative",
+        );
+        check(
+            self.live_scraper.backoff_seconds >= 1,
+            "live_scraper.backoff_seconds",
+            "Must be at least 1",
+        );
+        check(
+            self.live_scraper.max_backoff_seconds >= self.live_scraper.backoff_seconds,
+            "live_scraper.max_backoff_seconds",
+            "Must not be below the backoff",
+        );
+        check(
+            self.price_source.fallback.as_ref() != Some(&self.price_source.primary),
+            "price_source.fallback",
+            "Must differ from the primary source",
+        );
+        check(
+            (0.0..=100.0).contains(&self.trust.max_untrusted_undercut_percent),
+            "trust.max_untrusted_undercut_percent",
+            "Must be between 0 and 100",
+        );
+        check(
+            self.outliers.iqr_multiplier > 0.0,
+            "outliers.iqr_multiplier",
+            "Must be greater than 0",
+        );
+        check(
+            self.outliers.z_score_threshold > 0.0,
+            "outliers.z_score_threshold",
+            "Must be greater than 0",
+        );
+        check(
+            self.ducats.min_ducats_per_plat > 0.0,
+            "ducats.min_ducats_per_plat",
+            "Must be greater than 0",
+        );
+        check(
+            self.forecast.ewma_alpha > 0.0 && self.forecast.ewma_alpha <= 1.0,
+            "forecast.ewma_alpha",
+            "Must be greater than 0 and at most 1",
+        );
+        let weights = [
+            ("scoring.margin_weight", self.scoring.margin_weight),
+            ("scoring.volume_weight", self.scoring.volume_weight),
+            ("scoring.volatility_weight", self.scoring.volatility_weight),
+            (
+                "scoring.time_to_sell_weight",
+                self.scoring.time_to_sell_weight,
+            ),
+            (
+                "scoring.competition_weight",
+                self.scoring.competition_weight,
+            ),
+        ];
+        for (field, weight) in weights {
+            check(weight >= 0.0, field, "Must not be negative");
+        }
+        check(
+            weights.iter().any(|(_, weight)| *weight > 0.0),
+            "scoring",
+            "At least one weight must be greater than 0",
+        );
+        check(
+            self.order_book.interval_minutes >= 1,
+            "order_book.interval_minutes",
+            "Must be at least 1",
+        );
+        check(
+            self.repricing.grace_hours >= 0,
+            "repricing.grace_hours",
+            "Must not be negative",
+        );
+        check(
+            self.repricing.sell_decay_per_day >= 0,
+            "repricing.sell_decay_per_day",
+            "Must not be negative",
+        );
+        check(
+            self.repricing.buy_increase_per_day >= 0,
+            "repricing.buy_increase_per_day",
+            "Must not be negative",
+        );
+        check(
+            (1..=1_000_000).contains(&self.allocation.budget_steps),
+            "allocation.budget_steps",
+            "Must be between 1 and 1000000",
+        );
+        check(
+            self.allocation
+                .priorities
+                .values()
+                .all(|priority| *priority >= 0.0),
+            "allocation.priorities",
+            "Must not be negative",
+        );
+        check(
+            self.scheduler.min_loop_seconds >= 1,
+            "scheduler.min_loop_seconds",
+            "Must be at least 1",
+        );
+        check(
+            self.scheduler.max_idle_seconds >= self.scheduler.min_loop_seconds,
+            "scheduler.max_idle_seconds",
+            "Must not be below the minimum loop time",
+        );
+        check(
+            self.scheduler.hot_interval_seconds >= 0,
+            "scheduler.hot_interval_seconds",
+            "Must not be negative",
+        );
+        check(
+            self.scheduler.warm_interval_seconds >= 0,
+            "scheduler.warm_interval_seconds",
+            "Must not be negative",
+        );
+        check(
+            self.scheduler.cold_interval_seconds >= 0,
+            "scheduler.cold_interval_seconds",
+            "Must not be negative",
+        );
+        errors
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SettingsFieldError {
+    // Path of the field, like "live_scraper.max_retries"
+    pub field: String,
+    pub message: String,
+}
+
+/// Upgrades settings written by an older version to the current one. Version 0 files predate
+/// versioning, everything they lack is filled from the defaults when they are deserialized.
+pub fn migrate(mut value: Value) -> Value {
+    if let Some(settings) = value.as_object_mut() {
+        settings.insert("version".to_string(), Value::from(SETTINGS_VERSION));
+    }
+    value
+}
+
+fn is_valid_url(url: &str, schemes: &[&str]) -> bool {
+    match reqwest::Url::parse(url) {
+        Ok(url) => schemes.contains(&url.scheme()) && url.host_str().is_some(),
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn error_fields(settings: &SettingsState) -> Vec<String> {
+        settings
+            .validate()
+            .into_iter()
+            .map(|error| error.field)
+            .collect()
+    }
+
+    #[test]
+    fn defaults_are_valid() {
+        assert!(SettingsState::default().validate().is_empty());
+    }
+
+    #[test]
+    fn min_ducats_per_plat_must_be_positive() {
+        let mut settings = SettingsState::default();
+        settings.ducats.min_ducats_per_plat = 0.0;
+        assert_eq!(error_fields(&settings), vec!["ducats.min_ducats_per_plat"]);
+    }
+
+    #[test]
+    fn ewma_alpha_range() {
+        let mut settings = SettingsState::default();
+        for alpha in [0.0, -0.5, 1.1] {
+            settings.forecast.ewma_alpha = alpha;
+            assert_eq!(
+                error_fields(&settings),
+                vec!["forecast.ewma_alpha"],
+                "{}",
+                alpha
+            );
+        }
+        for alpha in [0.01, 1.0] {
+            settings.forecast.ewma_alpha = alpha;
+            assert!(settings.validate().is_empty(), "{}", alpha);
+        }
+    }
+
+    #[test]
+    fn scoring_weights() {
+        let mut settings = SettingsState::default();
+        settings.scoring.volume_weight = -0.1;
+        assert_eq!(error_fields(&settings), vec!["scoring.volume_weight"]);
+
+        settings.scoring = ScoringSettings {
+            margin_weight: 0.0,
+            volume_weight: 0.0,
+            volatility_weight: 0.0,
+            time_to_sell_weight: 0.0,
+            competition_weight: 0.0,
+            ..ScoringSettings::default()
+        };
+        assert_eq!(error_fields(&settings), vec!["scoring"]);
+
+        settings.scoring.competition_weight = 1.0;
+        assert!(settings.validate().is_empty());
+    }
+
+    #[test]
+    fn order_book_interval_at_least_a_minute() {
+        let mut settings = SettingsState::default();
+        settings.order_book.interval_minutes = 0;
+        assert_eq!(error_fields(&settings), vec!["order_book.interval_minutes"]);
+    }
+
+    #[test]
+    fn scheduler_intervals_are_checked_separately() {
+        let mut settings = SettingsState::default();
+        settings.scheduler.warm_interval_seconds = -1;
+        settings.scheduler.cold_interval_seconds = -1;
+        assert_eq!(
+            error_fields(&settings),
+            vec![
+                "scheduler.warm_interval_seconds",
+                "scheduler.cold_interval_seconds"
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_fields_are_reset_to_the_default() {
+        let mut settings = SettingsState::default();
+        settings.forecast.ewma_alpha = 2.0;
+        settings.forecast.horizon_days = 9;
+        settings.order_book.interval_minutes = 0;
+        settings.scoring = ScoringSettings {
+            margin_weight: 0.0,
+            volume_weight: 0.0,
+            volatility_weight: 0.0,
+            time_to_sell_weight: 0.0,
+            competition_weight: 0.0,
+            min_score: 4.0,
+            ..ScoringSettings::default()
+        };
+        let reset = settings.reset_fields(&settings.validate()).unwrap();
+        assert!(reset.validate().is_empty());
+        assert_eq!(
+            reset.forecast.ewma_alpha,
+            ForecastSettings::default().ewma_alpha
+        );
+        // Valid fields next to the invalid ones are kept
+        assert_eq!(reset.forecast.horizon_days, 9);
+        assert_eq!(
+            reset.order_book.interval_minutes,
+            OrderBookSettings::default().interval_minutes
+        );
+        // The weights are checked together, the whole section is reset
+        assert_eq!(reset.scoring.min_score, 0.0);
+    }
+
+    #[test]
+    fn fields_without_a_default_are_kept() {
+        let mut settings = SettingsState::default();
+        settings.notifications.rate_limit_seconds = -1;
+        let errors = vec![
+            SettingsFieldError {
+                field: "notifications.sinks[0].url".to_string(),
+                message: String::new(),
+            },
+            SettingsFieldError {
+                field: "notifications.rate_limit_seconds".to_string(),
+                message: String::new(),
+            },
+        ];
+        let reset = settings.reset_fields(&errors).unwrap();
+        assert_eq!(
+            reset.notifications.rate_limit_seconds,
+            NotificationSettings::default().rate_limit_seconds
+        );
+    }
+
+    #[test]
+    fn migrate_sets_the_version() {
+        let value = migrate(json!({ "volume_threshold": 20 }));
+        assert_eq!(value["version"], json!(SETTINGS_VERSION));
+        assert_eq!(value["volume_threshold"], json!(20));
+    }
+
+    #[test]
+    fn unversioned_files_load_with_defaults() {
+        let value = migrate(json!({
+            "volume_threshold": 20,
+            "blacklist": ["ash_prime_set"],
+            "forecast": { "horizon_days": 5 }
+        }));
+        let settings: SettingsState = serde_json::from_value(value).unwrap();
+        assert_eq!(settings.version, SETTINGS_VERSION);
+        assert_eq!(settings.volume_threshold, 20);
+        assert_eq!(settings.blacklist, vec!["ash_prime_set".to_string()]);
+        assert_eq!(settings.forecast.horizon_days, 5);
+        assert_eq!(
+            settings.forecast.ewma_alpha,
+            ForecastSettings::default().ewma_alpha
+        );
+        assert_eq!(
+            settings.range_threshold,
+            SettingsState::default().range_threshold
+        );
+        assert!(settings.validate().is_empty());
+    }
 }
@@ -0,0 +1,469 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+use crate::{
+    cache::CacheState,
+    helper,
+    item_search::ItemSearchFilter,
+    logger,
+    settings::{self, SettingsFieldError, SettingsState},
+    structs::GlobleError,
+};
+
+// Personal fields that are never exported, imported or stored in a preset
+const PERSONAL_FIELDS: [&str; 3] = ["version", "webhook", "notifications"];
+// Objects keyed by item instead of by setting name, their keys are not checked
+const MAP_FIELDS: [&str; 1] = ["allocation.priorities"];
+// Names of the built in presets, reserved even when a preset is not available
+const BUILTIN_NAMES: [&str; 3] = ["conservative", "aggressive", "arcane_only"];
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SettingsPreset {
+    pub name: String,
+    pub description: String,
+    // Built in presets can not be overwritten or deleted
+    #[serde(default)]
+    pub builtin: bool,
+    // The settings the preset changes, anything missing keeps its current value
+    pub settings: Value,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SettingsChange {
+    pub field: String,
+    pub old: Value,
+    pub new: Value,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SettingsPreview {
+    // Pass these to update_settings to apply the preview
+    pub settings: SettingsState,
+    pub changes: Vec<SettingsChange>,
+    pub errors: Vec<SettingsFieldError>,
+}
+
+fn get_file_path() -> PathBuf {
+    helper::get_app_roaming_path().join("settings_presets.json")
+}
+
+fn read_user_presets() -> Result<Vec<SettingsPreset>, GlobleError> {
+    let path = get_file_path();
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+    let mut content = String::new();
+    File::open(path)?.read_to_string(&mut content)?;
+    Ok(serde_json::from_str(&content)?)
+}
+
+fn write_user_presets(presets: &[SettingsPreset]) -> Result<(), GlobleError> {
+    let json = serde_json::to_string_pretty(presets)?;
+    // Write next to the file and rename it, so a crash never loses the saved presets
+    let path = get_file_path();
+    let temp_path = path.with_extension("json.tmp");
+    let mut file = File::create(&temp_path)?;
+    file.write_all(json.as_bytes())?;
+    file.sync_all()?;
+    drop(file);
+    fs::rename(temp_path, path)?;
+    Ok(())
+}
+
+fn get_arcanes(cache: &CacheState) -> Result<Vec<String>, GlobleError> {
+    Ok(cache
+        .search_items(
+            "",
+            &ItemSearchFilter {
+                tags: vec!["arcane_enhancement".to_string()],
+                ..Default::default()
+            },
+            usize::MAX,
+        )?
+        .into_iter()
+        .map(|result| result.item.url_name)
+        .collect())
+}
+
+/// The built in presets. Presets that depend on the item cache are left out when the cache can
+/// not provide their items, an empty strict whitelist would stop all trading.
+fn get_builtin_presets(cache: &CacheState) -> Vec<SettingsPreset> {
+    let preset = |name: &str, description: &str, settings: Value| SettingsPreset {
+        name: name.to_string(),
+        description: description.to_string(),
+        builtin: true,
+        settings,
+    };
+    let mut presets = vec![
+        preset(
+            "conservative",
+            "Cheap, liquid items from trusted traders with a safe margin",
+            json!({
+                "volume_threshold": 25,
+                "range_threshold": 15,
+                "avg_price_cap": 200,
+                "max_total_price_cap": 2000,
+                "trust": { "min_reputation": 5 },
+                "allocation": { "min_profit": 10.0 },
+                "repricing": { "enabled": false },
+            }),
+        ),
+        preset(
+            "aggressive",
+            "More items, higher caps and faster repricing for thinner margins",
+            json!({
+                "volume_threshold": 8,
+                "range_threshold": 6,
+                "avg_price_cap": 1000,
+                "max_total_price_cap": 10000,
+                "allocation": { "min_profit": 2.0 },
+                "repricing": {
+                    "enabled": true,
+                    "sell_decay_per_day": 3,
+                    "buy_increase_per_day": 2,
+                },
+            }),
+        ),
+    ];
+    match get_arcanes(cache) {
+        Ok(arcanes) if !arcanes.is_empty() => presets.push(preset(
+            "arcane_only",
+            "Only trade arcane enhancements",
+            json!({
+                "strict_whitelist": true,
+                "whitelist": arcanes,
+            }),
+        )),
+        Ok(_) => logger::info(
+            "SettingsPresets",
+            "No arcanes in the item cache, the arcane_only preset is not available",
+            true,
+            None,
+        ),
+        Err(e) => logger::error(
+            "SettingsPresets",
+            format!("Could not get the arcanes for arcane_only: {:?}", e).as_str(),
+            true,
+            None,
+        ),
+    }
+    presets
+}
+
+pub fn get_presets(cache: &CacheState) -> Result<Vec<SettingsPreset>, GlobleError> {
+    let mut presets = get_builtin_presets(cache);
+    presets.extend(read_user_presets()?);
+    Ok(presets)
+}
+
+pub fn get_preset(cache: &CacheState, name: &str) -> Result<SettingsPreset, GlobleError> {
+    get_presets(cache)?
+        .into_iter()
+        .find(|preset| preset.name == name)
+        .ok_or_else(|| {
+            if BUILTIN_NAMES.contains(&name) {
+                GlobleError::OtherError(format!("Preset {} is not available right now", name))
+            } else {
+                GlobleError::OtherError(format!("Preset {} does not exist", name))
+            }
+        })
+}
+
+/// Stores the current settings, without the personal fields, under the name. A preset with the
+/// same name is replaced.
+pub fn save_preset(
+    name: &str,
+    description: &str,
+    settings: &SettingsState,
+) -> Result<SettingsPreset, GlobleError> {
+    if name.trim().is_empty() {
+        return Err(GlobleError::OtherError("A preset needs a name".to_string()));
+    }
+    if BUILTIN_NAMES.contains(&name) {
+        return Err(GlobleError::OtherError(format!(
+            "Preset {} is built in and can not be overwritten",
+            name
+        )));
+    }
+    let preset = SettingsPreset {
+        name: name.to_string(),
+        description: description.to_string(),
+        builtin: false,
+        settings: get_shareable(settings)?,
+    };
+    let mut presets = read_user_presets()?;
+    presets.retain(|existing| existing.name != name);
+    presets.push(preset.clone());
+    write_user_presets(&presets)?;
+    Ok(preset)
+}
+
+pub fn delete_preset(name: &str) -> Result<(), GlobleError> {
+    let mut presets = read_user_presets()?;
+    let count = presets.len();
+    presets.retain(|preset| preset.name != name);
+    if presets.len() == count {
+        return Err(GlobleError::OtherError(format!(
+            "Preset {} does not exist or is built in",
+            name
+        )));
+    }
+    write_user_presets(&presets)
+}
+
+/// The settings without the personal fields, as they are exported and stored in presets.
+fn get_shareable(settings: &SettingsState) -> Result<Value, GlobleError> {
+    let mut value = serde_json::to_value(settings)?;
+    if let Some(object) = value.as_object_mut() {
+        for field in PERSONAL_FIELDS {
+            object.remove(field);
+        }
+    }
+    Ok(value)
+}
+
+pub fn export(settings: &SettingsState) -> Result<String, GlobleError> {
+    let mut value = get_shareable(settings)?;
+    // Lets an import migrate settings exported by an older version
+    value["version"] = json!(settings::SETTINGS_VERSION);
+    Ok(serde_json::to_string_pretty(&value)?)
+}
+
+/// Applies `overlay` on top of the current settings without saving them. Returns the resulting
+/// settings, what changed and what is invalid, so the user can review it before applying.
+pub fn preview(current: &SettingsState, overlay: Value) -> Result<SettingsPreview, GlobleError> {
+    let mut overlay = settings::migrate(overlay);
+    let overlay_object = overlay
+        .as_object_mut()
+        .ok_or_else(|| GlobleError::OtherError("The settings must be a JSON object".to_string()))?;
+    for field in PERSONAL_FIELDS {
+        overlay_object.remove(field);
+    }
+
+    let old = serde_json::to_value(current)?;
+    let mut errors = vec![];
+    find_unknown_fields("", &overlay, &old, &mut errors);
+    let mut merged = old.clone();
+    merge(&mut merged, overlay);
+
+    let settings: SettingsState = serde_json::from_value(merged)?;
+    errors.extend(settings.validate());
+    // Compare against the deserialized settings, so defaults filled in by serde show up too
+    let mut changes = vec![];
+    diff("", &old, &serde_json::to_value(&settings)?, &mut changes);
+    Ok(SettingsPreview {
+        settings,
+        changes,
+        errors,
+    })
+}
+
+fn join_path(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+fn find_unknown_fields(
+    path: &str,
+    overlay: &Value,
+    schema: &Value,
+    errors: &mut Vec<SettingsFieldError>,
+) {
+    let (overlay, schema) = match (overlay.as_object(), schema.as_object()) {
+        (Some(overlay), Some(schema)) if !MAP_FIELDS.contains(&path) => (overlay, schema),
+        _ => return,
+    };
+    for (key, value) in overlay {
+        let field = join_path(path, key);
+        match schema.get(key) {
+            Some(schema_value) => find_unknown_fields(&field, value, schema_value, errors),
+            None => errors.push(SettingsFieldError {
+                field,
+                message: "Unknown setting".to_string(),
+            }),
+        }
+    }
+}
+
+/// Recursively copies the overlay into the target, objects are merged and anything else is
+/// replaced.
+fn merge(target: &mut Value, overlay: Value) {
+    match (target, overlay) {
+        (Value::Object(target), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match target.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        target.insert(key, value);
+                    }
+                }
+            }
+        }
+        (target, overlay) => *target = overlay,
+    }
+}
+
+/// Lists the changed fields, lists like the blacklist are compared as a whole.
+fn diff(path: &str, old: &Value, new: &Value, changes: &mut Vec<SettingsChange>) {
+    if let (Some(old_object), Some(new_object)) = (old.as_object(), new.as_object()) {
+        let mut keys: Vec<&String> = old_object.keys().chain(new_object.keys()).collect();
+        keys.sort();
+        keys.dedup();
+        for key in keys {
+            diff(
+                &join_path(path, key),
+                old_object.get(key).unwrap_or(&Value::Null),
+                new_object.get(key).unwrap_or(&Value::Null),
+                changes,
+            );
+        }
+    } else if old != new {
+        changes.push(SettingsChange {
+            field: path.to_string(),
+            old: old.clone(),
+            new: new.clone(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(changes: &[SettingsChange]) -> Vec<String> {
+        changes.iter().map(|change| change.field.clone()).collect()
+    }
+
+    #[test]
+    fn merge_objects_and_replace_values() {
+        let mut target = json!({
+            "volume_threshold": 10,
+            "trust": { "min_reputation": 0, "trusted_reputation": 5 },
+            "blacklist": ["a", "b"],
+        });
+        merge(
+            &mut target,
+            json!({
+                "trust": { "min_reputation": 3 },
+                "blacklist": ["c"],
+                "new_field": true,
+            }),
+        );
+        assert_eq!(
+            target,
+            json!({
+                "volume_threshold": 10,
+                "trust": { "min_reputation": 3, "trusted_reputation": 5 },
+                "blacklist": ["c"],
+                "new_field": true,
+            })
+        );
+    }
+
+    #[test]
+    fn diff_lists_changed_leaves() {
+        let old = json!({
+            "volume_threshold": 10,
+            "trust": { "min_reputation": 0, "trusted_reputation": 5 },
+            "blacklist": ["a"],
+        });
+        let new = json!({
+            "volume_threshold": 10,
+            "trust": { "min_reputation": 3, "trusted_reputation": 5 },
+            "blacklist": ["a", "b"],
+            "whitelist": [],
+        });
+        let mut changes = vec![];
+        diff("", &old, &new, &mut changes);
+        assert_eq!(
+            fields(&changes),
+            vec!["blacklist", "trust.min_reputation", "whitelist"]
+        );
+        assert_eq!(changes[1].old, json!(0));
+        assert_eq!(changes[1].new, json!(3));
+        assert_eq!(changes[2].old, Value::Null);
+    }
+
+    #[test]
+    fn unknown_fields_are_reported_with_their_path() {
+        let schema = serde_json::to_value(SettingsState::default()).unwrap();
+        let overlay = json!({
+            "volume_threshold": 10,
+            "volume_treshold": 10,
+            "trust": { "min_reputation": 3, "min_rep": 3 },
+            "allocation": { "priorities": { "ash_prime_set": 2.0 } },
+        });
+        let mut errors = vec![];
+        find_unknown_fields("", &overlay, &schema, &mut errors);
+        let mut fields: Vec<String> = errors.into_iter().map(|error| error.field).collect();
+        fields.sort();
+        // Item keyed maps are not checked
+        assert_eq!(fields, vec!["trust.min_rep", "volume_treshold"]);
+    }
+
+    #[test]
+    fn preview_applies_the_overlay_without_personal_fields() {
+        let mut current = SettingsState::default();
+        current.webhook = "https://discord.com/api/webhooks/1/secret".to_string();
+        let preview = preview(
+            &current,
+            json!({
+                "volume_threshold": 42,
+                "trust": { "min_reputation": 7 },
+                "webhook": "https://example.com/hook",
+            }),
+        )
+        .unwrap();
+        assert_eq!(preview.settings.volume_threshold, 42);
+        assert_eq!(preview.settings.trust.min_reputation, 7);
+        // Untouched fields keep their current value
+        assert_eq!(
+            preview.settings.trust.trusted_reputation,
+            current.trust.trusted_reputation
+        );
+        assert_eq!(preview.settings.webhook, current.webhook);
+        assert_eq!(
+            fields(&preview.changes),
+            vec!["trust.min_reputation", "volume_threshold"]
+        );
+        assert!(preview.errors.is_empty());
+    }
+
+    #[test]
+    fn preview_reports_unknown_and_invalid_fields() {
+        let preview = preview(
+            &SettingsState::default(),
+            json!({
+                "avg_price_cap": 0,
+                "not_a_setting": 1,
+            }),
+        )
+        .unwrap();
+        let fields: Vec<String> = preview
+            .errors
+            .into_iter()
+            .map(|error| error.field)
+            .collect();
+        assert_eq!(fields, vec!["not_a_setting", "avg_price_cap"]);
+    }
+
+    #[test]
+    fn preview_rejects_non_objects() {
+        assert!(preview(&SettingsState::default(), json!([1, 2])).is_err());
+    }
+
+    #[test]
+    fn shareable_settings_leave_out_personal_fields() {
+        let value = get_shareable(&SettingsState::default()).unwrap();
+        for field in PERSONAL_FIELDS {
+            assert!(value.get(field).is_none(), "{}", field);
+        }
+        assert!(value.get("volume_threshold").is_some());
+    }
+}
@@ -0,0 +1,70 @@
+use std::sync::{Arc, Mutex};
+
+use crate::{logger, settings::SettingsState, structs::GlobleError, websocket::WFMSocketState};
+
+const STATUSES: [&str; 3] = ["online", "ingame", "invisible"];
+
+#[derive(Clone, Debug)]
+pub struct StatusState {
+    log_file: String,
+    settings: Arc<Mutex<SettingsState>>,
+    socket: Arc<Mutex<WFMSocketState>>,
+}
+
+impl StatusState {
+    pub fn new(settings: Arc<Mutex<SettingsState>>, socket: Arc<Mutex<WFMSocketState>>) -> Self {
+        StatusState {
+            log_file: "status.log".to_string(),
+            settings,
+            socket,
+        }
+    }
+
+    /// Sets our Warframe Market status, the status is only available over the socket so it is
+    /// started when needed.
+    pub fn set_status(&self, status: &str, reason: &str) -> Result<(), GlobleError> {
+        if !STATUSES.contains(&status) {
+            return Err(GlobleError::OtherError(format!(
+                "Unknown status: {}",
+                status
+            )));
+        }
+        let socket = self.socket.lock()?.clone();
+        socket.start();
+        socket.set_status(status)?;
+        logger::info(
+            "Status",
+            format!("Status: {}, Reason: {}", status, reason).as_str(),
+            true,
+            Some(self.log_file.as_str()),
+        );
+        Ok(())
+    }
+
+    fn is_automated(&self) -> Result<bool, GlobleError> {
+        Ok(self.settings.lock()?.status.automate)
+    }
+
+    /// Called by the EE.log watcher when the game starts writing to the log.
+    pub fn on_game_started(&self) -> Result<(), GlobleError> {
+        if self.is_automated()? {
+            self.set_status("ingame", "game started")?;
+        }
+        Ok(())
+    }
+
+    /// Called by the EE.log watcher when the game shuts down or stops writing to the log.
+    pub fn on_game_closed(&self) -> Result<(), GlobleError> {
+        if self.is_automated()? {
+            self.set_status("invisible", "game closed")?;
+        }
+        Ok(())
+    }
+
+    pub fn on_live_scraper_stopped(&self) -> Result<(), GlobleError> {
+        if self.is_automated()? {
+            self.set_status("invisible", "live scraper stopped")?;
+        }
+        Ok(())
+    }
+}
@@ -91,6 +91,10 @@ pub struct Item {
     pub tags: Option<Vec<String>>,
     pub mod_max_rank: Option<i64>,
     pub subtypes: Option<Vec<String>>,
+    #[serde(default)]
+    pub quantity_for_set: Option<i64>,
+    #[serde(default)]
+    pub ducats: Option<i64>,
 }
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct ItemDetails {
@@ -132,6 +136,9 @@ pub struct Order {
     #[serde(rename = "quantity")]
     pub quantity: i64,
 
+    #[serde(rename = "mod_rank", default)]
+    pub mod_rank: Option<i64>,
+
     #[serde(rename = "item")]
     pub item: OrderItem,
 }
@@ -196,6 +203,8 @@ pub struct Invantory {
     pub price: f64,
     pub listed_price: Option<i64>,
     pub owned: i64,
+    // When the current sell order was posted, reset when a unit sells
+    pub listed_at: Option<String>,
 }
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Transaction {
@@ -210,11 +219,48 @@ pub struct Transaction {
     pub transaction_type: String,
     pub quantity: i64,
 }
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct OrderBookSnapshot {
+    pub id: i64,
+    pub item_url: String,
+    pub mod_rank: Option<i64>,
+    pub datetime: String,
+    pub best_bid: Option<i64>,
+    pub best_ask: Option<i64>,
+    pub spread: Option<i64>,
+    // Quantity listed within 5, 10 and 20 percent of the best price
+    pub sell_depth_5: i64,
+    pub sell_depth_10: i64,
+    pub sell_depth_20: i64,
+    pub buy_depth_5: i64,
+    pub buy_depth_10: i64,
+    pub buy_depth_20: i64,
+    pub sell_orders: i64,
+    pub buy_orders: i64,
+    // Users that are ingame or on the website
+    pub online_sellers: i64,
+    pub online_buyers: i64,
+    // Sellers that are ingame and can trade right away
+    pub ingame_sellers: i64,
+    // Orders that appeared and disappeared since the previous snapshot of the item
+    pub new_orders: i64,
+    pub removed_orders: i64,
+}
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ItemSchedule {
+    pub item_url: String,
+    // When the live scraper should check the item again
+    pub next_check: String,
+    pub interval_seconds: i64,
+    // Best competitor prices at the last check, used to notice changes in the order book
+    pub best_buy: Option<i64>,
+    pub best_sell: Option<i64>,
+}
 
 /// Generated by https://quicktype.io
 extern crate serde_json;
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct OrderByItem {
     #[serde(rename = "order_type")]
     pub order_type: String,
@@ -249,7 +295,7 @@ pub struct OrderByItem {
     pub region: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct User {
     #[serde(rename = "reputation")]
     pub reputation: i64,
@@ -272,3 +318,402 @@ pub struct User {
     #[serde(rename = "status")]
     pub status: String,
 }
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Auction {
+    #[serde(rename = "id")]
+    pub id: String,
+
+    #[serde(rename = "owner")]
+    pub owner: User,
+
+    #[serde(rename = "starting_price")]
+    pub starting_price: i64,
+
+    #[serde(rename = "buyout_price")]
+    pub buyout_price: Option<i64>,
+
+    #[serde(rename = "top_bid")]
+    pub top_bid: Option<i64>,
+
+    #[serde(rename = "is_direct_sell")]
+    pub is_direct_sell: bool,
+
+    #[serde(rename = "visible")]
+    pub visible: bool,
+
+    #[serde(rename = "private")]
+    pub private: bool,
+
+    #[serde(rename = "closed")]
+    pub closed: bool,
+
+    #[serde(rename = "note")]
+    pub note: String,
+
+    #[serde(rename = "platform")]
+    pub platform: String,
+
+    #[serde(rename = "created")]
+    pub created: String,
+
+    #[serde(rename = "updated")]
+    pub updated: String,
+
+    #[serde(rename = "item")]
+    pub item: RivenItem,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RivenItem {
+    // Lich and kubrow auctions share the endpoints, their items only have the type
+    #[serde(rename = "type")]
+    pub item_type: String,
+
+    #[serde(rename = "weapon_url_name", default)]
+    pub weapon_url_name: String,
+
+    #[serde(rename = "name", default)]
+    pub name: String,
+
+    #[serde(rename = "mod_rank", default)]
+    pub mod_rank: i64,
+
+    #[serde(rename = "re_rolls", default)]
+    pub re_rolls: i64,
+
+    #[serde(rename = "mastery_level", default)]
+    pub mastery_level: i64,
+
+    #[serde(rename = "polarity", default)]
+    pub polarity: String,
+
+    #[serde(rename = "attributes", default)]
+    pub attributes: Vec<RivenAttribute>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RivenAttribute {
+    #[serde(rename = "url_name")]
+    pub url_name: String,
+
+    #[serde(rename = "positive")]
+    pub positive: bool,
+
+    #[serde(rename = "value")]
+    pub value: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ItemStatistics {
+    #[serde(rename = "statistics_closed")]
+    pub closed: StatisticsPeriods,
+
+    #[serde(rename = "statistics_live")]
+    pub live: StatisticsPeriods,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct StatisticsPeriods {
+    // One entry per hour
+    #[serde(rename = "48hours", default)]
+    pub hours_48: Vec<ItemStatistic>,
+
+    // One entry per day
+    #[serde(rename = "90days", default)]
+    pub days_90: Vec<ItemStatistic>,
+}
+
+// Closed and live statistics share most fields, the rest is only set on one of them
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ItemStatistic {
+    #[serde(rename = "id")]
+    pub id: String,
+
+    #[serde(rename = "datetime")]
+    pub datetime: String,
+
+    #[serde(rename = "volume")]
+    pub volume: i64,
+
+    #[serde(rename = "min_price")]
+    pub min_price: f64,
+
+    #[serde(rename = "max_price")]
+    pub max_price: f64,
+
+    #[serde(rename = "avg_price")]
+    pub avg_price: f64,
+
+    #[serde(rename = "wa_price")]
+    pub wa_price: f64,
+
+    #[serde(rename = "median")]
+    pub median: f64,
+
+    #[serde(rename = "moving_avg", default)]
+    pub moving_avg: Option<f64>,
+
+    #[serde(rename = "open_price", default)]
+    pub open_price: Option<f64>,
+
+    #[serde(rename = "closed_price", default)]
+    pub closed_price: Option<f64>,
+
+    #[serde(rename = "donch_top", default)]
+    pub donch_top: Option<f64>,
+
+    #[serde(rename = "donch_bot", default)]
+    pub donch_bot: Option<f64>,
+
+    // Only set on live statistics, "buy" or "sell"
+    #[serde(rename = "order_type", default)]
+    pub order_type: Option<String>,
+
+    #[serde(rename = "mod_rank", default)]
+    pub mod_rank: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DropSource {
+    #[serde(rename = "id")]
+    pub id: String,
+
+    // "relic" or "mission"
+    #[serde(rename = "type")]
+    pub source_type: String,
+
+    #[serde(rename = "item")]
+    pub item: String,
+
+    #[serde(rename = "rarity", default)]
+    pub rarity: Option<String>,
+
+    // Set for relics
+    #[serde(rename = "relic", default)]
+    pub relic: Option<String>,
+
+    #[serde(rename = "rates", default)]
+    pub rates: Option<RelicDropRates>,
+
+    // Set for missions
+    #[serde(rename = "mission_id", default)]
+    pub mission_id: Option<String>,
+
+    #[serde(rename = "node_id", default)]
+    pub node_id: Option<String>,
+
+    #[serde(rename = "rate", default)]
+    pub rate: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RelicDropRates {
+    #[serde(rename = "intact")]
+    pub intact: f64,
+
+    #[serde(rename = "exceptional")]
+    pub exceptional: f64,
+
+    #[serde(rename = "flawless")]
+    pub flawless: f64,
+
+    #[serde(rename = "radiant")]
+    pub radiant: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct UserProfile {
+    #[serde(rename = "id")]
+    pub id: String,
+
+    #[serde(rename = "ingame_name")]
+    pub ingame_name: String,
+
+    #[serde(rename = "reputation")]
+    pub reputation: i64,
+
+    #[serde(rename = "status")]
+    pub status: String,
+
+    #[serde(rename = "platform")]
+    pub platform: String,
+
+    #[serde(rename = "region", default)]
+    pub region: Option<String>,
+
+    #[serde(rename = "avatar", default)]
+    pub avatar: Option<String>,
+
+    #[serde(rename = "about", default)]
+    pub about: Option<String>,
+
+    #[serde(rename = "last_seen", default)]
+    pub last_seen: Option<String>,
+
+    #[serde(rename = "banned", default)]
+    pub banned: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Review {
+    #[serde(rename = "id")]
+    pub id: String,
+
+    #[serde(rename = "text")]
+    pub text: String,
+
+    // "appreciation" or "complaint"
+    #[serde(rename = "review_type")]
+    pub review_type: String,
+
+    #[serde(rename = "date")]
+    pub date: String,
+
+    #[serde(rename = "user_from")]
+    pub user_from: User,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AuctionBid {
+    #[serde(rename = "id")]
+    pub id: String,
+
+    #[serde(rename = "auction")]
+    pub auction: String,
+
+    #[serde(rename = "value")]
+    pub value: i64,
+
+    #[serde(rename = "created")]
+    pub created: String,
+
+    #[serde(rename = "user")]
+    pub user: User,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ClosedOrders {
+    #[serde(rename = "closed_orders_sell", default)]
+    pub sell_orders: Vec<ClosedOrder>,
+
+    #[serde(rename = "closed_orders_buy", default)]
+    pub buy_orders: Vec<ClosedOrder>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ClosedOrder {
+    #[serde(rename = "id")]
+    pub id: String,
+
+    #[serde(rename = "platinum")]
+    pub platinum: i64,
+
+    #[serde(rename = "quantity")]
+    pub quantity: i64,
+
+    #[serde(rename = "closed_date", default)]
+    pub closed_date: Option<String>,
+
+    #[serde(rename = "mod_rank", default)]
+    pub mod_rank: Option<i64>,
+
+    #[serde(rename = "item")]
+    pub item: OrderItem,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    #[test]
+    fn item_statistics() {
+        let statistics: ItemStatistics = fixtures::read_payload("wfm/item_statistics.json", None);
+        assert_eq!(statistics.closed.hours_48.len(), 2);
+        assert_eq!(statistics.closed.days_90.len(), 2);
+        let day = &statistics.closed.days_90[1];
+        assert_eq!(day.volume, 118);
+        assert_eq!(day.median, 63.0);
+        assert_eq!(day.moving_avg, Some(62.1));
+        assert_eq!(day.order_type, None);
+        assert_eq!(statistics.closed.hours_48[0].moving_avg, None);
+        let live = &statistics.live.hours_48;
+        assert_eq!(live[0].order_type.as_deref(), Some("buy"));
+        assert_eq!(live[1].order_type.as_deref(), Some("sell"));
+        assert_eq!(live[1].open_price, None);
+    }
+
+    #[test]
+    fn item_dropsources() {
+        let sources: Vec<DropSource> =
+            fixtures::read_payload("wfm/item_dropsources.json", Some("dropsources"));
+        assert_eq!(sources.len(), 2);
+        let relic = &sources[0];
+        assert_eq!(relic.source_type, "relic");
+        assert_eq!(relic.relic.as_deref(), Some("5bc1ab93b919f200c18c10ef"));
+        assert_eq!(relic.rates.as_ref().map(|rates| rates.radiant), Some(20.0));
+        assert_eq!(relic.rate, None);
+        let mission = &sources[1];
+        assert_eq!(mission.source_type, "mission");
+        assert_eq!(mission.rate, Some(2.25));
+        assert!(mission.rates.is_none());
+    }
+
+    #[test]
+    fn user_profile() {
+        let profile: UserProfile = fixtures::read_payload("wfm/profile.json", Some("profile"));
+        assert_eq!(profile.ingame_name, "TraderOne");
+        assert_eq!(profile.reputation, 84);
+        assert_eq!(profile.status, "ingame");
+        assert_eq!(profile.region.as_deref(), Some("en"));
+        assert!(!profile.banned);
+    }
+
+    #[test]
+    fn reviews() {
+        let reviews: Vec<Review> = fixtures::read_payload("wfm/reviews.json", Some("reviews"));
+        assert_eq!(reviews.len(), 2);
+        assert_eq!(reviews[0].review_type, "appreciation");
+        assert_eq!(reviews[0].user_from.ingame_name, "HappyBuyer");
+        assert_eq!(reviews[1].review_type, "complaint");
+        assert_eq!(reviews[1].user_from.reputation, -2);
+    }
+
+    #[test]
+    fn auction_entry() {
+        let auction: Auction = fixtures::read_payload("wfm/auction_entry.json", Some("auction"));
+        assert_eq!(auction.owner.ingame_name, "TraderThree");
+        assert!(!auction.is_direct_sell);
+        assert_eq!(auction.starting_price, 120);
+        assert_eq!(auction.top_bid, Some(200));
+        assert_eq!(auction.buyout_price, None);
+        assert_eq!(auction.item.weapon_url_name, "soma");
+    }
+
+    #[test]
+    fn auction_bids() {
+        let bids: Vec<AuctionBid> = fixtures::read_payload("wfm/auction_bids.json", Some("bids"));
+        assert_eq!(bids.len(), 2);
+        assert_eq!(bids[1].value, 200);
+        assert_eq!(bids[1].user.ingame_name, "BidderTwo");
+        assert_eq!(bids[0].auction, bids[1].auction);
+    }
+
+    #[test]
+    fn closed_orders() {
+        let orders: ClosedOrders = fixtures::read_payload("wfm/closed_orders.json", None);
+        assert_eq!(orders.sell_orders.len(), 2);
+        assert_eq!(orders.buy_orders.len(), 1);
+        let arcane = &orders.sell_orders[1];
+        assert_eq!(arcane.item.url_name, "arcane_energize");
+        assert_eq!(arcane.mod_rank, Some(5));
+        assert_eq!(arcane.quantity, 2);
+        assert_eq!(orders.sell_orders[0].mod_rank, None);
+        assert_eq!(orders.buy_orders[0].item.ducats, Some(45));
+        assert_eq!(
+            orders.buy_orders[0].closed_date.as_deref(),
+            Some("2023-08-09T20:00:00.000+00:00")
+        );
+    }
+}
@@ -0,0 +1,147 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::{settings::TrustSettings, structs::OrderByItem};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ExcludedOrder {
+    pub order_id: String,
+    pub username: String,
+    pub order_type: String,
+    pub platinum: i64,
+    pub reason: String,
+}
+
+impl ExcludedOrder {
+    pub fn new(order: &OrderByItem, reason: &str) -> Self {
+        ExcludedOrder {
+            order_id: order.id.clone(),
+            username: order.user.ingame_name.clone(),
+            order_type: order.order_type.clone(),
+            platinum: order.platinum,
+            reason: reason.to_string(),
+        }
+    }
+}
+
+pub fn is_blocked(settings: &TrustSettings, username: &str) -> bool {
+    settings
+        .blocklist
+        .iter()
+        .any(|blocked| blocked.eq_ignore_ascii_case(username))
+}
+
+/// Returns why we refuse to trade with the user, None when the user can be trusted.
+pub fn get_untrusted_reason(
+    settings: &TrustSettings,
+    username: &str,
+    reputation: Option<i64>,
+) -> Option<String> {
+    if is_blocked(settings, username) {
+        return Some("blocked".to_string());
+    }
+    match reputation {
+        Some(reputation) if reputation < settings.min_reputation => Some(format!(
+            "reputation {} below {}",
+            reputation, settings.min_reputation
+        )),
+        _ => None,
+    }
+}
+
+/// Hours since the order was last updated, None when the date can not be parsed.
+pub fn get_order_age_hours(order: &OrderByItem, now: DateTime<Utc>) -> Option<f64> {
+    let last_update = DateTime::parse_from_rfc3339(&order.last_update).ok()?;
+    Some((now - last_update.with_timezone(&Utc)).num_minutes() as f64 / 60.0)
+}
+
+/// Removes the orders of blocked, low reputation and stale users. Orders of users below the
+/// trusted reputation are kept, unless they undercut the best trusted order by more than the
+/// allowed percentage. Our own orders are never removed.
+pub fn filter_orders(
+    orders: Vec<OrderByItem>,
+    settings: &TrustSettings,
+    own_name: &str,
+    now: DateTime<Utc>,
+) -> (Vec<OrderByItem>, Vec<ExcludedOrder>) {
+    let mut kept = vec![];
+    let mut excluded = vec![];
+    for order in orders {
+        if order.user.ingame_name == own_name {
+            kept.push(order);
+            continue;
+        }
+        if let Some(reason) = get_untrusted_reason(
+            settings,
+            &order.user.ingame_name,
+            Some(order.user.reputation),
+        ) {
+            excluded.push(ExcludedOrder::new(&order, &reason));
+            continue;
+        }
+        if settings.max_order_age_hours > 0 {
+            match get_order_age_hours(&order, now) {
+                Some(age) if age > settings.max_order_age_hours as f64 => {
+                    excluded.push(ExcludedOrder::new(
+                        &order,
+                        format!("not updated for {:.0} hours", age).as_str(),
+                    ));
+                    continue;
+                }
+                _ => {}
+            }
+        }
+        kept.push(order);
+    }
+
+    // Best ingame prices of the trusted users per mod rank, the untrusted ones are compared
+    // against them
+    let is_trusted = |order: &OrderByItem| {
+        order.user.reputation >= settings.trusted_reputation || order.user.ingame_name == own_name
+    };
+    let mut best_sell: HashMap<Option<i64>, i64> = HashMap::new();
+    let mut best_buy: HashMap<Option<i64>, i64> = HashMap::new();
+    for order in kept.iter().filter(|order| {
+        order.user.status == "ingame" && order.user.ingame_name != own_name && is_trusted(order)
+    }) {
+        if order.order_type == "sell" {
+            let best = best_sell.entry(order.mod_rank).or_insert(order.platinum);
+            *best = (*best).min(order.platinum);
+        } else {
+            let best = best_buy.entry(order.mod_rank).or_insert(order.platinum);
+            *best = (*best).max(order.platinum);
+        }
+    }
+    let tolerance = settings.max_untrusted_undercut_percent.max(0.0) / 100.0;
+
+    let (kept, undercutting): (Vec<OrderByItem>, Vec<OrderByItem>) =
+        kept.into_iter().partition(|order| {
+            if is_trusted(order) {
+                return true;
+            }
+            let price = order.platinum as f64;
+            if order.order_type == "sell" {
+                match best_sell.get(&order.mod_rank) {
+                    Some(best) => price >= *best as f64 * (1.0 - tolerance),
+                    None => true,
+                }
+            } else {
+                match best_buy.get(&order.mod_rank) {
+                    Some(best) => price <= *best as f64 * (1.0 + tolerance),
+                    None => true,
+                }
+            }
+        });
+    excluded.extend(undercutting.iter().map(|order| {
+        ExcludedOrder::new(
+            order,
+            format!(
+                "reputation {} below {} and beats the trusted price by too much",
+                order.user.reputation, settings.trusted_reputation
+            )
+            .as_str(),
+        )
+    }));
+    (kept, excluded)
+}
@@ -0,0 +1,547 @@
+use futures_util::{SinkExt, StreamExt};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+use tokio::sync::mpsc::{self, UnboundedSender};
+use tokio_tungstenite::{
+    connect_async,
+    tungstenite::{client::IntoClientRequest, http::HeaderValue, Message},
+};
+
+use crate::{
+    auth::AuthState,
+    helper, logger,
+    settings::SettingsState,
+    structs::{GlobleError, OrderByItem},
+};
+
+#[derive(Deserialize, Debug)]
+struct SocketOrderItem {
+    url_name: String,
+}
+
+// Orders pushed over the socket also carry the item they belong to
+#[derive(Deserialize, Debug)]
+struct SocketOrder {
+    #[serde(flatten)]
+    order: OrderByItem,
+    item: SocketOrderItem,
+}
+
+#[derive(Clone, Debug)]
+struct ItemOrderBook {
+    orders: Vec<OrderByItem>,
+    // When the book was last seeded from the HTTP API, the socket does not push removals
+    seeded_at: Instant,
+    // Orders that only came from the socket and when, they expire for the same reason
+    pushed_at: HashMap<String, Instant>,
+    changed: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct WFMSocketState {
+    log_file: String,
+    settings: Arc<Mutex<SettingsState>>,
+    auth: Arc<Mutex<AuthState>>,
+    is_running: Arc<AtomicBool>,
+    // Bumped by every start, a task of an earlier start stops once it sees a newer one
+    generation: Arc<AtomicU64>,
+    is_connected: Arc<AtomicBool>,
+    books: Arc<Mutex<HashMap<String, ItemOrderBook>>>,
+    status: Arc<Mutex<Option<String>>>,
+    // Status to apply as soon as the socket is connected
+    desired_status: Arc<Mutex<Option<String>>>,
+    sender: Arc<Mutex<Option<UnboundedSender<String>>>>,
+}
+
+impl WFMSocketState {
+    pub fn new(settings: Arc<Mutex<SettingsState>>, auth: Arc<Mutex<AuthState>>) -> Self {
+        WFMSocketState {
+            log_file: "wfm_socket.log".to_string(),
+            settings,
+            auth,
+            is_running: Arc::new(AtomicBool::new(false)),
+            generation: Arc::new(AtomicU64::new(0)),
+            is_connected: Arc::new(AtomicBool::new(false)),
+            books: Arc::new(Mutex::new(HashMap::new())),
+            status: Arc::new(Mutex::new(None)),
+            desired_status: Arc::new(Mutex::new(None)),
+            sender: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.is_connected.load(Ordering::SeqCst)
+    }
+
+    pub fn get_status(&self) -> Result<Option<String>, GlobleError> {
+        Ok(self.status.lock()?.clone())
+    }
+
+    fn is_current(&self, generation: u64) -> bool {
+        self.is_running.load(Ordering::SeqCst)
+            && self.generation.load(Ordering::SeqCst) == generation
+    }
+
+    /// Starts the socket in the background, it reconnects until `stop` is called.
+    pub fn start(&self) {
+        if self.is_running.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let generation = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        let socket = self.clone();
+        tauri::async_runtime::spawn(async move {
+            let mut backoff = 1;
+            while socket.is_current(generation) {
+                match socket.run(generation).await {
+                    Ok(_) => backoff = 1,
+                    Err(e) => {
+                        logger::error(
+                            "WFMSocket",
+                            format!("Connection: {:?}", e).as_str(),
+                            true,
+                            Some(socket.log_file.as_str()),
+                        );
+                    }
+                }
+                // A newer start owns the connection state
+                if socket.generation.load(Ordering::SeqCst) != generation {
+                    break;
+                }
+                socket.is_connected.store(false, Ordering::SeqCst);
+                helper::send_message_to_window("wfm_socket", Some(json!({ "connected": false })));
+                if !socket.is_current(generation) {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_secs(backoff)).await;
+                backoff = (backoff * 2).min(60);
+            }
+            logger::info_con("WFMSocket", "Socket stopped");
+        });
+    }
+
+    pub fn stop(&self) -> Result<(), GlobleError> {
+        self.is_running.store(false, Ordering::SeqCst);
+        // Dropping the sender ends the connection
+        *self.sender.lock()? = None;
+        self.books.lock()?.clear();
+        Ok(())
+    }
+
+    /// Connects and handles messages until the connection is closed. Returns right away when
+    /// the socket was stopped or started again while connecting.
+    async fn run(&self, generation: u64) -> Result<(), GlobleError> {
+        let settings = self.settings.lock()?.clone().websocket;
+        let auth = self.auth.lock()?.clone();
+        let url = format!("{}?platform={}", settings.endpoint, auth.platform);
+        let mut request = url
+            .into_client_request()
+            .map_err(|e| GlobleError::OtherError(format!("{:?}", e)))?;
+        if let Some(token) = auth.access_token {
+            let cookie = HeaderValue::from_str(format!("JWT={}", token).as_str())
+                .map_err(|e| GlobleError::OtherError(format!("{:?}", e)))?;
+            request.headers_mut().insert("Cookie", cookie);
+        }
+        let (mut stream, _response) = connect_async(request)
+            .await
+            .map_err(|e| GlobleError::OtherError(format!("{:?}", e)))?;
+        if !self.is_current(generation) {
+            let _ = stream.close(None).await;
+            return Ok(());
+        }
+        let (mut write, mut read) = stream.split();
+
+        let (sender, mut receiver) = mpsc::unbounded_channel::<String>();
+        sender
+            .send(json!({ "type": "@WS/SUBSCRIBE/MOST_RECENT" }).to_string())
+            .map_err(|e| GlobleError::OtherError(format!("{:?}", e)))?;
+        if let Some(status) = self.desired_status.lock()?.clone() {
+            sender
+                .send(get_status_message(&status))
+                .map_err(|e| GlobleError::OtherError(format!("{:?}", e)))?;
+        }
+        // Only the shared sender may be kept, the connection closes once stop drops it. Checked
+        // under the lock, so a stop either sees the sender or this sees the stop
+        {
+            let mut shared = self.sender.lock()?;
+            if !self.is_current(generation) {
+                return Ok(());
+            }
+            *shared = Some(sender);
+        }
+        self.is_connected.store(true, Ordering::SeqCst);
+        helper::send_message_to_window("wfm_socket", Some(json!({ "connected": true })));
+        logger::info_con("WFMSocket", "Connected");
+
+        loop {
+            tokio::select! {
+                outgoing = receiver.recv() => match outgoing {
+                    Some(text) => write
+                        .send(Message::Text(text))
+                        .await
+                        .map_err(|e| GlobleError::OtherError(format!("{:?}", e)))?,
+                    // The sender was dropped by stop
+                    None => {
+                        let _ = write.close().await;
+                        return Ok(());
+                    }
+                },
+                incoming = read.next() => match incoming {
+                    Some(Ok(Message::Text(text))) => {
+                        if let Err(e) = self.handle_message(&text) {
+                            logger::error(
+                                "WFMSocket",
+                                format!("Message: {}, Error: {:?}", text, e).as_str(),
+                                false,
+                                Some(self.log_file.as_str()),
+                            );
+                        }
+                    }
+                    Some(Ok(Message::Close(_))) | None => return Ok(()),
+                    Some(Ok(_)) => {}
+                    Some(Err(e)) => return Err(GlobleError::OtherError(format!("{:?}", e))),
+                },
+            }
+        }
+    }
+
+    /// Applies a single socket message to the order books and our status.
+    pub fn handle_message(&self, text: &str) -> Result<(), GlobleError> {
+        let message: Value = serde_json::from_str(text)?;
+        let message_type = message["type"].as_str().unwrap_or_default();
+        match message_type {
+            "@WS/SUBSCRIPTIONS/MOST_RECENT/NEW_ORDER" => {
+                let order: SocketOrder =
+                    serde_json::from_value(message["payload"]["order"].clone())?;
+                let mut books = self.books.lock()?;
+                // The order carries the current status of its user, which applies to the
+                // user's other orders too
+                let user = &order.order.user;
+                for book in books.values_mut() {
+                    for existing in book.orders.iter_mut() {
+                        if existing.user.id == user.id && existing.user.status != user.status {
+                            existing.user.status = user.status.clone();
+                            book.changed = true;
+                        }
+                    }
+                }
+                // Only items we watch have a book
+                if let Some(book) = books.get_mut(&order.item.url_name) {
+                    let id = order.order.id.clone();
+                    let seeded = !book.pushed_at.contains_key(&id)
+                        && book.orders.iter().any(|existing| existing.id == id);
+                    book.orders.retain(|existing| existing.id != id);
+                    if order.order.visible {
+                        if !seeded {
+                            book.pushed_at.insert(id, Instant::now());
+                        }
+                        book.orders.push(order.order);
+                    } else {
+                        book.pushed_at.remove(&id);
+                    }
+                    book.changed = true;
+                }
+            }
+            "@WS/USER/SET_STATUS" => {
+                let status = message["payload"].as_str().map(String::from);
+                helper::send_message_to_window("wfm_socket", Some(json!({ "status": status })));
+                *self.status.lock()? = status;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Sets our online status, one of "online", "ingame" or "invisible". When the socket is
+    /// not connected yet the status is applied once it is.
+    pub fn set_status(&self, status: &str) -> Result<(), GlobleError> {
+        *self.desired_status.lock()? = Some(status.to_string());
+        let sender = self.sender.lock()?.clone();
+        if let Some(sender) = sender {
+            sender
+                .send(get_status_message(status))
+                .map_err(|e| GlobleError::OtherError(format!("{:?}", e)))?;
+        }
+        Ok(())
+    }
+
+    /// Stops tracking order books, the connection stays open for the status.
+    pub fn clear_books(&self) -> Result<(), GlobleError> {
+        self.books.lock()?.clear();
+        Ok(())
+    }
+
+    /// Seeds the book of the item with orders from the HTTP API and starts watching it.
+    pub fn watch(&self, item: &str, orders: Vec<OrderByItem>) -> Result<(), GlobleError> {
+        self.books.lock()?.insert(
+            item.to_string(),
+            ItemOrderBook {
+                orders,
+                seeded_at: Instant::now(),
+                pushed_at: HashMap::new(),
+                changed: false,
+            },
+        );
+        Ok(())
+    }
+
+    /// Returns the book of the item if it is younger than `max_age`, together with whether it
+    /// changed since the last call. Orders pushed longer than `pushed_max_age` ago are dropped
+    /// first. None means the item has to be fetched over HTTP.
+    pub fn get_orders(
+        &self,
+        item: &str,
+        max_age: Duration,
+        pushed_max_age: Duration,
+    ) -> Result<Option<(Vec<OrderByItem>, bool)>, GlobleError> {
+        if !self.is_connected() {
+            return Ok(None);
+        }
+        let mut books = self.books.lock()?;
+        match books.get_mut(item) {
+            Some(book) if book.seeded_at.elapsed() < max_age => {
+                let expired: Vec<String> = book
+                    .pushed_at
+                    .iter()
+                    .filter(|(_, pushed_at)| pushed_at.elapsed() >= pushed_max_age)
+                    .map(|(id, _)| id.clone())
+                    .collect();
+                if !expired.is_empty() {
+                    book.orders.retain(|order| !expired.contains(&order.id));
+                    for id in expired.iter() {
+                        book.pushed_at.remove(id);
+                    }
+                    book.changed = true;
+                }
+                let changed = book.changed;
+                book.changed = false;
+                Ok(Some((book.orders.clone(), changed)))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+fn get_status_message(status: &str) -> String {
+    json!({ "type": "@WS/USER/SET_STATUS", "payload": status }).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::{net::TcpListener, task::JoinHandle};
+    use tokio_tungstenite::accept_async;
+
+    const ITEM: &str = "loki_prime_set";
+
+    /// Accepts one connection, waits for `expected` messages, then sends the messages and closes
+    /// when there are any. Returns every text message that was received.
+    async fn spawn_socket_server(
+        expected: usize,
+        messages: Vec<String>,
+    ) -> (String, JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("ws://{}", listener.local_addr().unwrap());
+        let handle = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut socket = accept_async(stream).await.unwrap();
+            let mut received = vec![];
+            while received.len() < expected {
+                match socket.next().await {
+                    Some(Ok(Message::Text(text))) => received.push(text),
+                    Some(Ok(_)) => {}
+                    _ => break,
+                }
+            }
+            if !messages.is_empty() {
+                for message in messages {
+                    socket.send(Message::Text(message)).await.unwrap();
+                }
+                let _ = socket.close(None).await;
+            }
+            while let Some(Ok(message)) = socket.next().await {
+                if let Message::Text(text) = message {
+                    received.push(text);
+                }
+            }
+            received
+        });
+        (endpoint, handle)
+    }
+
+    fn create_socket(endpoint: &str) -> WFMSocketState {
+        let mut settings = SettingsState::default();
+        settings.websocket.endpoint = endpoint.to_string();
+        WFMSocketState::new(
+            Arc::new(Mutex::new(settings)),
+            Arc::new(Mutex::new(AuthState::default())),
+        )
+    }
+
+    fn get_order(id: &str, user: &str, status: &str, platinum: i64) -> Value {
+        json!({
+            "order_type": "sell",
+            "quantity": 1,
+            "platinum": platinum,
+            "user": {
+                "reputation": 5,
+                "ingame_name": user,
+                "id": format!("user_{}", user),
+                "status": status,
+            },
+            "platform": "pc",
+            "creation_date": "2023-08-20T10:00:00.000+00:00",
+            "last_update": "2023-08-20T10:00:00.000+00:00",
+            "visible": true,
+            "id": id,
+            "region": "en",
+        })
+    }
+
+    fn get_new_order_message(order: Value, item: &str) -> String {
+        let mut order = order;
+        order["item"] = json!({ "url_name": item });
+        json!({
+            "type": "@WS/SUBSCRIPTIONS/MOST_RECENT/NEW_ORDER",
+            "payload": { "order": order },
+        })
+        .to_string()
+    }
+
+    fn seed(socket: &WFMSocketState, orders: Vec<Value>) {
+        let orders = orders
+            .into_iter()
+            .map(|order| serde_json::from_value(order).unwrap())
+            .collect();
+        socket.watch(ITEM, orders).unwrap();
+        socket.is_connected.store(true, Ordering::SeqCst);
+    }
+
+    fn get_ids(socket: &WFMSocketState, pushed_max_age: Duration) -> Option<(Vec<String>, bool)> {
+        socket
+            .get_orders(ITEM, Duration::from_secs(600), pushed_max_age)
+            .unwrap()
+            .map(|(orders, changed)| (orders.into_iter().map(|order| order.id).collect(), changed))
+    }
+
+    #[tokio::test]
+    async fn updates_books_and_status_from_server() {
+        let (endpoint, server) = spawn_socket_server(
+            2,
+            vec![
+                get_new_order_message(get_order("o2", "Seller", "ingame", 90), ITEM),
+                get_new_order_message(get_order("o3", "Seller", "ingame", 10), "other_item"),
+                json!({ "type": "@WS/USER/SET_STATUS", "payload": "ingame" }).to_string(),
+            ],
+        )
+        .await;
+        let socket = create_socket(&endpoint);
+        seed(&socket, vec![get_order("o1", "Other", "ingame", 100)]);
+        socket.is_running.store(true, Ordering::SeqCst);
+        socket.set_status("ingame").unwrap();
+
+        socket.run(0).await.unwrap();
+
+        let received = server.await.unwrap();
+        assert!(received[0].contains("@WS/SUBSCRIBE/MOST_RECENT"));
+        assert_eq!(received[1], get_status_message("ingame"));
+        assert_eq!(socket.get_status().unwrap(), Some("ingame".to_string()));
+        // Orders of items without a book are ignored
+        let (ids, changed) = get_ids(&socket, Duration::from_secs(300)).unwrap();
+        assert_eq!(ids, vec!["o1".to_string(), "o2".to_string()]);
+        assert!(changed);
+        let (_, changed) = get_ids(&socket, Duration::from_secs(300)).unwrap();
+        assert!(!changed);
+    }
+
+    #[tokio::test]
+    async fn stopped_while_connecting() {
+        let (endpoint, server) = spawn_socket_server(0, vec![]).await;
+        let socket = create_socket(&endpoint);
+        // Not running, like a stop that happened while connect_async was pending
+        socket.run(0).await.unwrap();
+        assert!(socket.sender.lock().unwrap().is_none());
+        assert!(server.await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn restarted_while_connecting() {
+        let (endpoint, server) = spawn_socket_server(0, vec![]).await;
+        let socket = create_socket(&endpoint);
+        socket.is_running.store(true, Ordering::SeqCst);
+        socket.generation.store(2, Ordering::SeqCst);
+        // The task of the first start must give way to the second one
+        socket.run(1).await.unwrap();
+        assert!(socket.sender.lock().unwrap().is_none());
+        assert!(server.await.unwrap().is_empty());
+    }
+
+    #[test]
+    fn pushed_orders_expire() {
+        let socket = create_socket("ws://127.0.0.1:1");
+        seed(&socket, vec![get_order("o1", "Other", "ingame", 100)]);
+        socket
+            .handle_message(&get_new_order_message(
+                get_order("o2", "Seller", "ingame", 90),
+                ITEM,
+            ))
+            .unwrap();
+        // A seeded order that is updated over the socket does not expire
+        socket
+            .handle_message(&get_new_order_message(
+                get_order("o1", "Other", "ingame", 95),
+                ITEM,
+            ))
+            .unwrap();
+        let (ids, changed) = get_ids(&socket, Duration::ZERO).unwrap();
+        assert_eq!(ids, vec!["o1".to_string()]);
+        assert!(changed);
+    }
+
+    #[test]
+    fn invisible_orders_are_removed() {
+        let socket = create_socket("ws://127.0.0.1:1");
+        seed(&socket, vec![get_order("o1", "Other", "ingame", 100)]);
+        let mut order = get_order("o1", "Other", "ingame", 100);
+        order["visible"] = json!(false);
+        socket
+            .handle_message(&get_new_order_message(order, ITEM))
+            .unwrap();
+        let (ids, changed) = get_ids(&socket, Duration::from_secs(300)).unwrap();
+        assert!(ids.is_empty());
+        assert!(changed);
+    }
+
+    #[test]
+    fn user_status_applies_to_other_orders() {
+        let socket = create_socket("ws://127.0.0.1:1");
+        seed(&socket, vec![get_order("o1", "Seller", "ingame", 100)]);
+        socket
+            .handle_message(&get_new_order_message(
+                get_order("o2", "Seller", "offline", 150),
+                "other_item",
+            ))
+            .unwrap();
+        let (orders, changed) = socket
+            .get_orders(ITEM, Duration::from_secs(600), Duration::from_secs(300))
+            .unwrap()
+            .unwrap();
+        assert_eq!(orders[0].user.status, "offline");
+        assert!(changed);
+    }
+
+    #[test]
+    fn disconnected_socket_has_no_books() {
+        let socket = create_socket("ws://127.0.0.1:1");
+        seed(&socket, vec![get_order("o1", "Other", "ingame", 100)]);
+        socket.is_connected.store(false, Ordering::SeqCst);
+        assert!(get_ids(&socket, Duration::from_secs(300)).is_none());
+    }
+}
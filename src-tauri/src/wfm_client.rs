@@ -1,4 +1,7 @@
-use std::sync::{Arc, Mutex};
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 
 use polars::{
     prelude::{DataFrame, NamedFrom},
@@ -11,14 +14,22 @@ use serde_json::{json, Value};
 use crate::{
     auth::AuthState,
     logger,
-    structs::{GlobleError, Item, ItemDetails, Order, OrderByItem, Ordres},
+    structs::{
+        Auction, AuctionBid, ClosedOrders, DropSource, GlobleError, Item, ItemDetails,
+        ItemStatistics, Order, OrderByItem, Ordres, Review, UserProfile,
+    },
 };
 
+// Warframe Market allows about 3 requests per second, we stay well below that
+const REQUEST_INTERVAL: Duration = Duration::from_secs(1);
+
 #[derive(Clone, Debug)]
 pub struct WFMClientState {
     endpoint: String,
     log_file: String,
     auth: Arc<Mutex<AuthState>>,
+    // Shared by every clone, so the live scraper and bulk commands are limited together
+    last_request: Arc<tokio::sync::Mutex<Option<Instant>>>,
 }
 
 impl WFMClientState {
@@ -27,8 +38,21 @@ impl WFMClientState {
             endpoint: "https://api.warframe.market/v1/".to_string(),
             log_file: "wfmAPICalls.log".to_string(),
             auth,
+            last_request: Arc::new(tokio::sync::Mutex::new(None)),
         }
     }
+
+    /// Waits until the request interval has passed since the last request of any caller.
+    async fn wait_for_rate_limit(&self) {
+        let mut last_request = self.last_request.lock().await;
+        if let Some(last) = *last_request {
+            let elapsed = last.elapsed();
+            if elapsed < REQUEST_INTERVAL {
+                tokio::time::sleep(REQUEST_INTERVAL - elapsed).await;
+            }
+        }
+        *last_request = Some(Instant::now());
+    }
     async fn send_request<T: DeserializeOwned>(
         &self,
         method: Method,
@@ -37,8 +61,8 @@ impl WFMClientState {
         body: Option<Value>,
     ) -> Result<(T, HeaderMap), GlobleError> {
         let auth = self.auth.lock()?.clone();
-        // Sleep for 1 seconds before sending a new request, to avoid 429 error
-        tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+        // Space the requests out, to avoid 429 error
+        self.wait_for_rate_limit().await;
         let client = Client::new();
         let new_url = format!("{}{}", self.endpoint, url);
 
@@ -71,14 +95,13 @@ impl WFMClientState {
             ));
         }
         if status != 200 {
-            let rep = response_data.text().await.unwrap();
-            return Err(GlobleError::OtherError(format!(
-                "URL: {}, Body: {}, Status: {}, Response: {}",
-                new_url,
-                body.unwrap_or(json!({})),
+            // Keep the status, server errors are retried by the live scraper
+            let rep = response_data.text().await.unwrap_or_default();
+            return Err(GlobleError::HttpError(
                 status,
-                rep
-            )));
+                format!("Body: {}, Response: {}", body.unwrap_or(json!({})), rep),
+                new_url,
+            ));
         }
 
         let headers = response_data.headers().clone();
@@ -493,7 +516,8 @@ impl WFMClientState {
         }
     }
 
-    pub async fn get_ordres_by_item(&self, item: &str) -> Result<DataFrame, GlobleError> {
+    /// Returns the full order book of the item, including offline users.
+    pub async fn get_item_orders(&self, item: &str) -> Result<Vec<OrderByItem>, GlobleError> {
         let url = format!("items/{}/orders", item);
 
         let orders: Vec<OrderByItem> = match self.get(&url, Some("orders")).await {
@@ -508,7 +532,19 @@ impl WFMClientState {
                 vec![]
             }
         };
+        Ok(orders)
+    }
+
+    pub async fn get_ordres_by_item(&self, item: &str) -> Result<DataFrame, GlobleError> {
+        let orders = self.get_item_orders(item).await?;
+        self.convert_item_orders_to_datafream(orders)
+    }
 
+    /// Reduces the order book to the ingame orders of the highest mod rank.
+    pub fn convert_item_orders_to_datafream(
+        &self,
+        orders: Vec<OrderByItem>,
+    ) -> Result<DataFrame, GlobleError> {
         if orders.len() == 0 {
             return Ok(DataFrame::new_no_checks(vec![]));
         }
@@ -565,6 +601,183 @@ impl WFMClientState {
         Ok(orders_df)
     }
 
+    /// Returns the lowest ingame sell price of the item at the given mod rank, excluding our
+    /// own orders. Items without ranks use rank 0.
+    pub async fn get_lowest_sell_price(
+        &self,
+        item: &str,
+        rank: i64,
+    ) -> Result<Option<i64>, GlobleError> {
+        let in_game_name = self.auth.lock()?.ingame_name.clone();
+        let orders = self.get_item_orders(item).await?;
+        Ok(get_lowest_sell_price(&orders, rank, &in_game_name))
+    }
+
+    /// Searches the riven auctions, `query` holds the query parameters of `auctions/search`
+    /// such as weapon_url_name, positive_stats and buyout_policy.
+    pub async fn search_riven_auctions(
+        &self,
+        query: Vec<(String, String)>,
+    ) -> Result<Vec<Auction>, GlobleError> {
+        let mut url = Url::parse(&format!("{}auctions/search", self.endpoint)).unwrap();
+        url.query_pairs_mut().append_pair("type", "riven");
+        for (key, value) in query.iter() {
+            url.query_pairs_mut().append_pair(key, value);
+        }
+        let url = format!("auctions/search?{}", url.query().unwrap_or_default());
+        match self.get(&url, Some("auctions")).await {
+            Ok((auctions, _headers)) => Ok(auctions),
+            Err(e) => {
+                logger::error(
+                    "WarframeMarket:SearchRivenAuctions",
+                    format!("Query: {:?}, Error: {:?}", query, e).as_str(),
+                    true,
+                    Some(self.log_file.as_str()),
+                );
+                Err(e)
+            }
+        }
+    }
+
+    // Get auctions of the logged in user from warframe market
+    pub async fn get_user_auctions(&self) -> Result<Vec<Auction>, GlobleError> {
+        let auth = self.auth.lock()?.clone();
+        let url = format!("profile/{}/auctions", auth.ingame_name.clone());
+        match self.get(&url, Some("auctions")).await {
+            Ok((auctions, _headers)) => Ok(auctions),
+            Err(e) => {
+                logger::error(
+                    "WarframeMarket:GetUserAuctions",
+                    format!("User: {}, Error: {:?}", auth.ingame_name, e).as_str(),
+                    true,
+                    Some(self.log_file.as_str()),
+                );
+                Err(e)
+            }
+        }
+    }
+
+    /// Returns the closed and live statistics of the item for the last 48 hours and 90 days.
+    pub async fn get_item_statistics(&self, item: &str) -> Result<ItemStatistics, GlobleError> {
+        let url = format!("items/{}/statistics", item);
+        match self.get(&url, None).await {
+            Ok((statistics, _headers)) => Ok(statistics),
+            Err(e) => {
+                logger::error(
+                    "WarframeMarket:GetItemStatistics",
+                    format!("Item: {}, Error: {:?}", item, e).as_str(),
+                    true,
+                    Some(self.log_file.as_str()),
+                );
+                Err(e)
+            }
+        }
+    }
+
+    pub async fn get_item_dropsources(&self, item: &str) -> Result<Vec<DropSource>, GlobleError> {
+        let url = format!("items/{}/dropsources", item);
+        match self.get(&url, Some("dropsources")).await {
+            Ok((dropsources, _headers)) => Ok(dropsources),
+            Err(e) => {
+                logger::error(
+                    "WarframeMarket:GetItemDropsources",
+                    format!("Item: {}, Error: {:?}", item, e).as_str(),
+                    true,
+                    Some(self.log_file.as_str()),
+                );
+                Err(e)
+            }
+        }
+    }
+
+    pub async fn get_user_profile(&self, user: &str) -> Result<UserProfile, GlobleError> {
+        let url = format!("profile/{}", user);
+        match self.get(&url, Some("profile")).await {
+            Ok((profile, _headers)) => Ok(profile),
+            Err(e) => {
+                logger::error(
+                    "WarframeMarket:GetUserProfile",
+                    format!("User: {}, Error: {:?}", user, e).as_str(),
+                    true,
+                    Some(self.log_file.as_str()),
+                );
+                Err(e)
+            }
+        }
+    }
+
+    /// Returns a page of the reviews of the user, pages start at 1.
+    pub async fn get_user_reviews(
+        &self,
+        user: &str,
+        page: i64,
+    ) -> Result<Vec<Review>, GlobleError> {
+        let url = format!("profile/{}/reviews/{}", user, page.max(1));
+        match self.get(&url, Some("reviews")).await {
+            Ok((reviews, _headers)) => Ok(reviews),
+            Err(e) => {
+                logger::error(
+                    "WarframeMarket:GetUserReviews",
+                    format!("User: {}, Page: {}, Error: {:?}", user, page, e).as_str(),
+                    true,
+                    Some(self.log_file.as_str()),
+                );
+                Err(e)
+            }
+        }
+    }
+
+    pub async fn get_auction(&self, auction_id: &str) -> Result<Auction, GlobleError> {
+        let url = format!("auctions/entry/{}", auction_id);
+        match self.get(&url, Some("auction")).await {
+            Ok((auction, _headers)) => Ok(auction),
+            Err(e) => {
+                logger::error(
+                    "WarframeMarket:GetAuction",
+                    format!("Auction: {}, Error: {:?}", auction_id, e).as_str(),
+                    true,
+                    Some(self.log_file.as_str()),
+                );
+                Err(e)
+            }
+        }
+    }
+
+    pub async fn get_auction_bids(&self, auction_id: &str) -> Result<Vec<AuctionBid>, GlobleError> {
+        let url = format!("auctions/entry/{}/bids", auction_id);
+        match self.get(&url, Some("bids")).await {
+            Ok((bids, _headers)) => Ok(bids),
+            Err(e) => {
+                logger::error(
+                    "WarframeMarket:GetAuctionBids",
+                    format!("Auction: {}, Error: {:?}", auction_id, e).as_str(),
+                    true,
+                    Some(self.log_file.as_str()),
+                );
+                Err(e)
+            }
+        }
+    }
+
+    /// Returns the closed order history of the user, the logged in user when `user` is None.
+    pub async fn get_closed_orders(&self, user: Option<&str>) -> Result<ClosedOrders, GlobleError> {
+        let auth = self.auth.lock()?.clone();
+        let user = user.unwrap_or(auth.ingame_name.as_str());
+        let url = format!("profile/{}/statistics", user);
+        match self.get(&url, None).await {
+            Ok((closed_orders, _headers)) => Ok(closed_orders),
+            Err(e) => {
+                logger::error(
+                    "WarframeMarket:GetClosedOrders",
+                    format!("User: {}, Error: {:?}", user, e).as_str(),
+                    true,
+                    Some(self.log_file.as_str()),
+                );
+                Err(e)
+            }
+        }
+    }
+
     pub async fn close_order_by_url(&self, item: &str) -> Result<String, GlobleError> {
         // Get the user orders and find the order
         let mut ordres_vec = self.get_user_ordres().await?;
@@ -580,14 +793,19 @@ impl WFMClientState {
             return Ok("No Order Found".to_string());
         }
 
-        let url = format!("profile/orders/close/{}", order.unwrap().id);
+        self.close_order(&order.unwrap().id).await
+    }
+
+    /// Closes one unit of the order, as if it was traded.
+    pub async fn close_order(&self, order_id: &str) -> Result<String, GlobleError> {
+        let url = format!("profile/orders/close/{}", order_id);
         let result: Result<(Option<String>, HeaderMap), GlobleError> =
             self.put(&url, Some("order_id"), None).await;
         match result {
             Ok((order_data, _headers)) => {
                 logger::info(
                     "WarframeMarket:CloseOrder",
-                    format!("Closed Order: {}", order.unwrap().id).as_str(),
+                    format!("Closed Order: {}", order_id).as_str(),
                     true,
                     Some(self.log_file.as_str()),
                 );
@@ -605,3 +823,122 @@ impl WFMClientState {
         }
     }
 }
+
+fn get_lowest_sell_price(orders: &[OrderByItem], rank: i64, own_name: &str) -> Option<i64> {
+    orders
+        .iter()
+        .filter(|order| {
+            order.order_type == "sell"
+                && order.user.status == "ingame"
+                && order.user.ingame_name != own_name
+                && order.mod_rank.unwrap_or(0) == rank
+        })
+        .map(|order| order.platinum)
+        .min()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::live_scraper::is_transient;
+    use crate::structs::User;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    /// A client whose endpoint answers every request with the given status line.
+    async fn client_with_status(status: &'static str) -> WFMClientState {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buffer = [0; 4096];
+            let _ = stream.read(&mut buffer).await;
+            let response = format!(
+                "HTTP/1.1 {}\r\nContent-Length: 4\r\nConnection: close\r\n\r\nfail",
+                status
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+        });
+        let mut client = WFMClientState::new(Arc::new(Mutex::new(AuthState::default())));
+        client.endpoint = format!("http://{}/", address);
+        client
+    }
+
+    #[tokio::test]
+    async fn server_error_is_transient() {
+        let client = client_with_status("503 Service Unavailable").await;
+        let error = client.get::<Value>("items", None).await.unwrap_err();
+        match &error {
+            GlobleError::HttpError(status, _, url) => {
+                assert_eq!(status.as_u16(), 503);
+                assert!(url.ends_with("/items"));
+            }
+            _ => panic!("Expected an http error, got {:?}", error),
+        }
+        assert!(is_transient(&error));
+    }
+
+    #[tokio::test]
+    async fn client_error_is_not_transient() {
+        let client = client_with_status("404 Not Found").await;
+        let error = client
+            .get::<Value>("items/unknown", None)
+            .await
+            .unwrap_err();
+        assert!(matches!(error, GlobleError::HttpError(status, _, _) if status.as_u16() == 404));
+        assert!(!is_transient(&error));
+    }
+
+    fn order(
+        user: &str,
+        status: &str,
+        order_type: &str,
+        rank: Option<i64>,
+        platinum: i64,
+    ) -> OrderByItem {
+        OrderByItem {
+            order_type: order_type.to_string(),
+            quantity: 1,
+            platinum,
+            mod_rank: rank,
+            user: User {
+                reputation: 10,
+                ingame_name: user.to_string(),
+                id: user.to_string(),
+                status: status.to_string(),
+            },
+            platform: "pc".to_string(),
+            creation_date: String::new(),
+            last_update: String::new(),
+            visible: true,
+            id: format!("{}_{}", user, platinum),
+            region: "en".to_string(),
+        }
+    }
+
+    #[test]
+    fn lowest_sell_price_of_the_rank() {
+        let orders = vec![
+            order("me", "ingame", "sell", Some(0), 10),
+            order("offline", "offline", "sell", Some(0), 12),
+            order("buyer", "ingame", "buy", Some(0), 30),
+            order("unranked", "ingame", "sell", Some(0), 20),
+            order("maxed", "ingame", "sell", Some(5), 200),
+            order("maxed_cheap", "ingame", "sell", Some(5), 150),
+        ];
+        assert_eq!(get_lowest_sell_price(&orders, 0, "me"), Some(20));
+        assert_eq!(get_lowest_sell_price(&orders, 5, "me"), Some(150));
+        assert_eq!(get_lowest_sell_price(&orders, 3, "me"), None);
+    }
+
+    #[test]
+    fn lowest_sell_price_without_ranks() {
+        let orders = vec![
+            order("a", "ingame", "sell", None, 40),
+            order("b", "ingame", "sell", None, 35),
+        ];
+        assert_eq!(get_lowest_sell_price(&orders, 0, "me"), Some(35));
+    }
+}
@@ -1,4 +1,9 @@
+use crate::database::DatabaseClient;
+use crate::notification::NotificationState;
 use crate::settings::SettingsState;
+use crate::status::StatusState;
+use crate::trust;
+use crate::wfm_client::WFMClientState;
 use crate::{helper, logger};
 use regex::Regex;
 use serde_json::json;
@@ -8,7 +13,7 @@ use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread::{self, JoinHandle};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 #[derive(Clone, serde::Serialize)]
 struct Payload {
@@ -22,48 +27,78 @@ pub struct WhisperScraper {
     last_file_size: Arc<Mutex<u64>>,
     handle: Arc<Mutex<Option<JoinHandle<()>>>>,
     settings: Arc<Mutex<SettingsState>>,
+    notifications: Arc<Mutex<NotificationState>>,
+    status: Arc<Mutex<StatusState>>,
+    wfm: Arc<Mutex<WFMClientState>>,
+    db: Arc<Mutex<DatabaseClient>>,
 }
 
 impl WhisperScraper {
-    pub fn new(settings: Arc<Mutex<SettingsState>>) -> Self {
+    pub fn new(
+        settings: Arc<Mutex<SettingsState>>,
+        notifications: Arc<Mutex<NotificationState>>,
+        status: Arc<Mutex<StatusState>>,
+        wfm: Arc<Mutex<WFMClientState>>,
+        db: Arc<Mutex<DatabaseClient>>,
+    ) -> Self {
         Self {
             is_running: Arc::new(AtomicBool::new(false)),
             log_path: helper::get_app_local_path().join("Warframe").join("EE.log"),
             last_file_size: Arc::new(Mutex::new(0)),
             handle: Arc::new(Mutex::new(None)),
             settings,
+            notifications,
+            status,
+            wfm,
+            db,
         }
     }
 
     pub fn start_loop(&mut self) {
         let is_running = Arc::clone(&self.is_running);
-        let settings = Arc::clone(&self.settings).lock().unwrap().clone();
+        let notifications = Arc::clone(&self.notifications).lock().unwrap().clone();
+        let status = Arc::clone(&self.status).lock().unwrap().clone();
+        let settings = Arc::clone(&self.settings);
         let scraper = self.clone();
 
         self.is_running.store(true, Ordering::SeqCst);
 
         let handle = thread::spawn(move || {
             let mut is_starting = false;
+            let mut game_running = false;
+            let mut last_activity = Instant::now();
             while is_running.load(Ordering::SeqCst) {
                 let new_lines_result = scraper.read_new_lines();
                 match new_lines_result {
                     Ok(new_lines) => {
+                        // Lines written before we started do not mean the game is running
+                        if is_starting && !new_lines.is_empty() {
+                            last_activity = Instant::now();
+                            if !game_running {
+                                game_running = true;
+                                scraper.on_game_state_changed(&status, true);
+                            }
+                        }
                         for line in new_lines {
+                            if game_running && line.contains("Main Shutdown Initiated") {
+                                game_running = false;
+                                scraper.on_game_state_changed(&status, false);
+                            }
                             match WhisperScraper::match_pattern(&line) {
                                 Ok((matched, group1)) => {
                                     if matched && is_starting {
-                                        helper::send_message_to_window(
-                                            "whisper_scraper_mesage_from_player",
-                                            Some(json!({"name": group1.clone().unwrap()})),
-                                        );
-                                        if settings.webhook != "" {
-                                            helper::send_message_to_discord(
-                                                settings.webhook.clone(),
-                                                format!(
-                                                    "You have whisper(s) from {}",
-                                                    group1.unwrap().as_str()
-                                                ),
-                                                settings.ping_on_notif,
+                                        let name = group1.unwrap_or_default();
+                                        scraper.record_whisper(&name);
+                                        if scraper.is_trusted_player(&name) {
+                                            helper::send_message_to_window(
+                                                "whisper_scraper_mesage_from_player",
+                                                Some(json!({ "name": name.clone() })),
+                                            );
+                                            notifications.notify(
+                                                "whisper",
+                                                "New whisper",
+                                                format!("You have whisper(s) from {}", name)
+                                                    .as_str(),
                                             );
                                         }
                                     }
@@ -86,6 +121,13 @@ impl WhisperScraper {
                     Err(err) => eprintln!("Error: {:?}", err),
                 }
 
+                let idle_minutes = settings.lock().unwrap().status.idle_minutes.max(1) as u64;
+                if game_running && last_activity.elapsed() > Duration::from_secs(idle_minutes * 60)
+                {
+                    game_running = false;
+                    scraper.on_game_state_changed(&status, false);
+                }
+
                 is_starting = true;
 
                 thread::sleep(Duration::from_secs(1));
@@ -95,6 +137,58 @@ impl WhisperScraper {
         *self.handle.lock().unwrap() = Some(handle);
     }
 
+    fn on_game_state_changed(&self, status: &StatusState, running: bool) {
+        helper::send_message_to_window(
+            "whisper_scraper_game_state",
+            Some(json!({ "running": running })),
+        );
+        let result = if running {
+            status.on_game_started()
+        } else {
+            status.on_game_closed()
+        };
+        if let Err(err) = result {
+            logger::error("WhisperScraper", format!("{:?}", err).as_str(), true, None);
+        }
+    }
+
+    /// Keeps the whisper in the whisper history, used to find players that never answer.
+    fn record_whisper(&self, name: &str) {
+        let db = self.db.lock().unwrap().clone();
+        if let Err(err) = tauri::async_runtime::block_on(db.create_whisper_entry(name, "received"))
+        {
+            logger::error("WhisperScraper", format!("{:?}", err).as_str(), true, None);
+        }
+    }
+
+    /// Checks the player against the blocklist and, when a minimum reputation is set, against
+    /// the reputation of their Warframe Market profile.
+    fn is_trusted_player(&self, name: &str) -> bool {
+        let settings = self.settings.lock().unwrap().trust.clone();
+        let reputation = if settings.min_reputation > 0 && !trust::is_blocked(&settings, name) {
+            let wfm = self.wfm.lock().unwrap().clone();
+            // Players without a profile can not be checked, so they are let through
+            match tauri::async_runtime::block_on(wfm.get_user_profile(name)) {
+                Ok(profile) => Some(profile.reputation),
+                Err(_) => None,
+            }
+        } else {
+            None
+        };
+        match trust::get_untrusted_reason(&settings, name, reputation) {
+            Some(reason) => {
+                logger::info(
+                    "WhisperScraper",
+                    format!("Ignored whisper from {}: {}", name, reason).as_str(),
+                    true,
+                    None,
+                );
+                false
+            }
+            None => true,
+        }
+    }
+
     pub fn stop_loop(&self) {
         self.is_running.store(false, Ordering::SeqCst);
     }
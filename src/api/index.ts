@@ -1,9 +1,32 @@
-import { SetupResponse, Wfm, InventoryEntryDto, TransactionEntryDto, Settings } from '../types'
+import { SetupResponse, Wfm, InventoryEntryDto, TransactionEntryDto, Settings, SettingsFieldError, SettingsPreset, SettingsPreview } from '../types'
 import { invoke } from '@tauri-apps/api';
 const api = {
   base: {
-    updatesettings: async (settings: Settings): Promise<Settings | undefined> => {
+    // Returns the field errors, the settings are only saved when there are none
+    updatesettings: async (settings: Settings): Promise<SettingsFieldError[]> => {
       return await invoke("update_settings", { settings })
+    },
+    validatesettings: async (settings: Settings): Promise<SettingsFieldError[]> => {
+      return await invoke("validate_settings", { settings })
+    },
+    // Presets and imports are previewed first, apply them with updatesettings(preview.settings)
+    getpresets: async (): Promise<SettingsPreset[]> => {
+      return await invoke("get_settings_presets")
+    },
+    savepreset: async (name: string, description: string): Promise<SettingsPreset> => {
+      return await invoke("save_settings_preset", { name, description })
+    },
+    deletepreset: async (name: string): Promise<void> => {
+      return await invoke("delete_settings_preset", { name })
+    },
+    previewpreset: async (name: string): Promise<SettingsPreview> => {
+      return await invoke("preview_settings_preset", { name })
+    },
+    exportsettings: async (): Promise<string> => {
+      return await invoke("export_settings")
+    },
+    previewimport: async (json: string): Promise<SettingsPreview> => {
+      return await invoke("preview_settings_import", { json })
     }
   },
   auth: {
@@ -41,6 +64,12 @@ const api = {
   },
   orders: {
   },
+  whispers: {
+    // Outgoing whispers are recorded so players that never answer can be left out of pricing
+    async recordWhisperSent(username: string): Promise<void> {
+      return await invoke("record_whisper_sent", { username });
+    },
+  },
 }
 
 export default api
@@ -0,0 +1,78 @@
+import { Button, Group, NumberInput, Stack, TextInput, Title } from '@mantine/core';
+import { useForm } from '@mantine/form';
+import { useClipboard } from '@mantine/hooks';
+import { notifications } from '@mantine/notifications';
+import { FontAwesomeIcon } from '@fortawesome/react-fontawesome';
+import { faCheck } from '@fortawesome/free-solid-svg-icons';
+import { useTranslateComponent, useTranslateSuccess } from '@hooks/index';
+import { SearchItemField } from './searchItemField';
+import api from '@api/index';
+
+// The message Warframe Market copies when you contact a player
+const createWhisper = (player: string, type: string, item: string, price: number) =>
+  `/w ${player} Hi! I want to ${type}: "${item}" for ${price} platinum. (warframe.market)`
+
+export const WhisperPlayer = () => {
+  const useTranslate = (key: string, context?: { [key: string]: any }) => useTranslateComponent(`whisperPlayer.${key}`, { ...context })
+  const useTranslateWhisperSuccess = (key: string, context?: { [key: string]: any }) => useTranslateSuccess(`whisper.${key}`, { ...context })
+  const clipboard = useClipboard();
+  const whisperForm = useForm({
+    initialValues: {
+      player: "",
+      item: "",
+      item_name: "",
+      price: 0,
+      type: "buy"
+    },
+    validate: {
+      player: (val) => (val.trim().length <= 0 ? ('player_required') : null),
+    },
+  });
+  return (
+    <Stack mt="md">
+      <Title order={4}>{useTranslate('title')}</Title>
+      <form method="post" onSubmit={whisperForm.onSubmit(async (d) => {
+        const player = d.player.trim();
+        clipboard.copy(createWhisper(player, d.type, d.item_name, d.price));
+        // Recorded when copied, the whisper scraper records the answer
+        await api.whispers.recordWhisperSent(player);
+        notifications.show({
+          title: useTranslateWhisperSuccess("copied_title"),
+          icon: <FontAwesomeIcon icon={faCheck} />,
+          message: useTranslateWhisperSuccess("copied_message", { name: player }),
+          color: "green"
+        });
+      })}>
+        <Stack spacing="md">
+          <TextInput
+            required
+            label={useTranslate('player')}
+            description={useTranslate('player_description')}
+            value={whisperForm.values.player}
+            onChange={(event) => whisperForm.setFieldValue('player', event.currentTarget.value)}
+          />
+          <SearchItemField value={whisperForm.values.item} onChange={(value) => {
+            whisperForm.setFieldValue('item', value.url_name)
+            whisperForm.setFieldValue('item_name', value.item_name)
+          }} />
+          <NumberInput
+            required
+            label={useTranslate('price')}
+            description={useTranslate('price_description')}
+            value={whisperForm.values.price}
+            min={0}
+            onChange={(value) => whisperForm.setFieldValue('price', Number(value))}
+          />
+          <Group position="center">
+            <Button type="submit" onClick={() => whisperForm.setFieldValue('type', "buy")} disabled={whisperForm.values.item.length <= 0} radius="xl">
+              {useTranslate('buttons.buy')}
+            </Button>
+            <Button type="submit" onClick={() => whisperForm.setFieldValue('type', "sell")} disabled={whisperForm.values.item.length <= 0} radius="xl">
+              {useTranslate('buttons.sell')}
+            </Button>
+          </Group>
+        </Stack>
+      </form>
+    </Stack>
+  );
+}
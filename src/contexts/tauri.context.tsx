@@ -84,8 +84,17 @@ export const TauriContextProvider = ({ children }: TauriContextProviderProps) =>
   const handleUpdateSettings = async (settingsData: Partial<Settings>) => {
     if (!settings) return;
     const data = { ...settings, ...settingsData } as Settings;
+    const errors = await api.base.updatesettings(data as any); // add 'as any' to avoid type checking
+    if (errors.length > 0) {
+      notifications.show({
+        title: 'Invalid Settings',
+        message: errors.map((error) => `${error.field}: ${error.message}`).join("\n"),
+        color: 'red',
+        autoClose: 5000,
+      });
+      return;
+    }
     setSettings(data);
-    await api.base.updatesettings(data as any); // add 'as any' to avoid type checking
   }
 
   const handleSendNotification = async (title: string, body: string) => {
@@ -73,6 +73,17 @@ export const en = {
       wisper_start: "Start Whisper",
       wisper_stop: "Stop Whisper",
     },
+    whisperPlayer: {
+      title: "Whisper Player",
+      player: "Player",
+      player_description: "In game name of the player",
+      price: "Price",
+      price_description: "Price per unit",
+      buttons: {
+        buy: "Copy Buy Whisper",
+        sell: "Copy Sell Whisper",
+      },
+    },
     modals: {
       prompt: {
         confirmLabel: "Confirm",
@@ -184,6 +195,10 @@ export const en = {
       delete_message: "Item {{name}} deleted successfully",
       sell_title: "Item sold",
       sell_message: "Item {{name}} sold successfully for {{price}}",
+    },
+    whisper: {
+      copied_title: "Whisper copied",
+      copied_message: "Paste the whisper to {{name}} in game",
     }
   },
   error: {}
@@ -1,6 +1,7 @@
 import { Grid } from "@mantine/core";
 import { Inventory } from "../../components/inventory";
 import { TransactionControl } from "../../components/transactionControl";
+import { WhisperPlayer } from "../../components/whisperPlayer";
 
 export default function LiveTradingPage() {
   return (
@@ -10,6 +11,7 @@ export default function LiveTradingPage() {
       </Grid.Col>
       <Grid.Col md={4}>
         <TransactionControl />
+        <WhisperPlayer />
       </Grid.Col>
     </Grid>
   );
@@ -6,6 +6,7 @@ export interface InventoryEntryDto {
   rank: number;
   price: number;
   listed_price?: number | null;
+  listed_at?: string | null;
   owned: number;
 }
 export interface TransactionEntryDto {
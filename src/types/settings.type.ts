@@ -8,6 +8,7 @@ export interface SetupResponse {
 }
 
 export interface Settings {
+  version?: number;
   volume_threshold: number;
   max_total_price_cap: number;
   range_threshold: number;
@@ -18,6 +19,182 @@ export interface Settings {
   webhook: string;
   blacklist: string[];
   whitelist: string[];
+  notifications: NotificationSettings;
+  alert_rules: AlertRule[];
+  arbitrage: ArbitrageSettings;
+  ducats: DucatSettings;
+  order_book: OrderBookSettings;
+  forecast: ForecastSettings;
+  scoring: ScoringSettings;
+  websocket: WebsocketSettings;
+  status: StatusSettings;
+  live_scraper: LiveScraperSettings;
+  price_source: PriceSourceSettings;
+  trust: TrustSettings;
+  outliers: OutlierSettings;
+  repricing: RepricingSettings;
+  allocation: AllocationSettings;
+  scheduler: SchedulerSettings;
+}
+
+export interface SchedulerSettings {
+  enabled: boolean;
+  hot_interval_seconds: number;
+  warm_interval_seconds: number;
+  cold_interval_seconds: number;
+  hot_profit: number;
+  warm_profit: number;
+  min_loop_seconds: number;
+  max_idle_seconds: number;
+}
+
+export type AllocationMethod = "scaled_dp" | "greedy";
+
+export interface AllocationSettings {
+  method: AllocationMethod;
+  budget_steps: number;
+  min_profit: number;
+  priorities: Record<string, number>;
+}
+
+export interface SettingsFieldError {
+  field: string;
+  message: string;
+}
+
+export interface SettingsPreset {
+  name: string;
+  description: string;
+  builtin: boolean;
+  settings: Partial<Settings>;
+}
+
+export interface SettingsChange {
+  field: string;
+  old: unknown;
+  new: unknown;
+}
+
+export interface SettingsPreview {
+  settings: Settings;
+  changes: SettingsChange[];
+  errors: SettingsFieldError[];
+}
+
+export interface RepricingSettings {
+  enabled: boolean;
+  grace_hours: number;
+  sell_decay_per_day: number;
+  sell_floor_profit: number;
+  buy_increase_per_day: number;
+  buy_ceiling_profit: number;
+}
+
+export interface OutlierSettings {
+  enabled: boolean;
+  iqr_multiplier: number;
+  z_score_threshold: number;
+  min_std_percent: number;
+  stale_order_hours: number;
+  unanswered_whispers: number;
+  whisper_response_hours: number;
+}
+
+export interface TrustSettings {
+  min_reputation: number;
+  trusted_reputation: number;
+  max_untrusted_undercut_percent: number;
+  max_order_age_hours: number;
+  blocklist: string[];
+}
+
+export type PriceSourceKind = "relics_run" | "warframe_market";
+
+export interface PriceSourceSettings {
+  primary: PriceSourceKind;
+  fallback?: PriceSourceKind;
+  merge: boolean;
+  max_statistics_items: number;
+}
+
+export interface LiveScraperSettings {
+  delete_orders_on_start: boolean;
+  delete_orders_on_stop: boolean;
+  max_retries: number;
+  backoff_seconds: number;
+  max_backoff_seconds: number;
+}
+
+export interface StatusSettings {
+  automate: boolean;
+  idle_minutes: number;
+}
+
+export interface WebsocketSettings {
+  enabled: boolean;
+  endpoint: string;
+  full_refresh_minutes: number;
+  pushed_order_minutes: number;
+}
+
+export interface ScoringSettings {
+  sort_by_score: boolean;
+  min_score: number;
+  margin_weight: number;
+  volume_weight: number;
+  volatility_weight: number;
+  time_to_sell_weight: number;
+  competition_weight: number;
+}
+
+export interface ForecastSettings {
+  use_in_strategy: boolean;
+  horizon_days: number;
+  ewma_alpha: number;
+  moving_average_days: number;
+}
+
+export interface OrderBookSettings {
+  enabled: boolean;
+  interval_minutes: number;
+  retention_days: number;
+}
+
+export interface ArbitrageSettings {
+  feed_live_scraper: boolean;
+  min_profit: number;
+  max_sets: number;
+}
+
+export interface DucatSettings {
+  plat_per_ducat: number;
+  min_ducats_per_plat: number;
+  min_ducats: number;
+}
+
+export interface AlertRule {
+  id: string;
+  enabled: boolean;
+  item?: string;
+  cooldown_minutes: number;
+  condition: "sells_below" | "spread_above" | "price_shift_above" | "listed_below_my_buy";
+  price?: number;
+  spread?: number;
+  percent?: number;
+}
+
+export interface NotificationSettings {
+  rate_limit_seconds: number;
+  sinks: NotificationSinkSettings[];
+}
+
+export interface NotificationSinkSettings {
+  name: string;
+  enabled: boolean;
+  events: string[];
+  rate_limit_seconds?: number;
+  type: "discord" | "webhook" | "ntfy" | "gotify" | "email" | "desktop";
+  [key: string]: any;
 }
 
 export interface CacheBase {